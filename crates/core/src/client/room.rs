@@ -343,6 +343,19 @@ pub struct ReportContentReqBody {
     pub reason: Option<String>,
 }
 
+/// `POST /_matrix/client/*/rooms/{room_id}/report`
+///
+/// Report a room as inappropriate.
+/// `/v3/` ([spec])
+///
+/// [spec]: https://spec.matrix.org/latest/client-server-api/#post_matrixclientv3roomsroomidreport
+#[derive(ToSchema, Deserialize, Debug)]
+pub struct ReportRoomReqBody {
+    /// The reason the room is being reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
 // const METADATA: Metadata = metadata! {
 //     method: POST,
 //     rate_limited: true,
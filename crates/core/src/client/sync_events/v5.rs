@@ -13,6 +13,8 @@ use serde::de::Error as _;
 use serde::{Deserialize, Serialize};
 
 use super::UnreadNotificationsCount;
+#[cfg(feature = "unstable-msc4308")]
+use crate::client::room::{ThreadSubscription, ThreadUnsubscription};
 use crate::device::DeviceLists;
 use crate::directory::RoomTypeFilter;
 use crate::events::receipt::SyncReceiptEvent;
@@ -508,6 +510,17 @@ pub struct ExtensionsConfig {
     )]
     pub profiles: ProfilesConfig,
 
+    /// Configure the thread subscriptions extension.
+    ///
+    /// Uses the unstable field name until MSC4308 is accepted.
+    #[cfg(feature = "unstable-msc4308")]
+    #[serde(
+        default,
+        rename = "io.element.msc4308.thread_subscriptions",
+        skip_serializing_if = "ThreadSubscriptionsConfig::is_empty"
+    )]
+    pub thread_subscriptions: ThreadSubscriptionsConfig,
+
     /// Extensions may add further fields to the list.
     #[serde(flatten)]
     #[salvo(schema(value_type = Object, additional_properties = true))]
@@ -527,6 +540,9 @@ impl ExtensionsConfig {
         #[cfg(feature = "unstable-msc4262")]
         let empty = empty && self.profiles.is_empty();
 
+        #[cfg(feature = "unstable-msc4308")]
+        let empty = empty && self.thread_subscriptions.is_empty();
+
         empty
     }
 }
@@ -564,6 +580,17 @@ pub struct Extensions {
         skip_serializing_if = "Profiles::is_empty"
     )]
     pub profiles: Profiles,
+
+    /// Thread subscriptions extension in response.
+    ///
+    /// Uses the unstable field name until MSC4308 is accepted.
+    #[cfg(feature = "unstable-msc4308")]
+    #[serde(
+        default,
+        rename = "io.element.msc4308.thread_subscriptions",
+        skip_serializing_if = "ThreadSubscriptions::is_empty"
+    )]
+    pub thread_subscriptions: ThreadSubscriptions,
 }
 
 impl Extensions {
@@ -583,6 +610,9 @@ impl Extensions {
         #[cfg(feature = "unstable-msc4262")]
         let empty = empty && self.profiles.is_empty();
 
+        #[cfg(feature = "unstable-msc4308")]
+        let empty = empty && self.thread_subscriptions.is_empty();
+
         empty
     }
 
@@ -600,6 +630,9 @@ impl Extensions {
         #[cfg(feature = "unstable-msc4262")]
         let empty = empty && self.profiles.is_empty();
 
+        #[cfg(feature = "unstable-msc4308")]
+        let empty = empty && self.thread_subscriptions.is_empty();
+
         empty
     }
 }
@@ -701,6 +734,59 @@ impl Profiles {
     }
 }
 
+/// Thread subscriptions extension configuration.
+///
+/// According to [MSC4308](https://github.com/matrix-org/matrix-spec-proposals/pull/4308).
+#[cfg(feature = "unstable-msc4308")]
+#[derive(ToSchema, Clone, Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct ThreadSubscriptionsConfig {
+    /// Activate or deactivate this extension. Sticky.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub enabled: Option<bool>,
+
+    /// Max number of thread subscription changes per response.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub limit: Option<usize>,
+}
+
+#[cfg(feature = "unstable-msc4308")]
+impl ThreadSubscriptionsConfig {
+    /// Whether all fields are empty or `None`.
+    pub fn is_empty(&self) -> bool {
+        self.enabled.is_none() && self.limit.is_none()
+    }
+}
+
+/// Thread subscriptions extension response data.
+///
+/// According to [MSC4308](https://github.com/matrix-org/matrix-spec-proposals/pull/4308).
+#[cfg(feature = "unstable-msc4308")]
+#[derive(ToSchema, Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ThreadSubscriptions {
+    /// New thread subscriptions, keyed by room and thread root.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[salvo(schema(value_type = Object, additional_properties = true))]
+    pub subscribed: BTreeMap<OwnedRoomId, BTreeMap<OwnedEventId, ThreadSubscription>>,
+
+    /// New thread unsubscriptions, keyed by room and thread root.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    #[salvo(schema(value_type = Object, additional_properties = true))]
+    pub unsubscribed: BTreeMap<OwnedRoomId, BTreeMap<OwnedEventId, ThreadUnsubscription>>,
+
+    /// Token to fetch older changes through `/thread_subscriptions` when the response was
+    /// truncated by `limit`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prev_batch: Option<String>,
+}
+
+#[cfg(feature = "unstable-msc4308")]
+impl ThreadSubscriptions {
+    /// Whether the extension carries no thread subscription changes.
+    pub fn is_empty(&self) -> bool {
+        self.subscribed.is_empty() && self.unsubscribed.is_empty()
+    }
+}
+
 /// To-device messages extension configuration.
 ///
 /// According to [MSC3885](https://github.com/matrix-org/matrix-spec-proposals/pull/3885).
@@ -1039,9 +1125,13 @@ impl Typing {
 mod tests {
     use std::collections::BTreeMap;
 
+    #[cfg(feature = "unstable-msc4308")]
+    use super::ThreadSubscriptions;
     use super::{E2ee, Extensions, ExtensionsConfig, SyncEventsResBody, Typing, TypingConfig};
     #[cfg(feature = "unstable-msc4262")]
     use super::{ExtensionRoomConfig, Profiles, ProfilesConfig};
+    #[cfg(feature = "unstable-msc4308")]
+    use crate::client::room::{ThreadSubscription, ThreadUnsubscription};
     use crate::events::typing::{SyncTypingEvent, TypingEventContent};
     #[cfg(feature = "unstable-msc4262")]
     use crate::profile::{ProfileFieldName, UserProfileUpdate};
@@ -1222,6 +1312,52 @@ mod tests {
         );
     }
 
+    #[cfg(feature = "unstable-msc4308")]
+    #[test]
+    fn thread_subscriptions_extension_uses_msc4308_shape() {
+        let room_id = RoomId::parse("!room:example.org").unwrap().to_owned();
+        let thread_id = crate::EventId::parse("$thread:example.org")
+            .unwrap()
+            .to_owned();
+        let old_thread_id = crate::EventId::parse("$old:example.org")
+            .unwrap()
+            .to_owned();
+        let extensions = Extensions {
+            thread_subscriptions: ThreadSubscriptions {
+                subscribed: BTreeMap::from([(
+                    room_id.clone(),
+                    BTreeMap::from([(thread_id, ThreadSubscription::new(true, 12))]),
+                )]),
+                unsubscribed: BTreeMap::from([(
+                    room_id,
+                    BTreeMap::from([(old_thread_id, ThreadUnsubscription::new(9))]),
+                )]),
+                prev_batch: Some("9".to_owned()),
+            },
+            ..Default::default()
+        };
+
+        assert!(!extensions.is_empty());
+        assert_eq!(
+            serde_json::to_value(extensions).unwrap(),
+            serde_json::json!({
+                "io.element.msc4308.thread_subscriptions": {
+                    "subscribed": {
+                        "!room:example.org": {
+                            "$thread:example.org": { "automatic": true, "bump_stamp": 12 }
+                        }
+                    },
+                    "unsubscribed": {
+                        "!room:example.org": {
+                            "$old:example.org": { "bump_stamp": 9 }
+                        }
+                    },
+                    "prev_batch": "9"
+                }
+            })
+        );
+    }
+
     #[cfg(feature = "unstable-msc4262")]
     #[test]
     fn selector_only_profiles_config_is_not_empty() {
@@ -8,7 +8,7 @@
 use std::time::Duration;
 
 use salvo::prelude::*;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::OwnedServerName;
 use crate::authentication::TokenType;
@@ -64,3 +64,16 @@ impl RequstOpenidTokenResBody {
         }
     }
 }
+
+/// `POST /_matrix/client/*/users/{user_id}/report`
+///
+/// Report a user as inappropriate.
+/// `/v3/` ([spec])
+///
+/// [spec]: https://spec.matrix.org/latest/client-server-api/#post_matrixclientv3usersuseridreport
+#[derive(ToSchema, Deserialize, Debug)]
+pub struct ReportUserReqBody {
+    /// The reason the user is being reported.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
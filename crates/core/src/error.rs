@@ -212,6 +212,8 @@ impl Scribe for MatrixError {
                     UserDeactivated | UserLocked | UserSuspended => StatusCode::FORBIDDEN,
                     TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
                     CannotOverwriteMedia => StatusCode::CONFLICT,
+                    #[cfg(feature = "unstable-msc4306")]
+                    ConflictingUnsubscription => StatusCode::CONFLICT,
                     NotYetUploaded => StatusCode::GATEWAY_TIMEOUT,
                     _ => StatusCode::BAD_REQUEST,
                 }
@@ -35,7 +35,7 @@ impl SendEventNotificationReqBody {
 }
 
 /// Response type for the `send_event_notification` endpoint.
-#[derive(ToSchema, Serialize, Debug)]
+#[derive(ToSchema, Deserialize, Serialize, Debug)]
 pub struct SendEventNotificationResBody {
     /// A list of all pushkeys given in the notification request that are not
     /// valid.
@@ -46,6 +46,7 @@ pub struct SendEventNotificationResBody {
     /// pushers. It may not necessarily be the notification in the request
     /// that failed: it could be that a previous notification to the same
     /// pushkey failed. May be empty.
+    #[serde(default)]
     pub rejected: Vec<String>,
 }
 impl SendEventNotificationResBody {
@@ -230,3 +231,20 @@ impl Device {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{from_value as from_json_value, json};
+
+    use super::SendEventNotificationResBody;
+
+    #[test]
+    fn rejected_pushkeys_default_to_none() {
+        let body: SendEventNotificationResBody = from_json_value(json!({})).unwrap();
+        assert!(body.rejected.is_empty());
+
+        let body: SendEventNotificationResBody =
+            from_json_value(json!({ "rejected": ["abcdef"] })).unwrap();
+        assert_eq!(body.rejected, ["abcdef"]);
+    }
+}
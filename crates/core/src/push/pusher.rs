@@ -171,6 +171,17 @@ pub struct Pusher {
     /// Determines which set of device specific rules this pusher executes.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub profile_tag: Option<String>,
+
+    /// Whether the pusher should actively create push notifications.
+    ///
+    /// Disabled pushers are kept but not notified ([MSC3881]).
+    ///
+    /// [MSC3881]: https://github.com/matrix-org/matrix-spec-proposals/pull/3881
+    #[serde(
+        rename = "org.matrix.msc3881.enabled",
+        skip_serializing_if = "crate::serde::is_true"
+    )]
+    pub enabled: bool,
 }
 
 #[derive(Debug, Deserialize)]
@@ -181,6 +192,11 @@ struct PusherDeHelper {
     device_display_name: String,
     profile_tag: Option<String>,
     lang: String,
+    #[serde(
+        rename = "org.matrix.msc3881.enabled",
+        default = "crate::serde::default_true"
+    )]
+    enabled: bool,
 }
 
 impl<'de> Deserialize<'de> for Pusher {
@@ -196,6 +212,7 @@ impl<'de> Deserialize<'de> for Pusher {
             device_display_name,
             profile_tag,
             lang,
+            enabled,
         } = from_raw_json_value(&json)?;
         let kind = from_raw_json_value(&json)?;
 
@@ -206,6 +223,7 @@ impl<'de> Deserialize<'de> for Pusher {
             device_display_name,
             profile_tag,
             lang,
+            enabled,
         })
     }
 }
@@ -307,3 +325,47 @@ impl From<crate::push::HttpPusherData> for PusherData {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{from_value as from_json_value, json, to_value as to_json_value};
+
+    use super::Pusher;
+
+    fn pusher_json() -> serde_json::Value {
+        json!({
+            "pushkey": "abcdef",
+            "app_id": "org.example.app",
+            "kind": "http",
+            "data": { "url": "https://push.example.org/_matrix/push/v1/notify" },
+            "app_display_name": "Example",
+            "device_display_name": "Phone",
+            "lang": "en",
+        })
+    }
+
+    #[test]
+    fn pusher_is_enabled_by_default() {
+        let pusher: Pusher = from_json_value(pusher_json()).unwrap();
+        assert!(pusher.enabled);
+        assert!(
+            to_json_value(&pusher)
+                .unwrap()
+                .get("org.matrix.msc3881.enabled")
+                .is_none()
+        );
+    }
+
+    #[test]
+    fn disabled_pusher_round_trips() {
+        let mut json = pusher_json();
+        json["org.matrix.msc3881.enabled"] = json!(false);
+
+        let pusher: Pusher = from_json_value(json).unwrap();
+        assert!(!pusher.enabled);
+        assert_eq!(
+            to_json_value(&pusher).unwrap()["org.matrix.msc3881.enabled"],
+            json!(false)
+        );
+    }
+}
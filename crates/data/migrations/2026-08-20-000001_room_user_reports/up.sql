@@ -0,0 +1,27 @@
+-- Reports about whole rooms (MSC4151) and users (MSC4260), reviewed through the
+-- admin API alongside event reports.
+CREATE TABLE room_reports (
+    id BIGSERIAL NOT NULL PRIMARY KEY,
+    received_ts BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'new',
+    room_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    reason TEXT
+);
+
+CREATE INDEX idx_room_reports_room_id ON room_reports(room_id);
+CREATE INDEX idx_room_reports_user_id ON room_reports(user_id);
+CREATE INDEX idx_room_reports_status ON room_reports(status);
+
+CREATE TABLE user_reports (
+    id BIGSERIAL NOT NULL PRIMARY KEY,
+    received_ts BIGINT NOT NULL,
+    status TEXT NOT NULL DEFAULT 'new',
+    target_user_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    reason TEXT
+);
+
+CREATE INDEX idx_user_reports_target_user_id ON user_reports(target_user_id);
+CREATE INDEX idx_user_reports_user_id ON user_reports(user_id);
+CREATE INDEX idx_user_reports_status ON user_reports(status);
@@ -0,0 +1,40 @@
+-- Users who sent the root or a reply of a thread, used for `include=participated`
+-- and `current_user_participated`.
+CREATE TABLE thread_participants (
+    id BIGSERIAL NOT NULL PRIMARY KEY,
+    room_id TEXT NOT NULL,
+    thread_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    CONSTRAINT thread_participants_udx UNIQUE (thread_id, user_id)
+);
+
+CREATE INDEX idx_thread_participants_user_id ON thread_participants(user_id, room_id);
+
+INSERT INTO thread_participants (room_id, thread_id, user_id)
+SELECT t.room_id, t.event_id, e.sender_id
+FROM threads t
+JOIN events e ON e.id = t.event_id
+WHERE e.sender_id IS NOT NULL
+UNION
+SELECT p.room_id, p.thread_id, e.sender_id
+FROM event_points p
+JOIN events e ON e.id = p.event_id
+WHERE p.thread_id IS NOT NULL AND e.sender_id IS NOT NULL
+ON CONFLICT DO NOTHING;
+
+-- Per-user thread subscriptions (MSC4306). Unsubscribing keeps the row so the change
+-- can be reported through `/thread_subscriptions` and sliding sync (MSC4308).
+CREATE TABLE thread_subscriptions (
+    id BIGSERIAL NOT NULL PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    room_id TEXT NOT NULL,
+    thread_id TEXT NOT NULL,
+    subscribed BOOLEAN NOT NULL,
+    automatic BOOLEAN NOT NULL DEFAULT FALSE,
+    unsubscribed_at_sn BIGINT,
+    occur_sn BIGINT NOT NULL,
+    created_at BIGINT NOT NULL,
+    CONSTRAINT thread_subscriptions_udx UNIQUE (user_id, thread_id)
+);
+
+CREATE INDEX idx_thread_subscriptions_user_sn ON thread_subscriptions(user_id, occur_sn);
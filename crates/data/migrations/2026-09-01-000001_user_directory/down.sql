@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS user_directory_public_rooms;
+DROP TABLE IF EXISTS user_directory;
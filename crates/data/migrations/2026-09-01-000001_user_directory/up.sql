@@ -0,0 +1,67 @@
+CREATE EXTENSION IF NOT EXISTS pg_trgm;
+
+-- Searchable profile of every known user, kept in sync with profile and membership changes.
+CREATE TABLE IF NOT EXISTS user_directory
+(
+    user_id text NOT NULL PRIMARY KEY,
+    display_name text,
+    avatar_url text,
+    -- Lowercased user id and display name, matched by the trigram index.
+    search_text text NOT NULL,
+    updated_at bigint NOT NULL
+);
+CREATE INDEX IF NOT EXISTS user_directory_search_text_trgm_idx
+    ON user_directory USING gin (search_text gin_trgm_ops);
+
+-- Rooms whose join rule is public, so their members are visible to everyone searching.
+CREATE TABLE IF NOT EXISTS user_directory_public_rooms
+(
+    room_id text NOT NULL PRIMARY KEY
+);
+
+-- Remote users only have the profile carried by their latest join event.
+INSERT INTO user_directory (user_id, display_name, avatar_url, search_text, updated_at)
+SELECT user_id, display_name, avatar_url,
+       lower(user_id || ' ' || coalesce(display_name, '')),
+       (extract(epoch FROM now()) * 1000)::bigint
+FROM (
+    SELECT DISTINCT ON (e.state_key)
+           e.state_key AS user_id,
+           d.json_data -> 'content' ->> 'displayname' AS display_name,
+           d.json_data -> 'content' ->> 'avatar_url' AS avatar_url
+    FROM events e
+    JOIN event_datas d ON d.event_id = e.id
+    JOIN users u ON u.id = e.state_key
+    WHERE e.ty = 'm.room.member'
+      AND NOT u.is_local
+      AND NOT e.is_outlier
+      AND NOT e.is_rejected
+      AND d.json_data -> 'content' ->> 'membership' = 'join'
+    ORDER BY e.state_key, e.sn DESC
+) latest
+ON CONFLICT (user_id) DO NOTHING;
+
+INSERT INTO user_directory (user_id, display_name, avatar_url, search_text, updated_at)
+SELECT user_id, display_name, avatar_url,
+       lower(user_id || ' ' || coalesce(display_name, '')),
+       (extract(epoch FROM now()) * 1000)::bigint
+FROM user_profiles
+WHERE room_id IS NULL
+ON CONFLICT (user_id) DO NOTHING;
+
+INSERT INTO user_directory_public_rooms (room_id)
+SELECT room_id
+FROM (
+    SELECT DISTINCT ON (e.room_id)
+           e.room_id,
+           d.json_data -> 'content' ->> 'join_rule' AS join_rule
+    FROM events e
+    JOIN event_datas d ON d.event_id = e.id
+    WHERE e.ty = 'm.room.join_rules'
+      AND e.state_key = ''
+      AND NOT e.is_outlier
+      AND NOT e.is_rejected
+    ORDER BY e.room_id, e.sn DESC
+) latest
+WHERE join_rule = 'public'
+ON CONFLICT (room_id) DO NOTHING;
@@ -0,0 +1 @@
+ALTER TABLE event_searches DROP COLUMN IF EXISTS language;
@@ -0,0 +1,2 @@
+-- Text search configuration each row was indexed with, existing rows used 'english'.
+ALTER TABLE event_searches ADD COLUMN IF NOT EXISTS language text NOT NULL DEFAULT 'english';
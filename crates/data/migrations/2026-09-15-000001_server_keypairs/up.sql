@@ -0,0 +1,13 @@
+-- Signing keypairs of this server. The keypair without `expired_at` signs; the
+-- others are published as `old_verify_keys`.
+CREATE TABLE IF NOT EXISTS server_keypairs (
+    version text NOT NULL PRIMARY KEY,
+    document text NOT NULL,
+    public_key text NOT NULL,
+    activated_at bigint NOT NULL,
+    expired_at bigint
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS server_keypairs_active_udx
+    ON server_keypairs ((expired_at IS NULL))
+    WHERE expired_at IS NULL;
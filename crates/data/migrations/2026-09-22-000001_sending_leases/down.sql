@@ -0,0 +1,2 @@
+DROP TABLE IF EXISTS sending_leases;
+DROP TABLE IF EXISTS sending_instances;
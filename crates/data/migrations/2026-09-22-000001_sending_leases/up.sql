@@ -0,0 +1,18 @@
+-- Instances sharing the sending queues, kept alive by a heartbeat.
+CREATE TABLE IF NOT EXISTS sending_instances (
+    instance_id text NOT NULL PRIMARY KEY,
+    started_at bigint NOT NULL,
+    heartbeat_at bigint NOT NULL
+);
+
+-- Ownership of a sending queue (a federation destination, an appservice or a
+-- pusher). Only the instance holding an unexpired lease sends to the queue.
+CREATE TABLE IF NOT EXISTS sending_leases (
+    queue_key text NOT NULL PRIMARY KEY,
+    instance_id text NOT NULL,
+    acquired_at bigint NOT NULL,
+    expires_at bigint NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS sending_leases_instance_id_idx
+    ON sending_leases (instance_id);
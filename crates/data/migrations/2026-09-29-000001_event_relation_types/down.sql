@@ -0,0 +1,2 @@
+-- The backfilled rel_types are correct, nothing to undo.
+SELECT 1;
@@ -0,0 +1,15 @@
+-- Relations used to be recorded without their rel_type, which bundling into
+-- `m.relations` relies on. Backfill it from the content of the child events.
+UPDATE event_relations r
+SET rel_type = d.json_data -> 'content' -> 'm.relates_to' ->> 'rel_type'
+FROM event_datas d
+WHERE d.event_id = r.child_id
+    AND r.rel_type IS NULL
+    AND d.json_data -> 'content' -> 'm.relates_to' ->> 'rel_type' IS NOT NULL
+    AND NOT EXISTS (
+        SELECT 1 FROM event_relations o
+        WHERE o.room_id = r.room_id
+            AND o.event_id = r.event_id
+            AND o.child_id = r.child_id
+            AND o.rel_type = d.json_data -> 'content' -> 'm.relates_to' ->> 'rel_type'
+    );
@@ -0,0 +1,6 @@
+-- Users whose events are still being pruned after an erasure, so the pruning
+-- resumes after a restart.
+CREATE TABLE IF NOT EXISTS user_erasures (
+    user_id text NOT NULL PRIMARY KEY,
+    requested_at bigint NOT NULL
+);
@@ -0,0 +1,14 @@
+-- The `mxc://` URIs referenced by each stored event, so finding whether a media
+-- is still referenced does not have to scan every event.
+CREATE TABLE IF NOT EXISTS event_media_refs (
+    event_id text NOT NULL,
+    mxc text NOT NULL,
+    PRIMARY KEY (event_id, mxc)
+);
+CREATE INDEX IF NOT EXISTS event_media_refs_mxc_idx ON event_media_refs (mxc);
+
+INSERT INTO event_media_refs (event_id, mxc)
+SELECT DISTINCT event_id, uri #>> '{}'
+FROM event_datas,
+    jsonb_path_query(json_data::jsonb, 'strict $.** ? (@ starts with "mxc://")') AS uri
+ON CONFLICT DO NOTHING;
@@ -29,6 +29,17 @@ fn default_connection_timeout() -> u64 {
 fn default_statement_timeout() -> u64 {
     30000
 }
+fn default_max_replica_lag() -> u64 {
+    5000
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct ReplicaConfig {
+    /// URL of a read-only streaming replica of the primary database.
+    pub url: String,
+    #[serde(default = "default_db_pool_size")]
+    pub pool_size: u32,
+}
 
 #[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct DbConfig {
@@ -59,6 +70,14 @@ pub struct DbConfig {
     /// Whether to enforce that all the database connections are encrypted with TLS.
     #[serde(default = "default_false")]
     pub enforce_tls: bool,
+
+    /// Read-only replicas that read-heavy query paths may be routed to.
+    #[serde(default)]
+    pub replicas: Vec<ReplicaConfig>,
+    /// Maximum replication lag in milliseconds a replica may have before reads
+    /// fall back to the primary.
+    #[serde(default = "default_max_replica_lag")]
+    pub max_replica_lag: u64,
 }
 
 impl fmt::Display for DbConfig {
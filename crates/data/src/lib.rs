@@ -11,16 +11,17 @@ mod macros;
 mod config;
 pub use palpo_core as core;
 
-pub use crate::config::DbConfig;
+pub use crate::config::{DbConfig, ReplicaConfig};
 
 pub mod full_text_search;
 
 pub mod pool;
-pub use pool::{DieselPool, PgPooledConnection, PoolError};
+pub use pool::{DieselPool, PgPooledConnection, PoolError, ReplicaSet};
 
 pub mod appservice;
 pub mod media;
 pub mod misc;
+pub mod report;
 pub mod room;
 pub mod schema;
 pub mod sending;
@@ -34,13 +35,18 @@ use crate::core::Seqnum;
 pub type DataResult<T> = Result<T, DataError>;
 
 pub static DIESEL_POOL: OnceLock<DieselPool> = OnceLock::new();
-pub static REPLICA_POOL: OnceLock<Option<DieselPool>> = OnceLock::new();
+pub static REPLICA_SET: OnceLock<ReplicaSet> = OnceLock::new();
 
 pub const MIGRATIONS: EmbeddedMigrations = embed_migrations!();
 
 pub fn init(config: &DbConfig) {
-    let pool = DieselPool::new(&config.url, config).expect("diesel pool should be created");
+    let pool = DieselPool::new(&config.url, config.pool_size, config)
+        .expect("diesel pool should be created");
     DIESEL_POOL.set(pool).expect("diesel pool should be set");
+    let replicas = ReplicaSet::new(config).expect("replica pools should be created");
+    REPLICA_SET
+        .set(replicas)
+        .expect("replica set should be set");
     migrate(config);
 }
 
@@ -70,6 +76,35 @@ pub async fn connect() -> Result<PgPooledConnection, PoolError> {
         }
     }
 }
+
+/// Get a connection for a read-only query that tolerates replication lag.
+///
+/// Uses a replica that is within `max_replica_lag` of the primary when one is
+/// configured and healthy, otherwise falls back to the primary. Paths that must
+/// observe their own writes (sync watcher, anything right after an insert)
+/// should keep using [`connect`].
+pub async fn connect_replica() -> Result<PgPooledConnection, PoolError> {
+    if let Some(replicas) = REPLICA_SET.get()
+        && let Some(conn) = replicas.get().await
+    {
+        return Ok(conn);
+    }
+    connect().await
+}
+
+/// Re-probe the replication lag of all configured replicas.
+pub async fn refresh_replica_lag() {
+    if let Some(replicas) = REPLICA_SET.get() {
+        replicas.refresh_lag().await;
+    }
+}
+
+pub fn has_replicas() -> bool {
+    REPLICA_SET
+        .get()
+        .is_some_and(|replicas| !replicas.is_empty())
+}
+
 pub fn status() -> deadpool::managed::Status {
     DIESEL_POOL.get().expect("diesel pool should set").status()
 }
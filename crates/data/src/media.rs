@@ -1,8 +1,11 @@
+use std::collections::HashSet;
+
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
 
 use crate::core::UnixMillis;
 use crate::core::identifiers::*;
+use crate::core::serde::JsonValue;
 use crate::schema::*;
 use crate::{DataResult, connect};
 
@@ -442,3 +445,102 @@ struct CountResult {
     #[diesel(sql_type = diesel::sql_types::BigInt)]
     count: i64,
 }
+
+/// Of the given `mxc://` URIs, those still referenced by a stored event or a
+/// user profile.
+pub async fn referenced_mxcs(mxcs: &[String]) -> DataResult<HashSet<String>> {
+    if mxcs.is_empty() {
+        return Ok(HashSet::new());
+    }
+    let mut conn = connect().await?;
+    let mut referenced: HashSet<String> = user_profiles::table
+        .filter(user_profiles::avatar_url.eq_any(mxcs))
+        .select(user_profiles::avatar_url.assume_not_null())
+        .load::<String>(&mut conn)
+        .await?
+        .into_iter()
+        .collect();
+    referenced.extend(
+        event_media_refs::table
+            .filter(event_media_refs::mxc.eq_any(mxcs))
+            .select(event_media_refs::mxc)
+            .distinct()
+            .load::<String>(&mut conn)
+            .await?,
+    );
+    Ok(referenced)
+}
+
+/// Record the `mxc://` URIs referenced by a stored event, replacing those
+/// recorded for a previous version of it.
+pub async fn set_event_media_refs(event_id: &EventId, json_data: &JsonValue) -> DataResult<()> {
+    let mut mxcs = HashSet::new();
+    collect_mxc_uris(json_data, &mut mxcs);
+    let mut conn = connect().await?;
+    diesel::delete(event_media_refs::table.filter(event_media_refs::event_id.eq(event_id)))
+        .execute(&mut conn)
+        .await?;
+    if mxcs.is_empty() {
+        return Ok(());
+    }
+    let rows: Vec<_> = mxcs
+        .into_iter()
+        .map(|mxc| {
+            (
+                event_media_refs::event_id.eq(event_id),
+                event_media_refs::mxc.eq(mxc),
+            )
+        })
+        .collect();
+    diesel::insert_into(event_media_refs::table)
+        .values(&rows)
+        .on_conflict_do_nothing()
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Collect every `mxc://` URI found in an event, including nested ones such
+/// as `content.file.url` and `content.info.thumbnail_url`.
+pub fn collect_mxc_uris(value: &JsonValue, mxcs: &mut HashSet<String>) {
+    match value {
+        JsonValue::String(s) if s.starts_with("mxc://") => {
+            mxcs.insert(s.clone());
+        }
+        JsonValue::Array(values) => {
+            for value in values {
+                collect_mxc_uris(value, mxcs);
+            }
+        }
+        JsonValue::Object(map) => {
+            for value in map.values() {
+                collect_mxc_uris(value, mxcs);
+            }
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn collects_nested_mxc_uris() {
+        let content = json!({
+            "msgtype": "m.image",
+            "body": "image.png",
+            "url": "mxc://example.com/abc",
+            "info": { "thumbnail_url": "mxc://example.com/thumb" },
+            "file": { "url": "mxc://example.com/enc" },
+            "other": ["https://example.com", "mxc://example.com/list"],
+        });
+        let mut mxcs = HashSet::new();
+        collect_mxc_uris(&content, &mut mxcs);
+        assert_eq!(mxcs.len(), 4);
+        assert!(mxcs.contains("mxc://example.com/thumb"));
+        assert!(!mxcs.contains("https://example.com"));
+    }
+}
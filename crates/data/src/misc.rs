@@ -1,5 +1,5 @@
 use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use diesel_async::{AsyncConnection, RunQueryDsl};
 
 use crate::core::serde::JsonValue;
 use crate::core::{OwnedServerName, ServerName, UnixMillis};
@@ -48,3 +48,45 @@ pub async fn upsert_signing_keys(server: &ServerName, key_data: JsonValue) -> Da
         .await?;
     Ok(())
 }
+
+/// A signing keypair of this server. The one without `expired_at` is the active one.
+#[derive(Identifiable, Queryable, Insertable, Debug, Clone)]
+#[diesel(table_name = server_keypairs, primary_key(version))]
+pub struct DbServerKeypair {
+    pub version: String,
+    /// Base64 encoded PKCS#8 document of the keypair.
+    pub document: String,
+    /// Unpadded base64 encoded public key.
+    pub public_key: String,
+    pub activated_at: UnixMillis,
+    pub expired_at: Option<UnixMillis>,
+}
+
+/// All signing keypairs this server ever stored, the active one first.
+pub async fn server_keypairs() -> DataResult<Vec<DbServerKeypair>> {
+    server_keypairs::table
+        .order_by((
+            server_keypairs::expired_at.is_not_null(),
+            server_keypairs::activated_at.desc(),
+        ))
+        .load::<DbServerKeypair>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
+/// Store `keypair` as the active signing keypair, retiring the previous one.
+pub async fn activate_server_keypair(keypair: &DbServerKeypair) -> DataResult<()> {
+    let mut conn = connect().await?;
+    conn.transaction::<_, crate::DataError, _>(async |conn| {
+        diesel::update(server_keypairs::table.filter(server_keypairs::expired_at.is_null()))
+            .set(server_keypairs::expired_at.eq(keypair.activated_at))
+            .execute(conn)
+            .await?;
+        diesel::insert_into(server_keypairs::table)
+            .values(keypair)
+            .execute(conn)
+            .await?;
+        Ok(())
+    })
+    .await
+}
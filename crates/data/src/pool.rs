@@ -1,3 +1,4 @@
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
 use std::time::Duration;
 
 use deadpool::Runtime;
@@ -28,7 +29,11 @@ impl std::fmt::Debug for DieselPool {
 }
 
 impl DieselPool {
-    pub(crate) fn new(url: &str, config: &DbConfig) -> Result<DieselPool, PoolError> {
+    pub(crate) fn new(
+        url: &str,
+        pool_size: u32,
+        config: &DbConfig,
+    ) -> Result<DieselPool, PoolError> {
         let conn_url = connection_url(config, url);
 
         // PostgreSQL `SET` does not support bind parameters, so the value is
@@ -57,7 +62,7 @@ impl DieselPool {
 
         let timeout = Duration::from_millis(config.connection_timeout);
         let inner = Pool::builder(manager)
-            .max_size(config.pool_size as usize)
+            .max_size(pool_size as usize)
             .runtime(Runtime::Tokio1)
             .timeouts(Timeouts {
                 wait: Some(timeout),
@@ -79,6 +84,125 @@ impl DieselPool {
     }
 }
 
+/// Read-only replicas with the replication lag observed by the last probe.
+///
+/// A replica is only handed out while its lag is within `max_lag`, so callers
+/// that can tolerate slightly stale data never read further behind than that.
+/// Replicas start out as unusable until the first successful probe.
+#[derive(Debug)]
+pub struct ReplicaSet {
+    replicas: Vec<Replica>,
+    max_lag: u64,
+    next: AtomicUsize,
+}
+
+#[derive(Debug)]
+struct Replica {
+    pool: DieselPool,
+    lag: AtomicU64,
+}
+
+impl Replica {
+    // `RunQueryDsl::load` is in scope, so spell out the atomic load.
+    fn lag(&self) -> u64 {
+        AtomicU64::load(&self.lag, Ordering::Relaxed)
+    }
+}
+
+const REPLICA_LAG_SQL: &str = "SELECT CASE \
+    WHEN NOT pg_is_in_recovery() THEN 0 \
+    WHEN pg_last_wal_receive_lsn() = pg_last_wal_replay_lsn() THEN 0 \
+    ELSE COALESCE((EXTRACT(EPOCH FROM now() - pg_last_xact_replay_timestamp()) * 1000)::BIGINT, 0) \
+    END";
+
+impl ReplicaSet {
+    pub(crate) fn new(config: &DbConfig) -> Result<ReplicaSet, PoolError> {
+        let replicas = config
+            .replicas
+            .iter()
+            .map(|replica| {
+                Ok(Replica {
+                    pool: DieselPool::new(&replica.url, replica.pool_size, config)?,
+                    lag: AtomicU64::new(u64::MAX),
+                })
+            })
+            .collect::<Result<Vec<_>, PoolError>>()?;
+        Ok(ReplicaSet {
+            replicas,
+            max_lag: config.max_replica_lag,
+            next: AtomicUsize::new(0),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.replicas.is_empty()
+    }
+
+    /// Returns a connection to the next replica that is within the lag limit,
+    /// or `None` if no replica is currently usable.
+    pub async fn get(&self) -> Option<PgPooledConnection> {
+        let len = self.replicas.len();
+        if len == 0 {
+            return None;
+        }
+        let start = self.next.fetch_add(1, Ordering::Relaxed);
+        for offset in 0..len {
+            let replica = &self.replicas[(start + offset) % len];
+            if replica.lag() > self.max_lag {
+                continue;
+            }
+            match replica.pool.get().await {
+                Ok(conn) => return Some(conn),
+                Err(e) => {
+                    tracing::warn!("replica connect error: {e}");
+                    replica.lag.store(u64::MAX, Ordering::Relaxed);
+                }
+            }
+        }
+        None
+    }
+
+    /// Probes every replica for its current replication lag.
+    ///
+    /// Unreachable replicas are marked as infinitely behind so that reads go to
+    /// the primary until they recover.
+    pub async fn refresh_lag(&self) {
+        for replica in &self.replicas {
+            let lag = match replica.pool.get().await {
+                Ok(mut conn) => diesel::dsl::sql::<diesel::sql_types::BigInt>(REPLICA_LAG_SQL)
+                    .get_result::<i64>(&mut conn)
+                    .await
+                    .map(|lag| lag.max(0) as u64)
+                    .unwrap_or_else(|e| {
+                        tracing::warn!("replica lag probe error: {e}");
+                        u64::MAX
+                    }),
+                Err(e) => {
+                    tracing::warn!("replica connect error: {e}");
+                    u64::MAX
+                }
+            };
+            let previous = replica.lag.swap(lag, Ordering::Relaxed);
+            if previous <= self.max_lag && lag > self.max_lag {
+                tracing::warn!(lag, "replica is lagging behind, reads fall back to primary");
+            } else if previous > self.max_lag && lag <= self.max_lag {
+                tracing::info!(lag, "replica caught up, resuming replica reads");
+            }
+        }
+    }
+
+    /// Replication lag of each replica in milliseconds, `None` if unknown.
+    pub fn lags(&self) -> Vec<Option<u64>> {
+        self.replicas
+            .iter()
+            .map(|replica| match replica.lag() {
+                u64::MAX => None,
+                lag => Some(lag),
+            })
+            .collect()
+    }
+}
+
 #[derive(Debug, Error)]
 pub enum PoolError {
     #[error(transparent)]
@@ -86,3 +210,40 @@ pub enum PoolError {
     #[error(transparent)]
     Get(#[from] DeadpoolError),
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Replicas on a port nothing listens on, so connecting fails right away.
+    fn unreachable_replicas(count: usize) -> ReplicaSet {
+        let replicas = (0..count)
+            .map(|_| serde_json::json!({ "url": "postgres://palpo@127.0.0.1:1/palpo" }))
+            .collect::<Vec<_>>();
+        let config: DbConfig = serde_json::from_value(serde_json::json!({
+            "url": "postgres://palpo@127.0.0.1:1/palpo",
+            "connection_timeout": 1000,
+            "replicas": replicas,
+        }))
+        .unwrap();
+        ReplicaSet::new(&config).unwrap()
+    }
+
+    #[tokio::test]
+    async fn unprobed_replicas_fall_back_to_primary() {
+        let replicas = unreachable_replicas(2);
+        assert_eq!(replicas.lags(), vec![None, None]);
+        assert!(replicas.get().await.is_none());
+    }
+
+    #[tokio::test]
+    async fn lagging_replicas_are_skipped_and_failing_ones_marked_unusable() {
+        let replicas = unreachable_replicas(2);
+        replicas.replicas[0].lag.store(10_000, Ordering::Relaxed);
+        replicas.replicas[1].lag.store(0, Ordering::Relaxed);
+
+        assert!(replicas.get().await.is_none());
+        // The lagging replica was not tried, the one within the limit was and failed.
+        assert_eq!(replicas.lags(), vec![Some(10_000), None]);
+    }
+}
@@ -0,0 +1,41 @@
+//! Types shared by the room and user reports, whose tables only differ by the
+//! column naming what is reported.
+
+/// Filter options for listing reports.
+///
+/// `target` filters on what is reported: the room of a room report, the user of a
+/// user report.
+#[derive(Debug, Clone)]
+pub struct ReportFilter<T> {
+    pub from: Option<i64>,
+    pub limit: Option<i64>,
+    pub direction: Option<String>,
+    pub user_id: Option<crate::core::identifiers::OwnedUserId>,
+    pub target: Option<T>,
+    pub status: Option<String>,
+}
+
+impl<T> Default for ReportFilter<T> {
+    fn default() -> Self {
+        Self {
+            from: None,
+            limit: None,
+            direction: None,
+            user_id: None,
+            target: None,
+            status: None,
+        }
+    }
+}
+
+impl<T> ReportFilter<T> {
+    /// Whether to list the oldest reports first; the default is newest first.
+    pub fn is_forward(&self) -> bool {
+        self.direction.as_deref() == Some("f")
+    }
+
+    /// The number of reports to list, 100 by default and at most 1000.
+    pub fn page_limit(&self) -> i64 {
+        self.limit.unwrap_or(100).min(1000)
+    }
+}
@@ -14,10 +14,13 @@ pub mod event_report;
 pub mod lazy_loading;
 pub mod peek;
 pub mod receipt;
+pub mod room_report;
+pub mod thread;
 pub mod timeline;
 pub mod transaction_id;
 pub mod typing;
 pub use event_report::*;
+pub use room_report::*;
 
 #[derive(Insertable, Identifiable, Queryable, Debug, Clone)]
 #[diesel(table_name = rooms)]
@@ -222,7 +225,7 @@ impl DbEventData {
             .set(self)
             .execute(&mut connect().await?)
             .await?;
-        Ok(())
+        crate::media::set_event_media_refs(&self.event_id, &self.json_data).await
     }
 }
 
@@ -419,6 +422,34 @@ pub async fn joined_servers_for_rooms(
         .map_err(Into::into)
 }
 
+#[derive(QueryableByName)]
+struct MemberResult {
+    #[diesel(sql_type = diesel::sql_types::Text)]
+    user_id: OwnedUserId,
+}
+
+/// Return the remote users joined to `room_id` who share no room with a user of
+/// `server_name` anymore.
+pub async fn remote_members_without_local_rooms(
+    room_id: &RoomId,
+    server_name: &ServerName,
+) -> DataResult<Vec<OwnedUserId>> {
+    let members = diesel::sql_query(
+        "SELECT DISTINCT m.user_id FROM room_users m \
+         WHERE m.room_id = $1 AND m.membership = 'join' AND m.user_server_id <> $2 \
+         AND NOT EXISTS ( \
+             SELECT 1 FROM room_users shared \
+             JOIN room_users l ON l.room_id = shared.room_id \
+             WHERE shared.user_id = m.user_id AND shared.membership = 'join' \
+             AND l.membership = 'join' AND l.user_server_id = $2)",
+    )
+    .bind::<diesel::sql_types::Text, _>(room_id.as_str())
+    .bind::<diesel::sql_types::Text, _>(server_name.as_str())
+    .load::<MemberResult>(&mut connect().await?)
+    .await?;
+    Ok(members.into_iter().map(|m| m.user_id).collect())
+}
+
 #[derive(Insertable, Debug, Clone)]
 #[diesel(table_name = banned_rooms)]
 pub struct NewDbBannedRoom {
@@ -519,21 +550,31 @@ pub async fn remove_alias(alias_id: &RoomAliasId) -> DataResult<()> {
     Ok(())
 }
 
-/// List a room's thread roots as `(event_id, event_sn)`, newest activity first,
-/// optionally only those with `event_sn <= before_sn`.
+/// List a room's thread roots as `(event_id, last_sn)`, newest activity first,
+/// optionally only those with `last_sn <= before_sn` or those `participant` took part in.
 pub async fn list_threads(
     room_id: &RoomId,
+    participant: Option<&UserId>,
     before_sn: Option<i64>,
     limit: i64,
 ) -> DataResult<Vec<(OwnedEventId, i64)>> {
     let mut query = threads::table
         .filter(threads::room_id.eq(room_id))
         .into_boxed();
+    if let Some(participant) = participant {
+        query = query.filter(
+            threads::event_id.eq_any(
+                thread_participants::table
+                    .filter(thread_participants::user_id.eq(participant))
+                    .select(thread_participants::thread_id),
+            ),
+        );
+    }
     if let Some(before_sn) = before_sn {
-        query = query.filter(threads::event_sn.le(before_sn));
+        query = query.filter(threads::last_sn.le(before_sn));
     }
     query
-        .select((threads::event_id, threads::event_sn))
+        .select((threads::event_id, threads::last_sn))
         .order_by(threads::last_sn.desc())
         .limit(limit)
         .load::<(OwnedEventId, i64)>(&mut connect().await?)
@@ -550,6 +591,28 @@ pub async fn set_event_thread_id(event_id: &EventId, thread_id: &EventId) -> Dat
     Ok(())
 }
 
+/// The thread an event belongs to, if any.
+pub async fn event_thread_id(event_id: &EventId) -> DataResult<Option<OwnedEventId>> {
+    event_points::table
+        .find(event_id)
+        .select(event_points::thread_id)
+        .first::<Option<OwnedEventId>>(&mut connect().await?)
+        .await
+        .optional()
+        .map(Option::flatten)
+        .map_err(Into::into)
+}
+
+/// Get a thread by its root event id.
+pub async fn get_thread(thread_id: &EventId) -> DataResult<Option<DbThread>> {
+    threads::table
+        .find(thread_id)
+        .first::<DbThread>(&mut connect().await?)
+        .await
+        .optional()
+        .map_err(Into::into)
+}
+
 /// Insert a thread root, updating its latest event if it already exists.
 pub async fn upsert_thread(thread: DbThread) -> DataResult<()> {
     let last_id = thread.last_id.clone();
@@ -649,6 +712,49 @@ pub async fn get_event_relations(
         .map_err(Into::into)
 }
 
+/// An `m.replace` or `m.reference` child of an event, as bundled into it.
+#[derive(Queryable, Debug, Clone)]
+pub struct DbBundledRelation {
+    /// The event the child relates to.
+    pub event_id: OwnedEventId,
+    pub child_id: OwnedEventId,
+    pub child_sn: Seqnum,
+    pub rel_type: Option<String>,
+    pub sender_id: Option<OwnedUserId>,
+    pub ty: String,
+    pub state_key: Option<String>,
+    pub origin_server_ts: UnixMillis,
+}
+
+/// The `m.replace` and `m.reference` children of `event_ids` that are neither
+/// outliers, redacted nor rejected, loaded at once for a page of served events.
+pub async fn bundled_relations(event_ids: &[&str]) -> DataResult<Vec<DbBundledRelation>> {
+    if event_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+    event_relations::table
+        .inner_join(events::table.on(events::id.eq(event_relations::child_id)))
+        .filter(event_relations::event_id.eq_any(event_ids))
+        .filter(event_relations::rel_type.eq_any(["m.replace", "m.reference"]))
+        .filter(events::is_outlier.eq(false))
+        .filter(events::is_redacted.eq(false))
+        .filter(events::is_rejected.eq(false))
+        .order(event_relations::child_sn.asc())
+        .select((
+            event_relations::event_id,
+            event_relations::child_id,
+            event_relations::child_sn,
+            event_relations::rel_type,
+            events::sender_id,
+            events::ty,
+            events::state_key,
+            events::origin_server_ts,
+        ))
+        .load::<DbBundledRelation>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
 /// Mark an event as soft-failed.
 pub async fn set_event_soft_failed(event_id: &EventId) -> DataResult<()> {
     diesel::update(events::table.filter(events::id.eq(event_id)))
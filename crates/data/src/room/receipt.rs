@@ -4,9 +4,9 @@ use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
 
 use crate::core::events::AnySyncEphemeralRoomEvent;
-use crate::core::events::receipt::{Receipt, ReceiptEventContent, ReceiptType};
+use crate::core::events::receipt::{Receipt, ReceiptEventContent, ReceiptThread, ReceiptType};
 use crate::core::identifiers::*;
-use crate::core::serde::{JsonValue, RawJson};
+use crate::core::serde::RawJson;
 use crate::core::{Seqnum, UnixMillis};
 use crate::room::DbReceipt;
 use crate::schema::*;
@@ -14,6 +14,8 @@ use crate::{DataResult, connect, next_sn};
 
 /// Returns an iterator over the most recent read_receipts in a room that happened after the event
 /// with id `since`.
+///
+/// Private read receipts are left out, they are only ever sent to their owner.
 pub async fn read_receipts(
     room_id: &RoomId,
     since_sn: Seqnum,
@@ -22,6 +24,7 @@ pub async fn read_receipts(
     let receipts = event_receipts::table
         .filter(event_receipts::sn.ge(since_sn))
         .filter(event_receipts::room_id.eq(room_id))
+        .filter(event_receipts::ty.ne(ReceiptType::ReadPrivate.to_string()))
         .order_by(event_receipts::sn.desc())
         .load::<DbReceipt>(&mut connect().await?)
         .await?;
@@ -76,7 +79,12 @@ pub async fn set_private_read(
     user_id: &UserId,
     event_id: &EventId,
     event_sn: Seqnum,
+    receipt: &Receipt,
 ) -> DataResult<()> {
+    let thread_id = match &receipt.thread {
+        ReceiptThread::Thread(id) => Some(id.clone()),
+        _ => None,
+    };
     diesel::insert_into(event_receipts::table)
         .values(&DbReceipt {
             sn: next_sn().await?,
@@ -85,9 +93,9 @@ pub async fn set_private_read(
             user_id: user_id.to_owned(),
             event_id: event_id.to_owned(),
             event_sn,
-            thread_id: None,
-            json_data: JsonValue::default(),
-            receipt_at: UnixMillis::now(),
+            thread_id,
+            json_data: serde_json::to_value(receipt)?,
+            receipt_at: receipt.ts.unwrap_or_else(UnixMillis::now),
         })
         .execute(&mut connect().await?)
         .await?;
@@ -116,19 +124,15 @@ pub async fn insert_receipt(receipt: &DbReceipt) -> DataResult<()> {
     Ok(())
 }
 
-/// Event id of the user's most recent private read receipt in a room.
-pub async fn last_private_read_event_id(
-    user_id: &UserId,
-    room_id: &RoomId,
-) -> DataResult<Option<OwnedEventId>> {
+/// The user's most recent private read receipt in a room, one per thread.
+pub async fn last_private_reads(user_id: &UserId, room_id: &RoomId) -> DataResult<Vec<DbReceipt>> {
     event_receipts::table
         .filter(event_receipts::room_id.eq(room_id))
         .filter(event_receipts::user_id.eq(user_id))
         .filter(event_receipts::ty.eq(ReceiptType::ReadPrivate.to_string()))
-        .order_by(event_receipts::sn.desc())
-        .select(event_receipts::event_id)
-        .first::<OwnedEventId>(&mut connect().await?)
+        .distinct_on(event_receipts::thread_id)
+        .order_by((event_receipts::thread_id, event_receipts::sn.desc()))
+        .load::<DbReceipt>(&mut connect().await?)
         .await
-        .optional()
         .map_err(Into::into)
 }
@@ -0,0 +1,123 @@
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+
+use crate::core::UnixMillis;
+use crate::core::identifiers::*;
+use crate::report::ReportFilter;
+use crate::schema::*;
+use crate::{DataResult, connect};
+
+/// Database model for room reports
+#[derive(Identifiable, Queryable, Debug, Clone)]
+#[diesel(table_name = room_reports)]
+pub struct DbRoomReport {
+    pub id: i64,
+    pub received_ts: i64,
+    pub status: String,
+    pub room_id: OwnedRoomId,
+    pub user_id: OwnedUserId,
+    pub reason: Option<String>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = room_reports)]
+pub struct NewDbRoomReport {
+    pub received_ts: i64,
+    pub status: String,
+    pub room_id: OwnedRoomId,
+    pub user_id: OwnedUserId,
+    pub reason: Option<String>,
+}
+
+impl NewDbRoomReport {
+    pub fn new(room_id: OwnedRoomId, user_id: OwnedUserId, reason: Option<String>) -> Self {
+        Self {
+            received_ts: UnixMillis::now().get() as i64,
+            status: "new".to_owned(),
+            room_id,
+            user_id,
+            reason,
+        }
+    }
+}
+
+/// Filter options for listing room reports
+pub type RoomReportFilter = ReportFilter<OwnedRoomId>;
+
+/// Create a new room report
+pub async fn create_room_report(report: NewDbRoomReport) -> DataResult<i64> {
+    let result = diesel::insert_into(room_reports::table)
+        .values(&report)
+        .returning(room_reports::id)
+        .get_result::<i64>(&mut connect().await?)
+        .await?;
+    Ok(result)
+}
+
+/// List room reports with pagination and filtering
+pub async fn list_room_reports(filter: &RoomReportFilter) -> DataResult<(Vec<DbRoomReport>, i64)> {
+    let filtered = || {
+        let mut query = room_reports::table.into_boxed();
+        if let Some(ref user_id) = filter.user_id {
+            query = query.filter(room_reports::user_id.eq(user_id));
+        }
+        if let Some(ref target) = filter.target {
+            query = query.filter(room_reports::room_id.eq(target));
+        }
+        if let Some(ref status) = filter.status {
+            query = query.filter(room_reports::status.eq(status));
+        }
+        query
+    };
+
+    let total = filtered()
+        .count()
+        .get_result::<i64>(&mut connect().await?)
+        .await?;
+
+    let mut query = if filter.is_forward() {
+        filtered().order(room_reports::id.asc())
+    } else {
+        filtered().order(room_reports::id.desc())
+    };
+    if let Some(from) = filter.from {
+        query = query.offset(from);
+    }
+    let reports = query
+        .limit(filter.page_limit())
+        .load::<DbRoomReport>(&mut connect().await?)
+        .await?;
+    Ok((reports, total))
+}
+
+/// Get a single room report by ID
+pub async fn get_room_report(report_id: i64) -> DataResult<Option<DbRoomReport>> {
+    room_reports::table
+        .find(report_id)
+        .first::<DbRoomReport>(&mut connect().await?)
+        .await
+        .optional()
+        .map_err(Into::into)
+}
+
+/// Update a room report status by ID
+pub async fn update_room_report_status(
+    report_id: i64,
+    status: &str,
+) -> DataResult<Option<DbRoomReport>> {
+    diesel::update(room_reports::table.find(report_id))
+        .set(room_reports::status.eq(status))
+        .get_result::<DbRoomReport>(&mut connect().await?)
+        .await
+        .optional()
+        .map_err(Into::into)
+}
+
+/// Delete a room report by ID
+/// Returns true if deleted, false if not found
+pub async fn delete_room_report(report_id: i64) -> DataResult<bool> {
+    let result = diesel::delete(room_reports::table.find(report_id))
+        .execute(&mut connect().await?)
+        .await?;
+    Ok(result > 0)
+}
@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+
+use crate::core::identifiers::*;
+use crate::core::{Seqnum, UnixMillis};
+use crate::schema::*;
+use crate::{DataResult, connect, next_sn};
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = thread_participants)]
+pub struct NewDbThreadParticipant {
+    pub room_id: OwnedRoomId,
+    pub thread_id: OwnedEventId,
+    pub user_id: OwnedUserId,
+}
+
+/// Record that `user_id` sent the root or a reply of the thread.
+pub async fn add_participant(
+    room_id: &RoomId,
+    thread_id: &EventId,
+    user_id: &UserId,
+) -> DataResult<()> {
+    diesel::insert_into(thread_participants::table)
+        .values(NewDbThreadParticipant {
+            room_id: room_id.to_owned(),
+            thread_id: thread_id.to_owned(),
+            user_id: user_id.to_owned(),
+        })
+        .on_conflict((thread_participants::thread_id, thread_participants::user_id))
+        .do_nothing()
+        .execute(&mut connect().await?)
+        .await?;
+    Ok(())
+}
+
+/// Of the given threads, those `user_id` sent the root or a reply of.
+pub async fn participated_threads(
+    thread_ids: &[&str],
+    user_id: &UserId,
+) -> DataResult<HashSet<OwnedEventId>> {
+    if thread_ids.is_empty() {
+        return Ok(HashSet::new());
+    }
+    thread_participants::table
+        .filter(thread_participants::thread_id.eq_any(thread_ids))
+        .filter(thread_participants::user_id.eq(user_id))
+        .select(thread_participants::thread_id)
+        .load::<OwnedEventId>(&mut connect().await?)
+        .await
+        .map(|thread_ids| thread_ids.into_iter().collect())
+        .map_err(Into::into)
+}
+
+/// A user's subscription state for a thread (MSC4306).
+///
+/// Unsubscribing keeps the row with `subscribed = false`, so that `occur_sn` can be
+/// reported as the bump stamp of the unsubscription.
+#[derive(Identifiable, Queryable, Debug, Clone)]
+#[diesel(table_name = thread_subscriptions)]
+pub struct DbThreadSubscription {
+    pub id: i64,
+    pub user_id: OwnedUserId,
+    pub room_id: OwnedRoomId,
+    pub thread_id: OwnedEventId,
+    pub subscribed: bool,
+    pub automatic: bool,
+    /// Stream position of the latest thread event when the user unsubscribed.
+    pub unsubscribed_at_sn: Option<Seqnum>,
+    pub occur_sn: Seqnum,
+    pub created_at: UnixMillis,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = thread_subscriptions)]
+pub struct NewDbThreadSubscription {
+    pub user_id: OwnedUserId,
+    pub room_id: OwnedRoomId,
+    pub thread_id: OwnedEventId,
+    pub subscribed: bool,
+    pub automatic: bool,
+    pub unsubscribed_at_sn: Option<Seqnum>,
+    pub occur_sn: Seqnum,
+    pub created_at: UnixMillis,
+}
+
+pub async fn get_subscription(
+    user_id: &UserId,
+    thread_id: &EventId,
+) -> DataResult<Option<DbThreadSubscription>> {
+    thread_subscriptions::table
+        .filter(thread_subscriptions::user_id.eq(user_id))
+        .filter(thread_subscriptions::thread_id.eq(thread_id))
+        .first::<DbThreadSubscription>(&mut connect().await?)
+        .await
+        .optional()
+        .map_err(Into::into)
+}
+
+/// Whether `user_id` is currently subscribed to the thread.
+pub async fn is_subscribed(user_id: &UserId, thread_id: &EventId) -> DataResult<bool> {
+    diesel::select(diesel::dsl::exists(
+        thread_subscriptions::table
+            .filter(thread_subscriptions::user_id.eq(user_id))
+            .filter(thread_subscriptions::thread_id.eq(thread_id))
+            .filter(thread_subscriptions::subscribed.eq(true)),
+    ))
+    .get_result::<bool>(&mut connect().await?)
+    .await
+    .map_err(Into::into)
+}
+
+/// Subscribe `user_id` to the thread, returning the bump stamp of the change.
+pub async fn subscribe(
+    user_id: &UserId,
+    room_id: &RoomId,
+    thread_id: &EventId,
+    automatic: bool,
+) -> DataResult<Seqnum> {
+    let occur_sn = next_sn().await?;
+    diesel::insert_into(thread_subscriptions::table)
+        .values(NewDbThreadSubscription {
+            user_id: user_id.to_owned(),
+            room_id: room_id.to_owned(),
+            thread_id: thread_id.to_owned(),
+            subscribed: true,
+            automatic,
+            unsubscribed_at_sn: None,
+            occur_sn,
+            created_at: UnixMillis::now(),
+        })
+        .on_conflict((
+            thread_subscriptions::user_id,
+            thread_subscriptions::thread_id,
+        ))
+        .do_update()
+        .set((
+            thread_subscriptions::subscribed.eq(true),
+            thread_subscriptions::automatic.eq(automatic),
+            thread_subscriptions::unsubscribed_at_sn.eq(None::<Seqnum>),
+            thread_subscriptions::occur_sn.eq(occur_sn),
+        ))
+        .execute(&mut connect().await?)
+        .await?;
+    Ok(occur_sn)
+}
+
+/// Unsubscribe `user_id` from the thread, remembering the latest thread event at that time.
+pub async fn unsubscribe(
+    user_id: &UserId,
+    thread_id: &EventId,
+    unsubscribed_at_sn: Seqnum,
+) -> DataResult<()> {
+    let occur_sn = next_sn().await?;
+    diesel::update(
+        thread_subscriptions::table
+            .filter(thread_subscriptions::user_id.eq(user_id))
+            .filter(thread_subscriptions::thread_id.eq(thread_id))
+            .filter(thread_subscriptions::subscribed.eq(true)),
+    )
+    .set((
+        thread_subscriptions::subscribed.eq(false),
+        thread_subscriptions::automatic.eq(false),
+        thread_subscriptions::unsubscribed_at_sn.eq(Some(unsubscribed_at_sn)),
+        thread_subscriptions::occur_sn.eq(occur_sn),
+    ))
+    .execute(&mut connect().await?)
+    .await?;
+    Ok(())
+}
+
+/// Subscription changes of `user_id`, newest first.
+///
+/// Only changes with `to < occur_sn < from` are returned, either bound being optional.
+pub async fn subscription_changes(
+    user_id: &UserId,
+    from: Option<Seqnum>,
+    to: Option<Seqnum>,
+    limit: i64,
+) -> DataResult<Vec<DbThreadSubscription>> {
+    let mut query = thread_subscriptions::table
+        .filter(thread_subscriptions::user_id.eq(user_id))
+        .into_boxed();
+    if let Some(from) = from {
+        query = query.filter(thread_subscriptions::occur_sn.lt(from));
+    }
+    if let Some(to) = to {
+        query = query.filter(thread_subscriptions::occur_sn.gt(to));
+    }
+    query
+        .order_by(thread_subscriptions::occur_sn.desc())
+        .limit(limit)
+        .load::<DbThreadSubscription>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
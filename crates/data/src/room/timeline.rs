@@ -1,9 +1,10 @@
 use diesel::prelude::*;
 use diesel::result::Error as DieselError;
-use diesel_async::{AsyncConnection, RunQueryDsl};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
 
 use crate::core::UnixMillis;
 use crate::core::identifiers::*;
+use crate::core::serde::JsonValue;
 use crate::room::DbEvent;
 use crate::schema::*;
 use crate::{DataResult, connect};
@@ -66,7 +67,88 @@ pub async fn purge_room_history(room_id: &RoomId, before_ts: i64) -> DataResult<
     }
 
     let event_ids: Vec<&str> = to_purge.iter().map(|(id, _)| id.as_str()).collect();
+    delete_events(&mut conn, &event_ids).await?;
 
+    Ok(to_purge.len() as i64)
+}
+
+/// Purge expired non-state events of a room for `m.room.retention`.
+///
+/// Unlike [`purge_room_history`], the current forward extremities are kept so
+/// the room always has events to build on; see [`expired_forward_extremities`]
+/// for pruning their content. Returns the JSON of the purged events so the
+/// caller can clean up media they referenced.
+pub async fn purge_expired_events(
+    room_id: &RoomId,
+    before_ts: UnixMillis,
+) -> DataResult<Vec<JsonValue>> {
+    let mut conn = connect().await?;
+
+    let event_ids: Vec<String> = events::table
+        .filter(events::room_id.eq(room_id))
+        .filter(events::origin_server_ts.lt(before_ts))
+        .filter(events::state_key.is_null())
+        .filter(
+            events::id.ne_all(
+                event_forward_extremities::table
+                    .filter(event_forward_extremities::room_id.eq(room_id))
+                    .select(event_forward_extremities::event_id),
+            ),
+        )
+        .select(events::id)
+        .load::<String>(&mut conn)
+        .await?;
+
+    if event_ids.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let event_ids: Vec<&str> = event_ids.iter().map(|id| id.as_str()).collect();
+    let mut purged = Vec::with_capacity(event_ids.len());
+    for chunk in event_ids.chunks(500) {
+        purged.extend(
+            event_datas::table
+                .filter(event_datas::event_id.eq_any(chunk))
+                .select(event_datas::json_data)
+                .load::<JsonValue>(&mut conn)
+                .await?,
+        );
+    }
+    delete_events(&mut conn, &event_ids).await?;
+
+    Ok(purged)
+}
+
+/// Expired non-state forward extremities of a room whose content is not pruned
+/// yet, with their JSON.
+///
+/// [`purge_expired_events`] keeps these events for the room DAG, so only their
+/// content can be dropped.
+pub async fn expired_forward_extremities(
+    room_id: &RoomId,
+    before_ts: UnixMillis,
+) -> DataResult<Vec<(OwnedEventId, JsonValue)>> {
+    events::table
+        .inner_join(event_datas::table.on(event_datas::event_id.eq(events::id)))
+        .filter(events::room_id.eq(room_id))
+        .filter(events::origin_server_ts.lt(before_ts))
+        .filter(events::state_key.is_null())
+        .filter(events::is_redacted.eq(false))
+        .filter(
+            events::id.eq_any(
+                event_forward_extremities::table
+                    .filter(event_forward_extremities::room_id.eq(room_id))
+                    .select(event_forward_extremities::event_id),
+            ),
+        )
+        .select((events::id, event_datas::json_data))
+        .load::<(OwnedEventId, JsonValue)>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
+/// Delete events and every row referencing them.
+async fn delete_events(conn: &mut AsyncPgConnection, event_ids: &[&str]) -> DataResult<()> {
     // Run all cascading deletes inside a single transaction so we cannot leave
     // dangling rows in event_datas/event_edges/etc. if one of the statements
     // fails mid-purge (transient db error, statement timeout, ...). Without
@@ -103,6 +185,11 @@ pub async fn purge_room_history(room_id: &RoomId, before_ts: i64) -> DataResult<
             diesel::delete(event_searches::table.filter(event_searches::event_id.eq_any(chunk)))
                 .execute(conn)
                 .await?;
+            diesel::delete(
+                event_media_refs::table.filter(event_media_refs::event_id.eq_any(chunk)),
+            )
+            .execute(conn)
+            .await?;
             diesel::delete(
                 event_push_actions::table.filter(event_push_actions::event_id.eq_any(chunk)),
             )
@@ -117,6 +204,16 @@ pub async fn purge_room_history(room_id: &RoomId, before_ts: i64) -> DataResult<
             diesel::delete(threads::table.filter(threads::event_id.eq_any(chunk)))
                 .execute(conn)
                 .await?;
+            diesel::delete(
+                thread_participants::table.filter(thread_participants::thread_id.eq_any(chunk)),
+            )
+            .execute(conn)
+            .await?;
+            diesel::delete(
+                thread_subscriptions::table.filter(thread_subscriptions::thread_id.eq_any(chunk)),
+            )
+            .execute(conn)
+            .await?;
             diesel::delete(timeline_gaps::table.filter(timeline_gaps::event_id.eq_any(chunk)))
                 .execute(conn)
                 .await?;
@@ -129,7 +226,7 @@ pub async fn purge_room_history(room_id: &RoomId, before_ts: i64) -> DataResult<
     })
     .await?;
 
-    Ok(to_purge.len() as i64)
+    Ok(())
 }
 
 /// Get PDU by timestamp
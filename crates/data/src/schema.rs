@@ -260,6 +260,16 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    event_media_refs (event_id, mxc) {
+        event_id -> Text,
+        mxc -> Text,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use crate::full_text_search::*;
@@ -402,6 +412,7 @@ diesel::table! {
         vector -> Tsvector,
         origin_server_ts -> Int8,
         stream_ordering -> Nullable<Int8>,
+        language -> Text,
     }
 }
 
@@ -588,6 +599,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    room_reports (id) {
+        id -> Int8,
+        received_ts -> Int8,
+        status -> Text,
+        room_id -> Text,
+        user_id -> Text,
+        reason -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use crate::full_text_search::*;
@@ -687,6 +712,42 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    sending_instances (instance_id) {
+        instance_id -> Text,
+        started_at -> Int8,
+        heartbeat_at -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    sending_leases (queue_key) {
+        queue_key -> Text,
+        instance_id -> Text,
+        acquired_at -> Int8,
+        expires_at -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    server_keypairs (version) {
+        version -> Text,
+        document -> Text,
+        public_key -> Text,
+        activated_at -> Int8,
+        expired_at -> Nullable<Int8>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use crate::full_text_search::*;
@@ -740,6 +801,35 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    thread_participants (id) {
+        id -> Int8,
+        room_id -> Text,
+        thread_id -> Text,
+        user_id -> Text,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    thread_subscriptions (id) {
+        id -> Int8,
+        user_id -> Text,
+        room_id -> Text,
+        thread_id -> Text,
+        subscribed -> Bool,
+        automatic -> Bool,
+        unsubscribed_at_sn -> Nullable<Int8>,
+        occur_sn -> Int8,
+        created_at -> Int8,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use crate::full_text_search::*;
@@ -868,6 +958,38 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    user_erasures (user_id) {
+        user_id -> Text,
+        requested_at -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    user_directory (user_id) {
+        user_id -> Text,
+        display_name -> Nullable<Text>,
+        avatar_url -> Nullable<Text>,
+        search_text -> Text,
+        updated_at -> Int8,
+    }
+}
+
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    user_directory_public_rooms (room_id) {
+        room_id -> Text,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use crate::full_text_search::*;
@@ -1058,6 +1180,20 @@ diesel::table! {
     }
 }
 
+diesel::table! {
+    use diesel::sql_types::*;
+    use crate::full_text_search::*;
+
+    user_reports (id) {
+        id -> Int8,
+        received_ts -> Int8,
+        status -> Text,
+        target_user_id -> Text,
+        user_id -> Text,
+        reason -> Nullable<Text>,
+    }
+}
+
 diesel::table! {
     use diesel::sql_types::*;
     use crate::full_text_search::*;
@@ -1178,6 +1314,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     event_edges,
     event_forward_extremities,
     event_idempotents,
+    event_media_refs,
     event_missings,
     event_phases,
     event_points,
@@ -1199,6 +1336,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     room_lookup_servers,
     room_peeking_servers,
     room_peeks,
+    room_reports,
     room_state_deltas,
     room_state_fields,
     room_state_frames,
@@ -1206,11 +1344,16 @@ diesel::allow_tables_to_appear_in_same_query!(
     room_typings,
     room_users,
     rooms,
+    sending_instances,
+    sending_leases,
     sliding_sync_connections,
+    server_keypairs,
     server_signing_keys,
     stats_monthly_active_users,
     stats_room_currents,
     stats_user_daily_visits,
+    thread_participants,
+    thread_subscriptions,
     threads,
     threepid_guests,
     threepid_id_servers,
@@ -1221,6 +1364,9 @@ diesel::allow_tables_to_appear_in_same_query!(
     user_datas,
     user_dehydrated_devices,
     user_devices,
+    user_erasures,
+    user_directory,
+    user_directory_public_rooms,
     user_external_ids,
     user_filters,
     user_ignores,
@@ -1234,6 +1380,7 @@ diesel::allow_tables_to_appear_in_same_query!(
     user_ratelimit_override,
     user_refresh_tokens,
     user_registration_tokens,
+    user_reports,
     user_sessions,
     user_threepids,
     user_uiaa_datas,
@@ -167,6 +167,18 @@ pub async fn persist_retry_state(dest: OutgoingDestination<'_>, tries: u32) -> D
     Ok(())
 }
 
+/// Failed attempts persisted for the active requests of a federation destination.
+pub async fn destination_retry_count(server: &ServerName) -> DataResult<u32> {
+    let tries = outgoing_requests::table
+        .filter(outgoing_requests::kind.eq("normal"))
+        .filter(outgoing_requests::server_id.eq(server))
+        .filter(outgoing_requests::state.eq("pending"))
+        .select(diesel::dsl::max(outgoing_requests::retry_count))
+        .first::<Option<i32>>(&mut connect().await?)
+        .await?;
+    Ok(tries.unwrap_or_default().max(0) as u32)
+}
+
 /// Reset retry timings for a destination
 pub async fn reset_destination_retry(server: &ServerName) -> DataResult<()> {
     diesel::update(
@@ -182,3 +194,143 @@ pub async fn reset_destination_retry(server: &ServerName) -> DataResult<()> {
     .await?;
     Ok(())
 }
+
+/// An instance sharing the sending queues.
+#[derive(Identifiable, Queryable, Debug, Clone)]
+#[diesel(table_name = sending_instances, primary_key(instance_id))]
+pub struct DbSendingInstance {
+    pub instance_id: String,
+    pub started_at: i64,
+    pub heartbeat_at: i64,
+}
+
+/// Ownership of a sending queue by an instance, valid until `expires_at`.
+#[derive(Identifiable, Queryable, Debug, Clone)]
+#[diesel(table_name = sending_leases, primary_key(queue_key))]
+pub struct DbSendingLease {
+    pub queue_key: String,
+    pub instance_id: String,
+    pub acquired_at: i64,
+    pub expires_at: i64,
+}
+
+/// Record that `instance_id` is alive, and forget instances that stopped
+/// beating before `stale_before` along with leases that expired before it.
+pub async fn heartbeat_sending_instance(instance_id: &str, stale_before: i64) -> DataResult<()> {
+    let now = UnixMillis::now().get() as i64;
+    let mut conn = connect().await?;
+    diesel::insert_into(sending_instances::table)
+        .values((
+            sending_instances::instance_id.eq(instance_id),
+            sending_instances::started_at.eq(now),
+            sending_instances::heartbeat_at.eq(now),
+        ))
+        .on_conflict(sending_instances::instance_id)
+        .do_update()
+        .set(sending_instances::heartbeat_at.eq(now))
+        .execute(&mut conn)
+        .await?;
+    diesel::delete(
+        sending_instances::table.filter(sending_instances::heartbeat_at.lt(stale_before)),
+    )
+    .execute(&mut conn)
+    .await?;
+    diesel::delete(sending_leases::table.filter(sending_leases::expires_at.lt(stale_before)))
+        .execute(&mut conn)
+        .await?;
+    Ok(())
+}
+
+/// Instances that beat since `alive_since`.
+pub async fn live_sending_instances(alive_since: i64) -> DataResult<Vec<DbSendingInstance>> {
+    sending_instances::table
+        .filter(sending_instances::heartbeat_at.ge(alive_since))
+        .order_by(sending_instances::instance_id)
+        .load::<DbSendingInstance>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
+/// Leases that have not expired yet.
+pub async fn live_sending_leases() -> DataResult<Vec<DbSendingLease>> {
+    let now = UnixMillis::now().get() as i64;
+    sending_leases::table
+        .filter(sending_leases::expires_at.ge(now))
+        .order_by((sending_leases::instance_id, sending_leases::queue_key))
+        .load::<DbSendingLease>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
+/// Take or extend the lease on `queue_key` for `instance_id`.
+///
+/// Succeeds when the queue has no lease, its lease expired, or `instance_id`
+/// already holds it. Each statement is atomic, so two instances can never both
+/// succeed for the same unexpired lease.
+pub async fn acquire_sending_lease(
+    queue_key: &str,
+    instance_id: &str,
+    ttl_ms: i64,
+) -> DataResult<bool> {
+    let now = UnixMillis::now().get() as i64;
+    let mut conn = connect().await?;
+    let inserted = diesel::insert_into(sending_leases::table)
+        .values((
+            sending_leases::queue_key.eq(queue_key),
+            sending_leases::instance_id.eq(instance_id),
+            sending_leases::acquired_at.eq(now),
+            sending_leases::expires_at.eq(now + ttl_ms),
+        ))
+        .on_conflict_do_nothing()
+        .execute(&mut conn)
+        .await?;
+    if inserted == 1 {
+        return Ok(true);
+    }
+    let taken_over = diesel::update(
+        sending_leases::table
+            .filter(sending_leases::queue_key.eq(queue_key))
+            .filter(
+                sending_leases::instance_id
+                    .eq(instance_id)
+                    .or(sending_leases::expires_at.lt(now)),
+            ),
+    )
+    .set((
+        sending_leases::instance_id.eq(instance_id),
+        sending_leases::acquired_at.eq(now),
+        sending_leases::expires_at.eq(now + ttl_ms),
+    ))
+    .execute(&mut conn)
+    .await?;
+    Ok(taken_over == 1)
+}
+
+/// Extend every unexpired lease of `instance_id`, returning the queues it
+/// still holds. Leases that expired in the meantime may have been taken over
+/// and are not extended.
+pub async fn renew_sending_leases(instance_id: &str, ttl_ms: i64) -> DataResult<Vec<String>> {
+    let now = UnixMillis::now().get() as i64;
+    diesel::update(
+        sending_leases::table
+            .filter(sending_leases::instance_id.eq(instance_id))
+            .filter(sending_leases::expires_at.ge(now)),
+    )
+    .set(sending_leases::expires_at.eq(now + ttl_ms))
+    .returning(sending_leases::queue_key)
+    .get_results::<String>(&mut connect().await?)
+    .await
+    .map_err(Into::into)
+}
+
+/// Give up the lease of `instance_id` on `queue_key`, if it still holds it.
+pub async fn release_sending_lease(queue_key: &str, instance_id: &str) -> DataResult<()> {
+    diesel::delete(
+        sending_leases::table
+            .filter(sending_leases::queue_key.eq(queue_key))
+            .filter(sending_leases::instance_id.eq(instance_id)),
+    )
+    .execute(&mut connect().await?)
+    .await?;
+    Ok(())
+}
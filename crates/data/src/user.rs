@@ -1,4 +1,5 @@
 pub mod device;
+pub mod directory;
 pub use device::{DbUserDevice, NewDbUserDevice};
 mod password;
 pub use password::*;
@@ -27,14 +28,16 @@ pub mod openid_token;
 pub mod presence;
 pub mod registration_token;
 pub mod uiaa;
+pub mod user_report;
 use std::mem;
 
 use diesel::dsl;
 use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use diesel_async::{AsyncConnection, RunQueryDsl};
 pub use external_id::*;
 pub use presence::*;
 pub use registration_token::*;
+pub use user_report::*;
 
 use crate::core::client::dehydrated_device::DehydratedDeviceData;
 use crate::core::events::AnyStrippedStateEvent;
@@ -42,7 +45,7 @@ use crate::core::identifiers::*;
 use crate::core::serde::{JsonValue, RawJson};
 use crate::core::{OwnedMxcUri, UnixMillis};
 use crate::schema::*;
-use crate::{DataError, DataResult, connect};
+use crate::{DataError, DataResult, connect, connect_replica};
 
 #[derive(Insertable, Identifiable, Queryable, Debug, Clone)]
 #[diesel(table_name = users)]
@@ -141,6 +144,18 @@ pub async fn is_admin(user_id: &UserId) -> DataResult<bool> {
         .map_err(Into::into)
 }
 
+/// The latest membership of a user in every room they have been part of.
+pub async fn room_memberships(user_id: &UserId) -> DataResult<Vec<(OwnedRoomId, String)>> {
+    room_users::table
+        .filter(room_users::user_id.eq(user_id))
+        .distinct_on(room_users::room_id)
+        .select((room_users::room_id, room_users::membership))
+        .order_by((room_users::room_id.desc(), room_users::id.desc()))
+        .load::<(OwnedRoomId, String)>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
 /// Returns an iterator over all rooms this user joined.
 pub async fn joined_rooms(user_id: &UserId) -> DataResult<Vec<OwnedRoomId>> {
     let room_memeberships = room_users::table
@@ -299,9 +314,8 @@ pub async fn set_display_name(user_id: &UserId, display_name: &str) -> DataResul
     )
     .set(user_profiles::display_name.eq(display_name))
     .execute(&mut connect().await?)
-    .await
-    .map(|_| ())
-    .map_err(Into::into)
+    .await?;
+    directory::sync_profile(user_id).await
 }
 pub async fn remove_display_name(user_id: &UserId) -> DataResult<()> {
     diesel::update(
@@ -311,9 +325,8 @@ pub async fn remove_display_name(user_id: &UserId) -> DataResult<()> {
     )
     .set(user_profiles::display_name.eq::<Option<String>>(None))
     .execute(&mut connect().await?)
-    .await
-    .map(|_| ())
-    .map_err(Into::into)
+    .await?;
+    directory::sync_profile(user_id).await
 }
 
 /// Get the avatar_url of a user.
@@ -337,7 +350,7 @@ pub async fn set_avatar_url(user_id: &UserId, avatar_url: &MxcUri) -> DataResult
     .set(user_profiles::avatar_url.eq(avatar_url.as_str()))
     .execute(&mut connect().await?)
     .await?;
-    Ok(())
+    directory::sync_profile(user_id).await
 }
 pub async fn remove_avatar_url(user_id: &UserId) -> DataResult<()> {
     diesel::update(
@@ -347,9 +360,8 @@ pub async fn remove_avatar_url(user_id: &UserId) -> DataResult<()> {
     )
     .set(user_profiles::avatar_url.eq::<Option<String>>(None))
     .execute(&mut connect().await?)
-    .await
-    .map(|_| ())
-    .map_err(Into::into)
+    .await?;
+    directory::sync_profile(user_id).await
 }
 
 pub async fn delete_profile(user_id: &UserId) -> DataResult<()> {
@@ -360,9 +372,134 @@ pub async fn delete_profile(user_id: &UserId) -> DataResult<()> {
     )
     .execute(&mut connect().await?)
     .await?;
+    directory::sync_profile(user_id).await
+}
+
+/// Delete everything stored about a user's account that erasure must not keep:
+/// account data (including push rules), cross-signing keys, key backups,
+/// profiles and their directory entry, the profile cached with their room
+/// memberships, third-party identifiers, filters and presence.
+///
+/// Devices, device keys and pushers are removed by [`remove_all_devices`].
+pub async fn erase(user_id: &UserId) -> DataResult<()> {
+    let mut conn = connect().await?;
+    conn.transaction::<_, DataError, _>(async |conn| {
+        diesel::delete(user_datas::table.filter(user_datas::user_id.eq(user_id)))
+            .execute(conn)
+            .await?;
+        diesel::delete(
+            e2e_cross_signing_keys::table.filter(e2e_cross_signing_keys::user_id.eq(user_id)),
+        )
+        .execute(conn)
+        .await?;
+        diesel::delete(
+            e2e_cross_signing_sigs::table
+                .filter(e2e_cross_signing_sigs::origin_user_id.eq(user_id)),
+        )
+        .execute(conn)
+        .await?;
+        diesel::delete(e2e_room_keys::table.filter(e2e_room_keys::user_id.eq(user_id)))
+            .execute(conn)
+            .await?;
+        diesel::delete(
+            e2e_room_keys_versions::table.filter(e2e_room_keys_versions::user_id.eq(user_id)),
+        )
+        .execute(conn)
+        .await?;
+        diesel::delete(user_profiles::table.filter(user_profiles::user_id.eq(user_id)))
+            .execute(conn)
+            .await?;
+        diesel::delete(user_directory::table.filter(user_directory::user_id.eq(user_id)))
+            .execute(conn)
+            .await?;
+        diesel::delete(user_threepids::table.filter(user_threepids::user_id.eq(user_id)))
+            .execute(conn)
+            .await?;
+        diesel::delete(user_filters::table.filter(user_filters::user_id.eq(user_id)))
+            .execute(conn)
+            .await?;
+        diesel::delete(user_presences::table.filter(user_presences::user_id.eq(user_id)))
+            .execute(conn)
+            .await?;
+        diesel::update(room_users::table.filter(room_users::user_id.eq(user_id)))
+            .set((
+                room_users::display_name.eq(None::<String>),
+                room_users::avatar_url.eq(None::<String>),
+            ))
+            .execute(conn)
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Ids of the events sent by a user that are not redacted yet, state events
+/// such as their membership included.
+pub async fn unredacted_event_ids(
+    user_id: &UserId,
+) -> DataResult<Vec<(OwnedRoomId, OwnedEventId)>> {
+    events::table
+        .filter(events::sender_id.eq(user_id))
+        .filter(events::is_redacted.eq(false))
+        .filter(events::is_outlier.eq(false))
+        .filter(events::is_rejected.eq(false))
+        .filter(events::ty.ne("m.room.redaction"))
+        .order(events::sn.asc())
+        .select((events::room_id, events::id))
+        .load(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
+/// Record that the events of an erased user still have to be pruned, so the
+/// pruning resumes if the server restarts before it is done.
+pub async fn add_pending_erasure(user_id: &UserId) -> DataResult<()> {
+    diesel::insert_into(user_erasures::table)
+        .values((
+            user_erasures::user_id.eq(user_id),
+            user_erasures::requested_at.eq(UnixMillis::now()),
+        ))
+        .on_conflict_do_nothing()
+        .execute(&mut connect().await?)
+        .await?;
     Ok(())
 }
 
+/// Users whose events are still to be pruned after their erasure.
+pub async fn pending_erasures() -> DataResult<Vec<OwnedUserId>> {
+    user_erasures::table
+        .select(user_erasures::user_id)
+        .load(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
+pub async fn remove_pending_erasure(user_id: &UserId) -> DataResult<()> {
+    diesel::delete(user_erasures::table.find(user_id))
+        .execute(&mut connect().await?)
+        .await?;
+    Ok(())
+}
+
+/// Page through the events sent by a user, oldest first.
+pub async fn sent_events(
+    user_id: &UserId,
+    since_sn: i64,
+    limit: i64,
+) -> DataResult<Vec<(i64, JsonValue)>> {
+    events::table
+        .inner_join(event_datas::table.on(event_datas::event_id.eq(events::id)))
+        .filter(events::sender_id.eq(user_id))
+        .filter(events::sn.gt(since_sn))
+        .filter(events::is_outlier.eq(false))
+        .order(events::sn.asc())
+        .limit(limit)
+        .select((events::sn, event_datas::json_data))
+        .load(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
 /// Get the blurhash of a user.
 pub async fn blurhash(user_id: &UserId) -> DataResult<Option<String>> {
     user_profiles::table
@@ -873,7 +1010,7 @@ pub async fn list_users(filter: &ListUsersFilter) -> DataResult<(Vec<DbUser>, i6
     // Get total count with filters applied
     let total: i64 = count_query
         .count()
-        .get_result(&mut connect().await?)
+        .get_result(&mut connect_replica().await?)
         .await?;
 
     // Apply ordering
@@ -931,7 +1068,7 @@ pub async fn list_users(filter: &ListUsersFilter) -> DataResult<(Vec<DbUser>, i6
     let limit = filter.limit.unwrap_or(100).min(1000);
     query = query.limit(limit);
 
-    let users = query.load::<DbUser>(&mut connect().await?).await?;
+    let users = query.load::<DbUser>(&mut connect_replica().await?).await?;
 
     Ok((users, total))
 }
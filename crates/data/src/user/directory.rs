@@ -0,0 +1,224 @@
+use diesel::prelude::*;
+use diesel::sql_types::{Array, Bool, Nullable, Text};
+use diesel_async::RunQueryDsl;
+
+use crate::core::identifiers::*;
+use crate::core::{OwnedMxcUri, UnixMillis};
+use crate::schema::*;
+use crate::{DataResult, connect, connect_replica};
+
+/// Search terms are split into at most this many words, each of which must match.
+const MAX_SEARCH_WORDS: usize = 8;
+
+#[derive(Insertable, AsChangeset, Debug, Clone)]
+#[diesel(table_name = user_directory, treat_none_as_null = true)]
+pub struct NewDbUserDirectoryEntry {
+    pub user_id: OwnedUserId,
+    pub display_name: Option<String>,
+    pub avatar_url: Option<OwnedMxcUri>,
+    pub search_text: String,
+    pub updated_at: UnixMillis,
+}
+
+impl NewDbUserDirectoryEntry {
+    pub fn new(
+        user_id: OwnedUserId,
+        display_name: Option<String>,
+        avatar_url: Option<OwnedMxcUri>,
+    ) -> Self {
+        let search_text = match &display_name {
+            Some(display_name) => format!("{user_id} {display_name}"),
+            None => user_id.to_string(),
+        }
+        .to_lowercase();
+        Self {
+            user_id,
+            display_name,
+            avatar_url,
+            search_text,
+            updated_at: UnixMillis::now(),
+        }
+    }
+}
+
+#[derive(QueryableByName, Debug, Clone)]
+pub struct DbUserDirectoryEntry {
+    #[diesel(sql_type = Text)]
+    pub user_id: OwnedUserId,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub display_name: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    pub avatar_url: Option<OwnedMxcUri>,
+}
+
+/// Users visible to a search, besides those sharing a room with the searcher.
+#[derive(Clone, Copy, Debug)]
+pub struct DirectoryVisibility {
+    /// Members of rooms with a public join rule.
+    pub public_rooms: bool,
+    /// Every local user.
+    pub local_users: bool,
+    /// Users registered through an application service.
+    pub appservice_users: bool,
+}
+
+pub async fn upsert_entry(entry: &NewDbUserDirectoryEntry) -> DataResult<()> {
+    diesel::insert_into(user_directory::table)
+        .values(entry)
+        .on_conflict(user_directory::user_id)
+        .do_update()
+        .set(entry)
+        .execute(&mut connect().await?)
+        .await?;
+    Ok(())
+}
+
+/// Refresh the entry of a user from their global profile.
+pub async fn sync_profile(user_id: &UserId) -> DataResult<()> {
+    let profile = user_profiles::table
+        .filter(user_profiles::user_id.eq(user_id))
+        .filter(user_profiles::room_id.is_null())
+        .select((user_profiles::display_name, user_profiles::avatar_url))
+        .first::<(Option<String>, Option<OwnedMxcUri>)>(&mut connect().await?)
+        .await
+        .optional()?;
+    match profile {
+        Some((display_name, avatar_url)) => {
+            upsert_entry(&NewDbUserDirectoryEntry::new(
+                user_id.to_owned(),
+                display_name,
+                avatar_url,
+            ))
+            .await
+        }
+        None => remove_entry(user_id).await,
+    }
+}
+
+pub async fn remove_entry(user_id: &UserId) -> DataResult<()> {
+    diesel::delete(user_directory::table.filter(user_directory::user_id.eq(user_id)))
+        .execute(&mut connect().await?)
+        .await?;
+    Ok(())
+}
+
+/// Record whether the members of a room are visible to everyone searching the directory.
+pub async fn set_public_room(room_id: &RoomId, public: bool) -> DataResult<()> {
+    let mut conn = connect().await?;
+    if public {
+        diesel::insert_into(user_directory_public_rooms::table)
+            .values(user_directory_public_rooms::room_id.eq(room_id))
+            .on_conflict_do_nothing()
+            .execute(&mut conn)
+            .await?;
+    } else {
+        diesel::delete(
+            user_directory_public_rooms::table
+                .filter(user_directory_public_rooms::room_id.eq(room_id)),
+        )
+        .execute(&mut conn)
+        .await?;
+    }
+    Ok(())
+}
+
+fn escape_like(term: &str) -> String {
+    term.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+/// Search the directory for users matching every word of `search_term`.
+///
+/// Users whose localpart or display name start with the term rank first, the rest by
+/// trigram similarity. Deactivated users, the searcher themselves and user IDs matching
+/// one of the `excluded_users` regular expressions are never returned.
+pub async fn search(
+    searcher: &UserId,
+    search_term: &str,
+    visibility: DirectoryVisibility,
+    excluded_users: &[String],
+    limit: i64,
+) -> DataResult<Vec<DbUserDirectoryEntry>> {
+    let term = search_term.trim().to_lowercase();
+    let words = term
+        .split_whitespace()
+        .take(MAX_SEARCH_WORDS)
+        .map(|word| format!("%{}%", escape_like(word)))
+        .collect::<Vec<_>>();
+    if words.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // $1 searcher, $2..$4 visibility, $5 localpart prefix, $6 display name prefix,
+    // $7 whole term, $8 excluded user patterns, then one pattern per word.
+    let word_filters = (0..words.len())
+        .map(|i| format!(" AND d.search_text LIKE ${}", i + 9))
+        .collect::<String>();
+    let sql = format!(
+        "SELECT d.user_id, d.display_name, d.avatar_url \
+        FROM user_directory d \
+        JOIN users u ON u.id = d.user_id \
+        WHERE d.user_id <> $1 \
+            AND u.deactivated_at IS NULL \
+            AND ($4 OR u.appservice_id IS NULL) \
+            AND NOT (d.user_id ~ ANY($8)){word_filters} \
+            AND (EXISTS (\
+                    SELECT 1 FROM room_users mine \
+                    JOIN room_users theirs ON theirs.room_id = mine.room_id \
+                    WHERE mine.user_id = $1 AND mine.membership = 'join' \
+                        AND theirs.user_id = d.user_id AND theirs.membership = 'join'\
+                ) \
+                OR ($2 AND EXISTS (\
+                    SELECT 1 FROM room_users theirs \
+                    JOIN user_directory_public_rooms p ON p.room_id = theirs.room_id \
+                    WHERE theirs.user_id = d.user_id AND theirs.membership = 'join'\
+                )) \
+                OR ($3 AND u.is_local)) \
+        ORDER BY (d.user_id LIKE $5 OR lower(coalesce(d.display_name, '')) LIKE $6) DESC, \
+            similarity(d.search_text, $7) DESC, \
+            d.display_name IS NULL, \
+            d.user_id \
+        LIMIT {limit}"
+    );
+
+    let prefix = escape_like(&term);
+    let mut query = diesel::sql_query(sql)
+        .into_boxed::<diesel::pg::Pg>()
+        .bind::<Text, _>(searcher.as_str())
+        .bind::<Bool, _>(visibility.public_rooms)
+        .bind::<Bool, _>(visibility.local_users)
+        .bind::<Bool, _>(visibility.appservice_users)
+        .bind::<Text, _>(format!("@{prefix}%"))
+        .bind::<Text, _>(format!("{prefix}%"))
+        .bind::<Text, _>(term.clone())
+        .bind::<Array<Text>, _>(excluded_users);
+    for word in words {
+        query = query.bind::<Text, _>(word);
+    }
+    query
+        .load::<DbUserDirectoryEntry>(&mut connect_replica().await?)
+        .await
+        .map_err(Into::into)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{NewDbUserDirectoryEntry, escape_like};
+    use crate::core::owned_user_id;
+
+    #[test]
+    fn like_wildcards_in_search_terms_are_literal() {
+        assert_eq!(escape_like("50%_off\\"), "50\\%\\_off\\\\");
+    }
+
+    #[test]
+    fn search_text_covers_user_id_and_display_name() {
+        let entry = NewDbUserDirectoryEntry::new(
+            owned_user_id!("@alice:example.org"),
+            Some("Alice Liddell".to_owned()),
+            None,
+        );
+        assert_eq!(entry.search_text, "@alice:example.org alice liddell");
+    }
+}
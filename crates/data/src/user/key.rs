@@ -1,7 +1,7 @@
 use std::collections::BTreeMap;
 
 use diesel::prelude::*;
-use diesel_async::RunQueryDsl;
+use diesel_async::{AsyncConnection, RunQueryDsl};
 
 use crate::core::encryption::{CrossSigningKey, DeviceKeys, OneTimeKey};
 use crate::core::identifiers::*;
@@ -489,92 +489,138 @@ pub async fn add_one_time_key(
 }
 
 /// Insert or replace a fallback key for a device and algorithm.
+///
+/// Re-uploading the current key keeps its used marker, so that clients which
+/// upload the same fallback key again are still told to rotate it.
 pub async fn add_fallback_key(
     user_id: &UserId,
     device_id: &DeviceId,
     key_id: &DeviceKeyId,
     fallback_key: &OneTimeKey,
 ) -> DataResult<()> {
-    diesel::delete(
-        e2e_fallback_keys::table
+    let key_data = serde_json::to_value(fallback_key)?;
+    let mut conn = connect().await?;
+    conn.transaction::<_, crate::DataError, _>(async |conn| {
+        let existing = e2e_fallback_keys::table
             .filter(e2e_fallback_keys::user_id.eq(user_id))
             .filter(e2e_fallback_keys::device_id.eq(device_id))
-            .filter(e2e_fallback_keys::algorithm.eq(key_id.algorithm().to_string())),
-    )
-    .execute(&mut connect().await?)
-    .await?;
+            .filter(e2e_fallback_keys::algorithm.eq(key_id.algorithm().to_string()))
+            .for_update()
+            .first::<DbFallbackKey>(conn)
+            .await
+            .optional()?;
+        if let Some(existing) = existing {
+            if &*existing.key_id == key_id && existing.key_data == key_data {
+                return Ok(());
+            }
+            diesel::delete(e2e_fallback_keys::table.find(existing.id))
+                .execute(conn)
+                .await?;
+        }
 
-    diesel::insert_into(e2e_fallback_keys::table)
-        .values(&NewDbFallbackKey {
-            user_id: user_id.to_owned(),
-            device_id: device_id.to_owned(),
-            algorithm: key_id.algorithm().to_string(),
-            key_id: key_id.to_owned(),
-            key_data: serde_json::to_value(fallback_key)?,
-            used_at: None,
-            created_at: UnixMillis::now(),
-        })
-        .execute(&mut connect().await?)
+        diesel::insert_into(e2e_fallback_keys::table)
+            .values(&NewDbFallbackKey {
+                user_id: user_id.to_owned(),
+                device_id: device_id.to_owned(),
+                algorithm: key_id.algorithm().to_string(),
+                key_id: key_id.to_owned(),
+                key_data,
+                used_at: None,
+                created_at: UnixMillis::now(),
+            })
+            .execute(conn)
+            .await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Algorithms of the device's fallback keys that have not been handed out yet.
+pub async fn unused_fallback_key_types(
+    user_id: &UserId,
+    device_id: &DeviceId,
+) -> DataResult<Vec<DeviceKeyAlgorithm>> {
+    let algorithms = e2e_fallback_keys::table
+        .filter(e2e_fallback_keys::user_id.eq(user_id))
+        .filter(e2e_fallback_keys::device_id.eq(device_id))
+        .filter(e2e_fallback_keys::used_at.is_null())
+        .select(e2e_fallback_keys::algorithm)
+        .load::<String>(&mut connect().await?)
         .await?;
-    Ok(())
+    Ok(algorithms
+        .into_iter()
+        .map(DeviceKeyAlgorithm::from)
+        .collect())
 }
 
 /// Claim (read and remove) the oldest one-time key for a device and algorithm.
+///
+/// When the device has no one-time key left, its fallback key is returned and
+/// marked as used instead. Rows are locked with `SKIP LOCKED`, so concurrent
+/// claims for the same device never hand out the same one-time key.
 pub async fn claim_one_time_key(
     user_id: &UserId,
     device_id: &DeviceId,
     key_algorithm: &DeviceKeyAlgorithm,
 ) -> DataResult<Option<(OwnedDeviceKeyId, OneTimeKey)>> {
-    let one_time_key = e2e_one_time_keys::table
-        .filter(e2e_one_time_keys::user_id.eq(user_id))
-        .filter(e2e_one_time_keys::device_id.eq(device_id))
-        .filter(e2e_one_time_keys::algorithm.eq(key_algorithm.as_ref()))
-        .order(e2e_one_time_keys::id.asc())
-        .first::<DbOneTimeKey>(&mut connect().await?)
-        .await
-        .optional()?;
-    if let Some(DbOneTimeKey {
-        id,
-        key_id,
-        key_data,
-        ..
-    }) = one_time_key
-    {
-        diesel::delete(e2e_one_time_keys::table.find(id))
-            .execute(&mut connect().await?)
-            .await?;
-        Ok(Some((
+    let mut conn = connect().await?;
+    conn.transaction::<_, crate::DataError, _>(async |conn| {
+        let one_time_key = e2e_one_time_keys::table
+            .filter(e2e_one_time_keys::user_id.eq(user_id))
+            .filter(e2e_one_time_keys::device_id.eq(device_id))
+            .filter(e2e_one_time_keys::algorithm.eq(key_algorithm.as_ref()))
+            .order(e2e_one_time_keys::id.asc())
+            .for_update()
+            .skip_locked()
+            .first::<DbOneTimeKey>(conn)
+            .await
+            .optional()?;
+        if let Some(DbOneTimeKey {
+            id,
             key_id,
-            serde_json::from_value::<OneTimeKey>(key_data)?,
-        )))
-    } else if let Some(DbFallbackKey {
-        id,
-        key_id,
-        key_data,
-        used_at,
-        ..
-    }) = e2e_fallback_keys::table
-        .filter(e2e_fallback_keys::user_id.eq(user_id))
-        .filter(e2e_fallback_keys::device_id.eq(device_id))
-        .filter(e2e_fallback_keys::algorithm.eq(key_algorithm.as_ref()))
-        .order(e2e_fallback_keys::id.desc())
-        .first::<DbFallbackKey>(&mut connect().await?)
-        .await
-        .optional()?
-    {
+            key_data,
+            ..
+        }) = one_time_key
+        {
+            diesel::delete(e2e_one_time_keys::table.find(id))
+                .execute(conn)
+                .await?;
+            return Ok(Some((
+                key_id,
+                serde_json::from_value::<OneTimeKey>(key_data)?,
+            )));
+        }
+
+        let fallback_key = e2e_fallback_keys::table
+            .filter(e2e_fallback_keys::user_id.eq(user_id))
+            .filter(e2e_fallback_keys::device_id.eq(device_id))
+            .filter(e2e_fallback_keys::algorithm.eq(key_algorithm.as_ref()))
+            .order(e2e_fallback_keys::id.desc())
+            .first::<DbFallbackKey>(conn)
+            .await
+            .optional()?;
+        let Some(DbFallbackKey {
+            id,
+            key_id,
+            key_data,
+            used_at,
+            ..
+        }) = fallback_key
+        else {
+            return Ok(None);
+        };
         if used_at.is_none() {
             diesel::update(e2e_fallback_keys::table.find(id))
                 .set(e2e_fallback_keys::used_at.eq(UnixMillis::now().get() as i64))
-                .execute(&mut connect().await?)
+                .execute(conn)
                 .await?;
         }
         Ok(Some((
             key_id,
             serde_json::from_value::<OneTimeKey>(key_data)?,
         )))
-    } else {
-        Ok(None)
-    }
+    })
+    .await
 }
 
 /// Replace the key-change marker for a `(user, room)` (or global when
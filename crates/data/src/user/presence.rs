@@ -112,6 +112,139 @@ pub async fn set_presence(db_presence: NewDbPresence, force: bool) -> DataResult
     Ok(state_changed)
 }
 
+pub async fn get_presence(user_id: &UserId) -> DataResult<Option<DbPresence>> {
+    user_presences::table
+        .filter(user_presences::user_id.eq(user_id))
+        .first::<DbPresence>(&mut connect().await?)
+        .await
+        .optional()
+        .map_err(Into::into)
+}
+
+/// Refresh the activity timestamps of a user without announcing a presence change.
+pub async fn touch_presence(
+    user_id: &UserId,
+    last_active_at: Option<UnixMillis>,
+    last_user_sync_at: Option<UnixMillis>,
+) -> DataResult<()> {
+    let query = user_presences::table.filter(user_presences::user_id.eq(user_id));
+    if let Some(last_active_at) = last_active_at {
+        diesel::update(query)
+            .set((
+                user_presences::last_active_at.eq(last_active_at),
+                user_presences::currently_active.eq(true),
+            ))
+            .execute(&mut connect().await?)
+            .await?;
+    }
+    if let Some(last_user_sync_at) = last_user_sync_at {
+        diesel::update(query)
+            .set(user_presences::last_user_sync_at.eq(last_user_sync_at))
+            .execute(&mut connect().await?)
+            .await?;
+    }
+    Ok(())
+}
+
+/// Moves local users inactive since `idle_before` from online to unavailable.
+///
+/// Returns the users this call changed. Each row is only changed once even when several
+/// server instances run this concurrently, as the state is re-checked by the update.
+pub async fn idle_local_presences(idle_before: UnixMillis) -> DataResult<Vec<DbPresence>> {
+    diesel::update(
+        user_presences::table
+            .filter(user_presences::user_id.eq_any(local_user_ids()))
+            .filter(user_presences::state.eq(PresenceState::Online.to_string()))
+            .filter(
+                user_presences::last_active_at
+                    .is_null()
+                    .or(user_presences::last_active_at.lt(idle_before)),
+            ),
+    )
+    .set((
+        user_presences::state.eq(PresenceState::Unavailable.to_string()),
+        user_presences::currently_active.eq(false),
+        user_presences::occur_sn.eq(next_occur_sn()),
+    ))
+    .returning(user_presences::all_columns)
+    .get_results::<DbPresence>(&mut connect().await?)
+    .await
+    .map_err(Into::into)
+}
+
+/// Moves local users that neither were active nor synced since `offline_before` offline.
+///
+/// Returns the users this call changed, see [`idle_local_presences`].
+pub async fn offline_local_presences(offline_before: UnixMillis) -> DataResult<Vec<DbPresence>> {
+    diesel::update(
+        user_presences::table
+            .filter(user_presences::user_id.eq_any(local_user_ids()))
+            .filter(user_presences::state.eq_any([
+                PresenceState::Online.to_string(),
+                PresenceState::Unavailable.to_string(),
+            ]))
+            .filter(
+                user_presences::last_active_at
+                    .is_null()
+                    .or(user_presences::last_active_at.lt(offline_before)),
+            )
+            .filter(
+                user_presences::last_user_sync_at
+                    .is_null()
+                    .or(user_presences::last_user_sync_at.lt(offline_before)),
+            ),
+    )
+    .set((
+        user_presences::state.eq(PresenceState::Offline.to_string()),
+        user_presences::currently_active.eq(false),
+        user_presences::occur_sn.eq(next_occur_sn()),
+    ))
+    .returning(user_presences::all_columns)
+    .get_results::<DbPresence>(&mut connect().await?)
+    .await
+    .map_err(Into::into)
+}
+
+/// Moves remote users whose server sent no presence update since `offline_before` offline.
+pub async fn offline_remote_presences(offline_before: UnixMillis) -> DataResult<usize> {
+    diesel::update(
+        user_presences::table
+            .filter(diesel::dsl::not(
+                user_presences::user_id.eq_any(local_user_ids()),
+            ))
+            .filter(user_presences::state.eq_any([
+                PresenceState::Online.to_string(),
+                PresenceState::Unavailable.to_string(),
+            ]))
+            .filter(
+                user_presences::last_federation_update_at
+                    .lt(offline_before)
+                    .or(user_presences::last_federation_update_at
+                        .is_null()
+                        .and(user_presences::last_active_at.lt(offline_before))),
+            ),
+    )
+    .set((
+        user_presences::state.eq(PresenceState::Offline.to_string()),
+        user_presences::currently_active.eq(false),
+        user_presences::occur_sn.eq(next_occur_sn()),
+    ))
+    .execute(&mut connect().await?)
+    .await
+    .map_err(Into::into)
+}
+
+fn local_user_ids() -> users::BoxedQuery<'static, diesel::pg::Pg, diesel::sql_types::Text> {
+    users::table
+        .filter(users::is_local.eq(true))
+        .select(users::id)
+        .into_boxed()
+}
+
+fn next_occur_sn() -> diesel::expression::SqlLiteral<diesel::sql_types::BigInt> {
+    diesel::dsl::sql::<diesel::sql_types::BigInt>("nextval('occur_sn_seq')")
+}
+
 /// Removes the presence record for the given user from the database.
 pub async fn remove_presence(user_id: &UserId) -> DataResult<()> {
     diesel::delete(user_presences::table.filter(user_presences::user_id.eq(user_id)))
@@ -38,6 +38,9 @@ pub async fn create_profile(profile: &NewDbProfile) -> DataResult<()> {
         .values(profile)
         .execute(&mut connect().await?)
         .await?;
+    if profile.room_id.is_none() {
+        super::directory::sync_profile(&profile.user_id).await?;
+    }
     Ok(())
 }
 
@@ -63,6 +66,16 @@ pub async fn get_profile(
     Ok(profile)
 }
 
+/// Get the global profile and every per-room profile of a user.
+pub async fn get_profiles(user_id: &UserId) -> DataResult<Vec<DbProfile>> {
+    user_profiles::table
+        .filter(user_profiles::user_id.eq(user_id))
+        .order(user_profiles::id.asc())
+        .load::<DbProfile>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
 pub async fn profile_fields(user_id: &UserId) -> DataResult<JsonObject> {
     let fields = user_profiles::table
         .filter(user_profiles::user_id.eq(user_id))
@@ -66,6 +66,7 @@ impl TryInto<Pusher> for DbPusher {
             pushkey,
             lang,
             data,
+            enabled,
             ..
         } = self;
         Ok(Pusher {
@@ -75,6 +76,7 @@ impl TryInto<Pusher> for DbPusher {
             app_display_name,
             device_display_name,
             lang,
+            enabled,
         })
     }
 }
@@ -130,14 +132,27 @@ pub async fn get_actions<'a>(
         supported_features: vec![],
         // #[cfg(feature = "unstable-msc4306")]
         has_thread_subscription_fn: None,
-    };
+    }
+    .with_has_thread_subscription_fn({
+        let user_id = user.to_owned();
+        move |thread_root| {
+            let user_id = user_id.clone();
+            Box::pin(async move {
+                crate::room::thread::is_subscribed(&user_id, thread_root)
+                    .await
+                    .unwrap_or(false)
+            })
+        }
+    });
 
     Ok(ruleset.get_actions(pdu, &ctx).await)
 }
 
+/// Pushkeys of the enabled pushers of `user_id`.
 pub async fn get_push_keys(user_id: &UserId) -> DataResult<Vec<String>> {
     user_pushers::table
         .filter(user_pushers::user_id.eq(user_id))
+        .filter(user_pushers::enabled.eq(true))
         .select(user_pushers::pushkey)
         .load::<String>(&mut connect().await?)
         .await
@@ -175,6 +190,72 @@ pub async fn delete_pusher(user_id: &UserId, app_id: &str, pushkey: &str) -> Dat
     Ok(())
 }
 
+/// Users with a pusher of the app id and pushkey.
+pub async fn pusher_owners(app_id: &str, pushkey: &str) -> DataResult<Vec<OwnedUserId>> {
+    user_pushers::table
+        .filter(user_pushers::app_id.eq(app_id))
+        .filter(user_pushers::pushkey.eq(pushkey))
+        .select(user_pushers::user_id)
+        .distinct()
+        .load::<OwnedUserId>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
+
+/// Record that the push gateway accepted a notification for the pusher.
+pub async fn mark_pusher_success(user_id: &UserId, pushkey: &str) -> DataResult<()> {
+    diesel::update(
+        user_pushers::table
+            .filter(user_pushers::user_id.eq(user_id))
+            .filter(user_pushers::pushkey.eq(pushkey)),
+    )
+    .set((
+        user_pushers::last_success.eq(UnixMillis::now().get() as i64),
+        user_pushers::failing_since.eq(None::<i64>),
+    ))
+    .execute(&mut connect().await?)
+    .await?;
+    Ok(())
+}
+
+/// Record that notifying the pusher failed, returning since when it has been
+/// failing.
+pub async fn mark_pusher_failure(user_id: &UserId, pushkey: &str) -> DataResult<Option<i64>> {
+    let mut conn = connect().await?;
+    diesel::update(
+        user_pushers::table
+            .filter(user_pushers::user_id.eq(user_id))
+            .filter(user_pushers::pushkey.eq(pushkey))
+            .filter(user_pushers::failing_since.is_null()),
+    )
+    .set(user_pushers::failing_since.eq(UnixMillis::now().get() as i64))
+    .execute(&mut conn)
+    .await?;
+    user_pushers::table
+        .filter(user_pushers::user_id.eq(user_id))
+        .filter(user_pushers::pushkey.eq(pushkey))
+        .select(user_pushers::failing_since)
+        .first::<Option<i64>>(&mut conn)
+        .await
+        .optional()
+        .map(Option::flatten)
+        .map_err(Into::into)
+}
+
+/// Forget the failures of the pusher without a successful notification,
+/// once the notifications it failed to receive were given up on.
+pub async fn reset_pusher_failure(user_id: &UserId, pushkey: &str) -> DataResult<()> {
+    diesel::update(
+        user_pushers::table
+            .filter(user_pushers::user_id.eq(user_id))
+            .filter(user_pushers::pushkey.eq(pushkey)),
+    )
+    .set(user_pushers::failing_since.eq(None::<i64>))
+    .execute(&mut connect().await?)
+    .await?;
+    Ok(())
+}
+
 /// Insert a pusher row.
 pub async fn insert_pusher(new_pusher: &NewDbPusher) -> DataResult<()> {
     diesel::insert_into(user_pushers::table)
@@ -0,0 +1,123 @@
+use diesel::prelude::*;
+use diesel_async::RunQueryDsl;
+
+use crate::core::UnixMillis;
+use crate::core::identifiers::*;
+use crate::report::ReportFilter;
+use crate::schema::*;
+use crate::{DataResult, connect};
+
+/// Database model for user reports
+#[derive(Identifiable, Queryable, Debug, Clone)]
+#[diesel(table_name = user_reports)]
+pub struct DbUserReport {
+    pub id: i64,
+    pub received_ts: i64,
+    pub status: String,
+    pub target_user_id: OwnedUserId,
+    pub user_id: OwnedUserId,
+    pub reason: Option<String>,
+}
+
+#[derive(Insertable, Debug, Clone)]
+#[diesel(table_name = user_reports)]
+pub struct NewDbUserReport {
+    pub received_ts: i64,
+    pub status: String,
+    pub target_user_id: OwnedUserId,
+    pub user_id: OwnedUserId,
+    pub reason: Option<String>,
+}
+
+impl NewDbUserReport {
+    pub fn new(target_user_id: OwnedUserId, user_id: OwnedUserId, reason: Option<String>) -> Self {
+        Self {
+            received_ts: UnixMillis::now().get() as i64,
+            status: "new".to_owned(),
+            target_user_id,
+            user_id,
+            reason,
+        }
+    }
+}
+
+/// Filter options for listing user reports
+pub type UserReportFilter = ReportFilter<OwnedUserId>;
+
+/// Create a new user report
+pub async fn create_user_report(report: NewDbUserReport) -> DataResult<i64> {
+    let result = diesel::insert_into(user_reports::table)
+        .values(&report)
+        .returning(user_reports::id)
+        .get_result::<i64>(&mut connect().await?)
+        .await?;
+    Ok(result)
+}
+
+/// List user reports with pagination and filtering
+pub async fn list_user_reports(filter: &UserReportFilter) -> DataResult<(Vec<DbUserReport>, i64)> {
+    let filtered = || {
+        let mut query = user_reports::table.into_boxed();
+        if let Some(ref user_id) = filter.user_id {
+            query = query.filter(user_reports::user_id.eq(user_id));
+        }
+        if let Some(ref target) = filter.target {
+            query = query.filter(user_reports::target_user_id.eq(target));
+        }
+        if let Some(ref status) = filter.status {
+            query = query.filter(user_reports::status.eq(status));
+        }
+        query
+    };
+
+    let total = filtered()
+        .count()
+        .get_result::<i64>(&mut connect().await?)
+        .await?;
+
+    let mut query = if filter.is_forward() {
+        filtered().order(user_reports::id.asc())
+    } else {
+        filtered().order(user_reports::id.desc())
+    };
+    if let Some(from) = filter.from {
+        query = query.offset(from);
+    }
+    let reports = query
+        .limit(filter.page_limit())
+        .load::<DbUserReport>(&mut connect().await?)
+        .await?;
+    Ok((reports, total))
+}
+
+/// Get a single user report by ID
+pub async fn get_user_report(report_id: i64) -> DataResult<Option<DbUserReport>> {
+    user_reports::table
+        .find(report_id)
+        .first::<DbUserReport>(&mut connect().await?)
+        .await
+        .optional()
+        .map_err(Into::into)
+}
+
+/// Update a user report status by ID
+pub async fn update_user_report_status(
+    report_id: i64,
+    status: &str,
+) -> DataResult<Option<DbUserReport>> {
+    diesel::update(user_reports::table.find(report_id))
+        .set(user_reports::status.eq(status))
+        .get_result::<DbUserReport>(&mut connect().await?)
+        .await
+        .optional()
+        .map_err(Into::into)
+}
+
+/// Delete a user report by ID
+/// Returns true if deleted, false if not found
+pub async fn delete_user_report(report_id: i64) -> DataResult<bool> {
+    let result = diesel::delete(user_reports::table.find(report_id))
+        .execute(&mut connect().await?)
+        .await?;
+    Ok(result > 0)
+}
@@ -1,9 +1,11 @@
+use std::time::Instant;
+
 use clap::Subcommand;
 
 use crate::admin::{Context, RoomInfo, get_room_info};
 use crate::core::{OwnedRoomId, OwnedServerName, OwnedUserId};
 use crate::macros::admin_command_dispatch;
-use crate::{AppError, AppResult, config, data, sending};
+use crate::{AppError, AppResult, DestType, config, data, sending};
 
 #[admin_command_dispatch]
 #[derive(Debug, Subcommand)]
@@ -27,6 +29,17 @@ pub(crate) enum FederationCommand {
 
     /// - Lists all the rooms we share/track with the specified *remote* user
     RemoteUserInRooms { user_id: OwnedUserId },
+
+    /// - Show how requests to a server are currently routed
+    ///
+    /// Prints the cached result of resolving the server name through
+    /// `.well-known`, SRV and address lookups, and when it expires.
+    ShowDestination { server_name: OwnedServerName },
+
+    /// - Forget how to reach a server, so the next request resolves it again
+    ///
+    /// This also clears the cache of DNS records.
+    FlushDestination { server_name: OwnedServerName },
 }
 
 pub(super) async fn disable_room(ctx: &Context<'_>, room_id: OwnedRoomId) -> AppResult<()> {
@@ -124,3 +137,80 @@ pub(super) async fn remote_user_in_rooms(ctx: &Context<'_>, user_id: OwnedUserId
     ))
     .await
 }
+
+pub(super) async fn show_destination(
+    ctx: &Context<'_>,
+    server_name: OwnedServerName,
+) -> AppResult<()> {
+    let Some((response, srv_override)) = crate::cached_destination(&server_name) else {
+        return ctx
+            .write_str(&format!("No cached destination for {server_name}."))
+            .await;
+    };
+
+    let expires_in = |at: Instant| {
+        let secs = at.saturating_duration_since(Instant::now()).as_secs();
+        if secs == 0 {
+            "expired".to_owned()
+        } else {
+            format!("expires in {secs}s")
+        }
+    };
+    let resolution = match &response.dest_type {
+        DestType::IsIpOrHasPort => "IP literal or explicit port".to_owned(),
+        DestType::WellKnown { expires } => format!(".well-known, {}", expires_in(*expires)),
+        DestType::WellKnownSrv {
+            srv_expires,
+            well_known_expires,
+            well_known_host,
+        } => format!(
+            ".well-known to {well_known_host}, {}; SRV {}",
+            expires_in(*well_known_expires),
+            expires_in(*srv_expires)
+        ),
+        DestType::Srv {
+            srv_expires,
+            well_known_retry,
+            ..
+        } => format!(
+            "SRV, {}; no .well-known, retry {}",
+            expires_in(*srv_expires),
+            expires_in(*well_known_retry)
+        ),
+        DestType::LookupFailed {
+            well_known_retry, ..
+        } => format!(
+            "no .well-known nor SRV, retry {}",
+            expires_in(*well_known_retry)
+        ),
+    };
+
+    let mut body = format!(
+        "Destination: {}\nResolved through: {resolution}",
+        response.actual_destination.into_uri_string()
+    );
+    if let Some((addrs, port)) = srv_override {
+        let addrs = addrs
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        body.push_str(&format!("\nSRV target addresses: {addrs} (port {port})"));
+    }
+    ctx.write_str(&format!("```\n{body}\n```")).await
+}
+
+pub(super) async fn flush_destination(
+    ctx: &Context<'_>,
+    server_name: OwnedServerName,
+) -> AppResult<()> {
+    if crate::flush_destination(&server_name) {
+        ctx.write_str(&format!("Flushed the cached destination of {server_name}."))
+            .await
+    } else {
+        ctx.write_str(&format!(
+            "No cached destination for {server_name}, flushed the DNS cache only."
+        ))
+        .await
+    }
+}
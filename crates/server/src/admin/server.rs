@@ -33,6 +33,24 @@ pub(crate) enum ServerCommand {
     /// - Send a message to the admin room.
     AdminNotice { message: Vec<String> },
 
+    /// - Rebuild the full-text search index with the current search settings
+    ReindexSearch,
+
+    /// - Load the certificates of the TLS listeners again
+    ///
+    /// Certificates that do not match their key are rejected and the current
+    /// ones stay in use.
+    ReloadCertificates,
+
+    /// - Generate a new server signing key and start signing with it
+    ///
+    /// The previous key keeps being published as an old verify key.
+    RotateSigningKey,
+
+    /// - List the instances sharing the sending queues and the queues each
+    ///   one owns
+    SendingQueues,
+
     /// - Hot-reload the server
     #[clap(alias = "reload")]
     ReloadMods,
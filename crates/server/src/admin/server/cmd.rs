@@ -2,7 +2,7 @@ use std::fmt::Write;
 use std::path::PathBuf;
 
 use crate::admin::Context;
-use crate::{AppError, AppResult, config, info};
+use crate::{AppError, AppResult, config, data, info, sending};
 
 // pub(super) async fn uptime(ctx: &Context<'_>) -> AppResult<()> {
 //     // TODO: admin
@@ -67,6 +67,82 @@ pub(super) async fn admin_notice(ctx: &Context<'_>, message: Vec<String>) -> App
     ctx.write_str("Notice was sent to #admins").await
 }
 
+pub(super) async fn reindex_search(ctx: &Context<'_>) -> AppResult<()> {
+    tokio::spawn(async {
+        let message = match crate::event::search::reindex_all().await {
+            Ok(count) => format!("Search reindex done: reindexed {count} events."),
+            Err(e) => format!("Search reindex failed: {e}"),
+        };
+        if let Err(e) = crate::admin::send_text(&message).await {
+            error!("failed to report search reindex result: {e}");
+        }
+    });
+    ctx.write_str("Reindexing search in the background, progress is posted to this room.")
+        .await
+}
+
+pub(super) async fn reload_certificates(ctx: &Context<'_>) -> AppResult<()> {
+    let conf = crate::config::get();
+    let mut count = 0;
+    for tls_conf in conf.listeners.iter().filter_map(|l| l.enabled_tls()) {
+        crate::tls::load_config(tls_conf)?;
+        count += 1;
+    }
+    crate::tls::reload();
+    ctx.write_str(&format!("Reloading certificates of {count} TLS listeners."))
+        .await
+}
+
+pub(super) async fn rotate_signing_key(ctx: &Context<'_>) -> AppResult<()> {
+    let key_id = crate::signing_keys::rotate().await?;
+    ctx.write_str(&format!("Now signing with key {key_id}."))
+        .await
+}
+
+pub(super) async fn sending_queues(ctx: &Context<'_>) -> AppResult<()> {
+    let instances = sending::lease::live_instances().await?;
+    let leases = data::sending::live_sending_leases().await?;
+
+    let mut out = String::new();
+    for instance in &instances {
+        let this = if instance.instance_id == sending::lease::instance_name() {
+            " (this instance)"
+        } else {
+            ""
+        };
+        let owned = leases
+            .iter()
+            .filter(|lease| lease.instance_id == instance.instance_id)
+            .collect::<Vec<_>>();
+        writeln!(
+            out,
+            "- {}{this}: {} queues",
+            instance.instance_id,
+            owned.len()
+        )?;
+        for lease in owned {
+            writeln!(out, "  - `{}`", lease.queue_key)?;
+        }
+    }
+
+    // Leases of instances that stopped, taken over once they expire.
+    for lease in leases
+        .iter()
+        .filter(|lease| !instances.iter().any(|i| i.instance_id == lease.instance_id))
+    {
+        writeln!(
+            out,
+            "- `{}` held by stopped instance {} until {}",
+            lease.queue_key, lease.instance_id, lease.expires_at
+        )?;
+    }
+
+    if out.is_empty() {
+        out.push_str("No sending queue is owned by any instance.");
+    }
+    ctx.write_str(&out).await
+}
+
 pub(super) async fn reload_mods(_ctx: &Context<'_>) -> AppResult<()> {
     Err(AppError::public("module reload is not implemented yet."))
 }
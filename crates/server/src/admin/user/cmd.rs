@@ -177,7 +177,7 @@ pub(super) async fn deactivate(
 
         let all_joined_rooms: Vec<OwnedRoomId> = data::user::joined_rooms(&user_id).await?;
 
-        full_user_deactivate(&user_id, &all_joined_rooms).await?;
+        full_user_deactivate(&user_id, &all_joined_rooms, false).await?;
 
         membership::leave_all_rooms(&user_id).await?;
     }
@@ -287,7 +287,7 @@ pub(super) async fn deactivate_all(
                     info!("Forcing user {user_id} to leave all rooms apart of deactivate-all");
                     let all_joined_rooms = data::user::joined_rooms(&user_id).await?;
 
-                    full_user_deactivate(&user_id, &all_joined_rooms).await?;
+                    full_user_deactivate(&user_id, &all_joined_rooms, false).await?;
 
                     membership::leave_all_rooms(&user_id).await?;
                 }
@@ -95,6 +95,23 @@ impl RegistrationInfo {
         self.users.is_exclusive_match(user_id.as_str())
             || self.registration.sender_localpart == user_id.localpart()
     }
+
+    /// The regular expressions matching the user IDs of
+    /// [`is_exclusive_user_match`](Self::is_exclusive_user_match), for filtering in the
+    /// database.
+    pub fn exclusive_user_patterns(&self) -> Vec<String> {
+        let mut patterns = self
+            .users
+            .exclusive
+            .as_ref()
+            .map(|set| set.patterns().to_vec())
+            .unwrap_or_default();
+        patterns.push(format!(
+            "^@{}:",
+            regex::escape(&self.registration.sender_localpart)
+        ));
+        patterns
+    }
 }
 impl AsRef<Registration> for RegistrationInfo {
     fn as_ref(&self) -> &Registration {
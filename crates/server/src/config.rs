@@ -1,10 +1,8 @@
 use std::iter::once;
 use std::ops::Deref;
 use std::path::Path;
-use std::sync::{LazyLock, OnceLock};
+use std::sync::{Arc, LazyLock, OnceLock};
 
-use base64::Engine as _;
-use base64::engine::general_purpose::STANDARD;
 use figment::Figment;
 use figment::providers::{Env, Format, Json, Toml, Yaml};
 use figment::value::Value;
@@ -13,6 +11,8 @@ use kdl::{KdlDocument, KdlNode, KdlValue};
 
 mod server;
 pub use server::*;
+mod acme;
+pub use acme::*;
 mod admin;
 pub use admin::*;
 // mod appservice;
@@ -27,8 +27,8 @@ mod compression;
 pub use compression::*;
 mod db;
 pub use db::*;
-// mod dns;
-// pub use dns::*;
+mod dns;
+pub use dns::*;
 mod federation;
 pub use federation::*;
 mod http_client;
@@ -47,16 +47,24 @@ mod proxy;
 pub use proxy::*;
 mod read_receipt;
 pub use read_receipt::*;
+mod retention;
+pub use retention::*;
+mod search;
+pub use search::*;
 mod turn;
 pub use turn::*;
 mod typing;
 pub use typing::*;
 mod url_preview;
 pub use url_preview::*;
+mod user_directory;
+pub use user_directory::*;
 mod well_known;
 pub use well_known::*;
 mod oidc;
 pub use oidc::*;
+mod openapi;
+pub use openapi::*;
 mod delegated_auth;
 pub use delegated_auth::*;
 
@@ -349,20 +357,9 @@ pub fn appservice_registration_dir() -> Option<&'static str> {
     get().appservice_registration_dir.as_deref()
 }
 
-/// Returns this server's keypair.
-pub fn keypair() -> &'static Ed25519KeyPair {
-    static KEYPAIR: OnceLock<Ed25519KeyPair> = OnceLock::new();
-    KEYPAIR.get_or_init(|| {
-        if let Some(keypair) = &get().keypair {
-            let bytes = STANDARD
-                .decode(&keypair.document)
-                .expect("server keypair is invalid base64 string");
-            Ed25519KeyPair::from_der(&bytes, keypair.version.clone())
-                .expect("invalid server Ed25519KeyPair")
-        } else {
-            crate::utils::generate_keypair()
-        }
-    })
+/// Returns the keypair this server currently signs with.
+pub fn keypair() -> Arc<Ed25519KeyPair> {
+    crate::signing_keys::own_keys().keypair.clone()
 }
 
 pub fn valid_cidr_range(ip: &IPAddress) -> bool {
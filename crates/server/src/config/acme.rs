@@ -1,14 +1,93 @@
-use std::collections::BTreeMap;
-use std::fmt;
-use std::net::IpAddr;
 use std::path::PathBuf;
 
 use serde::Deserialize;
 
-use crate::core::serde::{default_false, default_true};
 use crate::macros::config_example;
 
 #[config_example(filename = "palpo-example.toml", section = "acme")]
-#[derive(Clone, Debug, Deserialize, Default)]
+#[derive(Clone, Debug, Deserialize)]
 pub struct AcmeConfig {
+    /// URL of the ACME directory certificates are ordered from.
+    ///
+    /// Defaults to Let's Encrypt. To test against a local CA such as Pebble,
+    /// point this to its directory and make sure the system trusts its
+    /// certificate.
+    ///
+    /// default: "https://acme-v02.api.letsencrypt.org/directory"
+    #[serde(default = "default_directory_url")]
+    pub directory_url: String,
+
+    /// Contact URLs given to the ACME provider, for expiry notices.
+    ///
+    /// example: ["mailto:admin@example.com"]
+    ///
+    /// default: []
+    #[serde(default)]
+    pub contacts: Vec<String>,
+
+    /// Domains to include in the certificate besides `server_name`, for
+    /// example the host delegated to through `.well-known`.
+    ///
+    /// default: []
+    #[serde(default)]
+    pub domains: Vec<String>,
+
+    /// Directory where the account key and the certificates are stored, so
+    /// they survive restarts.
+    ///
+    /// default: "./acme"
+    #[serde(default = "default_cache_path")]
+    pub cache_path: PathBuf,
+
+    /// How the ACME provider validates that we control the domains.
+    ///
+    /// - `tls_alpn01`: answered on the TLS listener itself, which must be
+    ///   reachable on port 443.
+    /// - `http01`: answered on `http_address`, which must be reachable on
+    ///   port 80.
+    ///
+    /// default: "tls_alpn01"
+    #[serde(default)]
+    pub challenge: AcmeChallenge,
+
+    /// Address plain HTTP is served on to answer `http01` challenges. Other
+    /// requests are redirected to HTTPS, unless a plain listener is configured
+    /// on the same address.
+    ///
+    /// default: "0.0.0.0:80"
+    #[serde(default = "default_http_address")]
+    pub http_address: String,
+}
+
+impl Default for AcmeConfig {
+    fn default() -> Self {
+        Self {
+            directory_url: default_directory_url(),
+            contacts: Vec::new(),
+            domains: Vec::new(),
+            cache_path: default_cache_path(),
+            challenge: AcmeChallenge::default(),
+            http_address: default_http_address(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AcmeChallenge {
+    #[default]
+    TlsAlpn01,
+    Http01,
+}
+
+fn default_directory_url() -> String {
+    "https://acme-v02.api.letsencrypt.org/directory".to_owned()
+}
+
+fn default_cache_path() -> PathBuf {
+    PathBuf::from("./acme")
+}
+
+fn default_http_address() -> String {
+    "0.0.0.0:80".to_owned()
 }
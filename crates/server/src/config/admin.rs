@@ -13,6 +13,12 @@ pub struct AdminConfig {
     #[serde(default = "default_true")]
     pub room_notices: bool,
 
+    /// Send a notice to the admin room whenever a user reports a room or
+    /// another user, so moderators see new reports without polling the admin
+    /// API.
+    #[serde(default = "default_true")]
+    pub report_notices: bool,
+
     /// Allow admins to enter commands in rooms other than "#admins" (admin
     /// room) by prefixing your message with "\!admin" or "\\!admin" followed up
     /// a normal palpo admin command. The reply will be publicly visible to
@@ -89,6 +95,7 @@ impl Default for AdminConfig {
     fn default() -> Self {
         Self {
             room_notices: true,
+            report_notices: true,
             escape_commands: true,
             console_automatic: false,
             startup_execute: Vec::new(),
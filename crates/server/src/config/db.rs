@@ -32,6 +32,30 @@ pub struct DbConfig {
     /// Whether to enforce that all the database connections are encrypted with TLS.
     #[serde(default = "default_false")]
     pub enforce_tls: bool,
+
+    /// Read-only streaming replicas of the primary database. Search, user
+    /// directory, room listings and `/messages` pagination are served from a
+    /// replica when one is healthy and within `max_replica_lag`.
+    ///
+    /// example: [{ url = "postgres://palpo@replica1/palpo", pool_size = 20 }]
+    ///
+    /// default: []
+    #[serde(default)]
+    pub replicas: Vec<DbReplicaConfig>,
+
+    /// Maximum replication lag in milliseconds before reads that would use a
+    /// replica fall back to the primary.
+    ///
+    /// default: 5000
+    #[serde(default = "default_max_replica_lag")]
+    pub max_replica_lag: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct DbReplicaConfig {
+    pub url: String,
+    #[serde(default = "default_db_pool_size")]
+    pub pool_size: u32,
 }
 
 impl DbConfig {
@@ -43,6 +67,8 @@ impl DbConfig {
             connection_timeout,
             statement_timeout,
             enforce_tls,
+            replicas,
+            max_replica_lag,
         } = self;
         crate::data::DbConfig {
             url: url.clone(),
@@ -51,6 +77,14 @@ impl DbConfig {
             connection_timeout,
             statement_timeout,
             enforce_tls,
+            replicas: replicas
+                .into_iter()
+                .map(|replica| crate::data::ReplicaConfig {
+                    url: replica.url,
+                    pool_size: replica.pool_size,
+                })
+                .collect(),
+            max_replica_lag,
         }
     }
 }
@@ -64,6 +98,8 @@ impl Default for DbConfig {
             connection_timeout: default_connection_timeout(),
             statement_timeout: default_statement_timeout(),
             enforce_tls: default_false(),
+            replicas: Vec::new(),
+            max_replica_lag: default_max_replica_lag(),
         }
     }
 }
@@ -84,3 +120,6 @@ fn default_connection_timeout() -> u64 {
 fn default_statement_timeout() -> u64 {
     30_000
 }
+fn default_max_replica_lag() -> u64 {
+    5_000
+}
@@ -1,11 +1,22 @@
+use std::net::IpAddr;
+
 use serde::Deserialize;
 
-use crate::core::serde::{default_false, default_true};
+use crate::core::serde::default_true;
 use crate::macros::config_example;
 
 #[config_example(filename = "palpo-example.toml", section = "dns")]
 #[derive(Clone, Debug, Deserialize)]
 pub struct DnsConfig {
+    /// Nameservers to query instead of those of the system resolver
+    /// configuration (`/etc/resolv.conf`).
+    ///
+    /// example: ["1.1.1.1", "2606:4700:4700::1111"]
+    ///
+    /// default: []
+    #[serde(default)]
+    pub nameservers: Vec<IpAddr>,
+
     /// Maximum entries stored in DNS memory-cache. The size of an entry may
     /// vary so please take care if raising this value excessively. Only
     /// decrease this when using an external DNS cache. Please note that
@@ -61,6 +72,7 @@ pub struct DnsConfig {
 impl Default for DnsConfig {
     fn default() -> Self {
         Self {
+            nameservers: Vec::new(),
             cache_entries: default_cache_entries(),
             min_ttl: default_min_ttl(),
             min_ttl_nxdomain: default_min_ttl_nxdomain(),
@@ -80,7 +92,7 @@ fn default_min_ttl() -> u64 {
 }
 
 fn default_min_ttl_nxdomain() -> u64 {
-    60 * 60 * 24 * 3_000
+    60 * 60 * 24 * 3
 }
 
 fn default_attempts() -> u16 {
@@ -88,5 +100,5 @@ fn default_attempts() -> u16 {
 }
 
 fn default_timeout() -> u64 {
-    10_000
+    10
 }
@@ -0,0 +1,52 @@
+use serde::Deserialize;
+
+use crate::core::serde::{default_false, default_true};
+use crate::macros::config_example;
+
+#[config_example(filename = "palpo-example.toml", section = "openapi")]
+#[derive(Clone, Debug, Deserialize)]
+pub struct OpenApiConfig {
+    /// Serve an OpenAPI document of the client, federation and admin APIs at
+    /// `/api-doc/openapi.json`.
+    ///
+    /// default: false
+    #[serde(default = "default_false")]
+    pub enable: bool,
+
+    /// Serve interactive documentation of the OpenAPI document, Scalar at
+    /// `/scalar` and Swagger UI at `/swagger-ui`.
+    ///
+    /// default: true
+    #[serde(default = "default_true")]
+    pub ui: bool,
+
+    /// Who the admin API is documented for.
+    ///
+    /// - `admins`: only requests carrying the access token of an admin get
+    ///   the admin API, everyone else gets the client and federation APIs.
+    /// - `everyone`: the admin API is documented for every request.
+    /// - `nobody`: the admin API is never documented.
+    ///
+    /// default: "admins"
+    #[serde(default)]
+    pub admin_api: OpenApiAdminAccess,
+}
+
+impl Default for OpenApiConfig {
+    fn default() -> Self {
+        Self {
+            enable: false,
+            ui: true,
+            admin_api: OpenApiAdminAccess::default(),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OpenApiAdminAccess {
+    #[default]
+    Admins,
+    Everyone,
+    Nobody,
+}
@@ -30,15 +30,17 @@ pub struct PresenceConfig {
     #[serde(default = "default_true")]
     pub allow_outgoing: bool,
 
-    /// How many seconds without presence updates before you become idle.
-    /// Defaults to 5 minutes.
+    /// How many milliseconds without activity before a local user becomes
+    /// idle (unavailable). Defaults to 5 minutes.
     ///
     /// default: 300_000
     #[serde(default = "default_presence_idle_timeout")]
     pub idle_timeout: u64,
 
-    /// How many seconds without presence updates before you become offline.
-    /// Defaults to 30 minutes.
+    /// How many milliseconds without activity or syncing before a local user
+    /// becomes offline. Remote users whose server sent no presence update for
+    /// this long are also set offline, see `timeout_remote_users`. Defaults to
+    /// 30 minutes.
     ///
     /// default: 1800_000
     #[serde(default = "default_presence_offline_timeout")]
@@ -0,0 +1,100 @@
+use serde::Deserialize;
+
+use crate::macros::config_example;
+
+#[config_example(filename = "palpo-example.toml", section = "retention")]
+#[derive(Clone, Debug, Deserialize)]
+pub struct RetentionConfig {
+    /// Enable support for `m.room.retention` policies and the background job
+    /// that purges expired messages.
+    ///
+    /// Only non-state events are purged. State events and the most recent
+    /// events of a room (its forward extremities) are always kept.
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Maximum lifetime (in milliseconds) applied to rooms that do not define
+    /// an `m.room.retention` policy. Messages older than this are purged.
+    ///
+    /// Leave unset to keep messages forever in rooms without a policy. For
+    /// example, `15552000000` keeps messages for at most 180 days.
+    pub default_policy_max_lifetime: Option<u64>,
+
+    /// Smallest `max_lifetime` (in milliseconds) a room policy may request.
+    /// Room policies asking for a shorter lifetime are clamped up to this
+    /// value.
+    pub allowed_lifetime_min: Option<u64>,
+
+    /// Largest `max_lifetime` (in milliseconds) a room policy may request.
+    /// Room policies asking for a longer (or no) lifetime are clamped down to
+    /// this value, so it also acts as a hard server-wide limit.
+    pub allowed_lifetime_max: Option<u64>,
+
+    /// How often (in milliseconds) the purge job runs.
+    ///
+    /// default: 86_400_000
+    #[serde(default = "default_purge_interval")]
+    pub purge_interval: u64,
+}
+
+impl Default for RetentionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            default_policy_max_lifetime: None,
+            allowed_lifetime_min: None,
+            allowed_lifetime_max: None,
+            purge_interval: default_purge_interval(),
+        }
+    }
+}
+
+impl RetentionConfig {
+    /// Effective `max_lifetime` for a room, given the `max_lifetime` of its
+    /// `m.room.retention` policy (if any).
+    pub fn effective_max_lifetime(&self, room_max_lifetime: Option<u64>) -> Option<u64> {
+        let lifetime = room_max_lifetime.or(self.default_policy_max_lifetime);
+        let lifetime = match (lifetime, self.allowed_lifetime_max) {
+            (Some(lifetime), Some(max)) => Some(lifetime.min(max)),
+            (None, max) => max,
+            (lifetime, None) => lifetime,
+        };
+        match (lifetime, self.allowed_lifetime_min) {
+            (Some(lifetime), Some(min)) => Some(lifetime.max(min)),
+            (lifetime, _) => lifetime,
+        }
+    }
+}
+
+fn default_purge_interval() -> u64 {
+    86_400_000
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn effective_max_lifetime_is_clamped() {
+        let conf = RetentionConfig {
+            enabled: true,
+            default_policy_max_lifetime: Some(1000),
+            allowed_lifetime_min: Some(100),
+            allowed_lifetime_max: Some(5000),
+            ..Default::default()
+        };
+        assert_eq!(conf.effective_max_lifetime(None), Some(1000));
+        assert_eq!(conf.effective_max_lifetime(Some(10)), Some(100));
+        assert_eq!(conf.effective_max_lifetime(Some(9000)), Some(5000));
+
+        let conf = RetentionConfig {
+            allowed_lifetime_max: Some(5000),
+            ..Default::default()
+        };
+        assert_eq!(conf.effective_max_lifetime(None), Some(5000));
+
+        let conf = RetentionConfig::default();
+        assert_eq!(conf.effective_max_lifetime(None), None);
+        assert_eq!(conf.effective_max_lifetime(Some(10)), Some(10));
+    }
+}
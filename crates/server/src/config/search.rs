@@ -0,0 +1,76 @@
+use std::collections::BTreeMap;
+
+use serde::Deserialize;
+
+use crate::core::identifiers::*;
+use crate::macros::config_example;
+
+#[config_example(filename = "palpo-example.toml", section = "search")]
+#[derive(Clone, Debug, Deserialize)]
+pub struct SearchConfig {
+    /// How message text is tokenized for full-text search.
+    ///
+    /// - `postgres`: Postgres text search with the room's language
+    ///   configuration.
+    /// - `ngram`: as `postgres`, but Chinese, Japanese and Korean text is also
+    ///   split into single characters and overlapping character bigrams, since
+    ///   these scripts do not separate words with spaces.
+    ///
+    /// Changing this only affects newly indexed events. Run the
+    /// `server reindex-search` admin command to rebuild existing ones.
+    ///
+    /// default: "postgres"
+    #[serde(default)]
+    pub backend: SearchBackend,
+
+    /// Postgres text search configuration used for rooms without an entry in
+    /// `room_languages`, e.g. "english", "german" or "simple".
+    ///
+    /// default: "english"
+    #[serde(default = "default_language")]
+    pub default_language: String,
+
+    /// Postgres text search configuration per room, overriding
+    /// `default_language`.
+    ///
+    /// example: { "!room:example.com" = "simple" }
+    #[serde(default)]
+    pub room_languages: BTreeMap<OwnedRoomId, String>,
+}
+
+impl Default for SearchConfig {
+    fn default() -> Self {
+        Self {
+            backend: SearchBackend::default(),
+            default_language: default_language(),
+            room_languages: BTreeMap::new(),
+        }
+    }
+}
+
+impl SearchConfig {
+    /// The text search configuration events of `room_id` are indexed with.
+    pub fn language(&self, room_id: &RoomId) -> &str {
+        self.room_languages
+            .get(room_id)
+            .unwrap_or(&self.default_language)
+    }
+
+    /// Every configured text search configuration.
+    pub fn languages(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.default_language.as_str())
+            .chain(self.room_languages.values().map(String::as_str))
+    }
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SearchBackend {
+    #[default]
+    Postgres,
+    Ngram,
+}
+
+fn default_language() -> String {
+    "english".to_owned()
+}
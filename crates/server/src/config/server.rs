@@ -5,13 +5,14 @@ use salvo::http::HeaderValue;
 use serde::Deserialize;
 
 use super::{
-    AdminConfig, BlurhashConfig, CompressionConfig, DbConfig, DelegatedAuthConfig,
-    FederationConfig, HttpClientConfig, JwtConfig, LoggerConfig, MediaConfig, OidcConfig,
-    PresenceConfig, ProxyConfig, ReadReceiptConfig, StorageConfig, TurnConfig, TypingConfig,
-    UrlPreviewConfig, WellKnownConfig,
+    AcmeConfig, AdminConfig, BlurhashConfig, CompressionConfig, DbConfig, DelegatedAuthConfig,
+    DnsConfig, FederationConfig, HttpClientConfig, JwtConfig, LoggerConfig, MediaConfig,
+    OidcConfig, OpenApiConfig, PresenceConfig, ProxyConfig, ReadReceiptConfig, RetentionConfig,
+    SearchConfig, StorageConfig, TurnConfig, TypingConfig, UrlPreviewConfig, UserDirectoryConfig,
+    WellKnownConfig,
 };
 use crate::core::serde::{default_false, default_true};
-use crate::core::{OwnedRoomOrAliasId, OwnedServerName, RoomVersionId};
+use crate::core::{OwnedRoomOrAliasId, OwnedServerName, RoomVersionId, UnixMillis};
 use crate::env_vars::required_var;
 use crate::macros::config_example;
 use crate::utils::sys;
@@ -23,6 +24,19 @@ const DEPRECATED_KEYS: &[&str; 0] = &[];
 pub struct KeypairConfig {
     pub document: String,
     pub version: String,
+    /// Keys this server signed with before, published as `old_verify_keys` so
+    /// remote servers can still verify events signed with them.
+    #[serde(default)]
+    pub old_keys: Vec<OldKeypairConfig>,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct OldKeypairConfig {
+    pub version: String,
+    /// The unpadded base64 encoded public key.
+    pub key: String,
+    /// When this server stopped signing with the key.
+    pub expired_ts: UnixMillis,
 }
 
 #[derive(Clone, Debug, Deserialize)]
@@ -30,8 +44,31 @@ pub struct ListenerConfig {
     /// The default address (IPv4 or IPv6) and port palpo will listen on.
     #[serde(default = "default_listen_address")]
     pub address: String,
+    /// The UNIX socket this listener binds instead of `address`.
+    ///
+    /// A stale socket file left at this path is removed on start. Remember to
+    /// make sure that your reverse proxy has access to this socket file,
+    /// either by adding your reverse proxy to the 'palpo' group or granting
+    /// world R/W permissions with `unix_socket_perms` (666 minimum).
+    ///
+    /// Client IPs on these connections are always taken from the
+    /// `X-Forwarded-For` or `X-Real-IP` headers set by the reverse proxy.
+    ///
+    /// example: "/run/palpo/palpo.sock"
+    pub unix_socket_path: Option<PathBuf>,
+    /// The permissions (in octal) to create the UNIX socket with.
+    ///
+    /// default: 660
+    #[serde(default = "default_unix_socket_perms")]
+    pub unix_socket_perms: u32,
+    /// Take client IPs from the `X-Forwarded-For` or `X-Real-IP` headers.
+    /// Only enable this behind a reverse proxy that sets them.
     #[serde(default)]
     pub x_forwarded: bool,
+    /// Serve TLS with certificates obtained and renewed through ACME, as
+    /// configured in the `acme` section, instead of the `tls` files.
+    #[serde(default)]
+    pub acme: bool,
     // external structure; separate section
     pub tls: Option<TlsConfig>,
 }
@@ -39,7 +76,10 @@ impl Default for ListenerConfig {
     fn default() -> Self {
         Self {
             address: default_listen_address(),
+            unix_socket_path: None,
+            unix_socket_perms: default_unix_socket_perms(),
             x_forwarded: false,
+            acme: false,
             tls: None,
         }
     }
@@ -52,6 +92,32 @@ impl ListenerConfig {
             None
         }
     }
+
+    /// The permission bits of the UNIX socket, `unix_socket_perms` read as octal.
+    pub fn unix_socket_mode(&self) -> AppResult<u32> {
+        u32::from_str_radix(&self.unix_socket_perms.to_string(), 8)
+            .ok()
+            .filter(|mode| *mode <= 0o777)
+            .ok_or_else(|| {
+                AppError::internal(format!(
+                    "unix_socket_perms `{}` is not valid octal permissions",
+                    self.unix_socket_perms
+                ))
+            })
+    }
+
+    /// Whether requests accepted at `local_addr` came through this TCP listener.
+    pub fn is_bound_to(&self, local_addr: &std::net::SocketAddr) -> bool {
+        if self.unix_socket_path.is_some() {
+            return false;
+        }
+        self.address
+            .parse::<std::net::SocketAddr>()
+            .is_ok_and(|addr| {
+                addr.port() == local_addr.port()
+                    && (addr.ip().is_unspecified() || addr.ip() == local_addr.ip())
+            })
+    }
 }
 
 #[config_example(
@@ -74,8 +140,9 @@ impl ListenerConfig {
 ### For more information, see:
 ### https://palpo.im/guide/configuration.html
 "#,
-    ignore = "federation well_known compression typing read_receipt presence \
-        admin url_preview turn media storage blurhash keypair ldap proxy jwt oidc logger db appservice"
+    ignore = "federation well_known compression typing read_receipt retention presence \
+        admin url_preview turn media storage blurhash keypair ldap proxy jwt oidc logger db appservice \
+        user_directory search dns acme openapi"
 )]
 #[derive(Clone, Debug, Deserialize)]
 pub struct ServerConfig {
@@ -99,6 +166,16 @@ pub struct ServerConfig {
     /// page.
     pub home_page: Option<String>,
 
+    /// Name of this instance when several share one database, shown by the
+    /// `server sending-queues` admin command. Must differ between instances.
+    ///
+    /// A random name is used when unset. Setting it lets a restarted instance
+    /// resume its sending queues right away instead of waiting for the leases
+    /// of its previous run to expire.
+    ///
+    /// example: "palpo-1"
+    pub instance_name: Option<String>,
+
     // display: hidden
     #[serde(default)]
     pub db: DbConfig,
@@ -132,23 +209,6 @@ pub struct ServerConfig {
     #[serde(default = "default_new_user_displayname_suffix")]
     pub new_user_displayname_suffix: String,
 
-    // /// The UNIX socket palpo will listen on.
-    // ///
-    // /// palpo cannot listen on both an IP address and a UNIX socket. If
-    // /// listening on a UNIX socket, you MUST remove/comment the `address` key.
-    // ///
-    // /// Remember to make sure that your reverse proxy has access to this socket
-    // /// file, either by adding your reverse proxy to the 'palpo' group or
-    // /// granting world R/W permissions with `unix_socket_perms` (666 minimum).
-    // ///
-    // /// example: "/run/palpo/palpo.sock"
-    // pub unix_socket_path: Option<PathBuf>,
-
-    // /// The default permissions (in octal) to create the UNIX socket with.
-    // ///
-    // /// default: 660
-    // #[serde(default = "default_unix_socket_perms")]
-    // pub unix_socket_perms: u32,
     /// Enable to query all nameservers until the domain is found. Referred to
     /// as "trust_negative_responses" in hickory_resolver. This can avoid
     /// useless DNS queries if the first nameserver responds with NXDOMAIN or
@@ -597,7 +657,6 @@ pub struct ServerConfig {
     #[serde(default = "default_ip_range_denylist")]
     pub ip_range_denylist: Vec<String>,
 
-    // pub auto_acme: Option<AcmeConfig>,
     /// Whether to query the servers listed in trusted_servers first or query
     /// the origin server first. For best security, querying the origin server
     /// first is advised to minimize the exposure to a compromised trusted
@@ -734,10 +793,22 @@ pub struct ServerConfig {
     #[serde(default)]
     pub read_receipt: ReadReceiptConfig,
 
+    // external structure; separate section
+    #[serde(default)]
+    pub retention: RetentionConfig,
+
     // external structure; separate section
     #[serde(default)]
     pub typing: TypingConfig,
 
+    // external structure; separate section
+    #[serde(default)]
+    pub user_directory: UserDirectoryConfig,
+
+    // external structure; separate section
+    #[serde(default)]
+    pub search: SearchConfig,
+
     // external structure; separate section
     #[serde(default)]
     pub compression: CompressionConfig,
@@ -754,6 +825,18 @@ pub struct ServerConfig {
     #[serde(default)]
     pub http_client: HttpClientConfig,
 
+    // external structure; separate section
+    #[serde(default)]
+    pub dns: DnsConfig,
+
+    // external structure; separate section
+    #[serde(default)]
+    pub acme: AcmeConfig,
+
+    // external structure; separate section
+    #[serde(default)]
+    pub openapi: OpenApiConfig,
+
     /// Toggles ignore checking/validating TLS certificates
     ///
     /// This applies to everything, including URL previews, federation requests,
@@ -920,6 +1003,57 @@ impl ServerConfig {
 
         self.warn_deprecated();
 
+        if let (Some(min), Some(max)) = (
+            self.retention.allowed_lifetime_min,
+            self.retention.allowed_lifetime_max,
+        ) && min > max
+        {
+            return Err(AppError::internal(
+                "retention.allowed_lifetime_min must not be greater than \
+                 retention.allowed_lifetime_max",
+            ));
+        }
+        if let Some(language) = self.search.languages().find(|language| {
+            language.is_empty()
+                || !language
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '.')
+        }) {
+            return Err(AppError::internal(format!(
+                "search language `{language}` is not a valid text search configuration name"
+            )));
+        }
+        for listener in &self.listeners {
+            if listener.acme && listener.enabled_tls().is_some() {
+                return Err(AppError::internal(format!(
+                    "listener {} enables both `acme` and `tls`, only one can provide its \
+                     certificate",
+                    listener.address
+                )));
+            }
+            if listener.unix_socket_path.is_none() {
+                continue;
+            }
+            if cfg!(not(unix)) {
+                return Err(AppError::internal(
+                    "UNIX socket support is only available on *nix platforms. Please remove \
+                     'unix_socket_path' from your config.",
+                ));
+            }
+            if listener.enabled_tls().is_some() || listener.acme {
+                return Err(AppError::internal(
+                    "TLS is not supported on UNIX socket listeners, terminate it in the \
+                     reverse proxy instead.",
+                ));
+            }
+            listener.unix_socket_mode()?;
+        }
+        if self.retention.enabled && self.retention.purge_interval == 0 {
+            return Err(AppError::internal(
+                "retention.purge_interval must be greater than 0",
+            ));
+        }
+
         // if self.sentry && self.sentry_endpoint.is_none() {
         //     return Err(AppError::internal(
         //         "sentry_endpoint",
@@ -938,13 +1072,6 @@ impl ServerConfig {
         //     );
         // }
 
-        // if cfg!(not(unix)) && self.unix_socket_path.is_some() {
-        //     return Err(AppError::internal(
-        //         "UNIX socket support is only available on *nix platforms. Please remove \
-        //          'unix_socket_path' from your config.",
-        //     ));
-        // }
-
         // if self.unix_socket_path.is_none() && self.get_bind_hosts().is_empty() {
         //     return Err(AppError::internal("No TCP addresses were specified to listen on"));
         // }
@@ -1169,6 +1296,32 @@ pub struct TlsConfig {
     /// Whether to listen and allow for HTTP and HTTPS connections (insecure!)
     #[serde(default)]
     pub dual_protocol: bool,
+
+    /// Certificates served instead of `cert` to clients asking for another
+    /// domain through SNI, for example the federation domain.
+    ///
+    /// example: [{ domain = "matrix.example.com", cert = "/path/to/matrix.crt",
+    /// key = "/path/to/matrix.key" }]
+    ///
+    /// default: []
+    #[serde(default)]
+    pub sni: Vec<SniCertConfig>,
+
+    /// How often, in seconds, the certificate files are checked for changes.
+    /// Changed certificates are loaded without a restart once they are
+    /// validated against their key. Set to 0 to reload only on the
+    /// `server reload-certificates` admin command.
+    ///
+    /// default: 60
+    #[serde(default = "default_tls_reload_interval")]
+    pub reload_interval: u64,
+}
+
+#[derive(Clone, Debug, Deserialize)]
+pub struct SniCertConfig {
+    pub domain: String,
+    pub cert: String,
+    pub key: String,
 }
 
 fn default_listener() -> Vec<ListenerConfig> {
@@ -1177,6 +1330,12 @@ fn default_listener() -> Vec<ListenerConfig> {
 fn default_listen_address() -> String {
     "0.0.0.0:8008".into()
 }
+fn default_unix_socket_perms() -> u32 {
+    660
+}
+fn default_tls_reload_interval() -> u64 {
+    60
+}
 fn default_server_name() -> OwnedServerName {
     OwnedServerName::try_from("change.palpo.im").expect("default server name should be valid")
 }
@@ -1393,9 +1552,25 @@ fn default_rc_message() -> RateLimitConfig {
 #[cfg(test)]
 mod tests {
     use super::{
-        default_ip_range_denylist, default_openid_token_ttl, default_request_idle_per_host,
+        ListenerConfig, default_ip_range_denylist, default_openid_token_ttl,
+        default_request_idle_per_host,
     };
 
+    #[test]
+    fn unix_socket_perms_are_read_as_octal() {
+        let mut listener = ListenerConfig::default();
+        assert_eq!(listener.unix_socket_mode().unwrap(), 0o660);
+        listener.unix_socket_perms = 680;
+        assert!(listener.unix_socket_mode().is_err());
+    }
+
+    #[test]
+    fn unspecified_listener_address_matches_any_local_ip() {
+        let listener = ListenerConfig::default();
+        assert!(listener.is_bound_to(&"10.0.0.1:8008".parse().unwrap()));
+        assert!(!listener.is_bound_to(&"10.0.0.1:8448".parse().unwrap()));
+    }
+
     #[test]
     fn openid_token_ttl_default_is_seconds() {
         assert_eq!(default_openid_token_ttl(), 60 * 60);
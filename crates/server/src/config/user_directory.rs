@@ -0,0 +1,31 @@
+use serde::Deserialize;
+
+use crate::macros::config_example;
+
+#[config_example(filename = "palpo-example.toml", section = "user_directory")]
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct UserDirectoryConfig {
+    /// Which users a search in the user directory can return.
+    ///
+    /// - `shared_rooms`: only users sharing a room with the searcher.
+    /// - `public_rooms`: also users joined to a room with a public join rule.
+    /// - `all_local_users`: also every local user, regardless of rooms.
+    ///
+    /// default: "public_rooms"
+    #[serde(default)]
+    pub search_scope: UserDirectoryScope,
+
+    /// Include users registered by application services (bridged users and
+    /// appservice bots) in search results.
+    #[serde(default)]
+    pub include_appservice_users: bool,
+}
+
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum UserDirectoryScope {
+    SharedRooms,
+    #[default]
+    PublicRooms,
+    AllLocalUsers,
+}
@@ -118,6 +118,20 @@ impl AppError {
             _ => false,
         }
     }
+
+    pub fn is_unique_violation(&self) -> bool {
+        use diesel::result::{DatabaseErrorKind, Error as DieselError};
+        matches!(
+            self,
+            Self::Diesel(DieselError::DatabaseError(
+                DatabaseErrorKind::UniqueViolation,
+                _
+            )) | Self::Data(crate::data::DataError::Diesel(DieselError::DatabaseError(
+                DatabaseErrorKind::UniqueViolation,
+                _
+            )))
+        )
+    }
 }
 
 /// Best-effort removal of `access_token` values from text destined for logs.
@@ -361,6 +375,17 @@ mod tests {
         assert!(error.is_not_found());
     }
 
+    #[test]
+    fn nested_data_unique_violation_is_recognized() {
+        let error = AppError::Data(crate::data::DataError::Diesel(DieselError::DatabaseError(
+            DatabaseErrorKind::UniqueViolation,
+            Box::new(String::from("duplicate key")),
+        )));
+
+        assert!(error.is_unique_violation());
+        assert!(!AppError::Diesel(DieselError::NotFound).is_unique_violation());
+    }
+
     #[tokio::test]
     async fn post_not_found_is_not_404() {
         let mut req = Request::new();
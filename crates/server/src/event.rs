@@ -20,7 +20,7 @@ use crate::data::connect;
 use crate::data::room::DbEvent;
 use crate::data::schema::*;
 use crate::utils::SeqnumQueueGuard;
-use crate::{AppError, AppResult, MatrixError};
+use crate::{AppError, AppResult, MatrixError, OptionalExtension as _};
 
 /// Generates a correct eventId for the incoming pdu.
 ///
@@ -130,7 +130,8 @@ pub async fn get_event_id_by_sn(event_sn: Seqnum) -> AppResult<OwnedEventId> {
         .map_err(Into::into)
 }
 
-pub async fn get_event_for_timestamp(
+/// The local event closest to `timestamp` in the direction `dir`.
+pub async fn get_local_event_for_timestamp(
     room_id: &RoomId,
     timestamp: UnixMillis,
     dir: Direction,
@@ -169,12 +170,128 @@ pub async fn get_event_for_timestamp(
             Ok((local_event_id, origin_server_ts))
         }
     }
-    // TODO: implement this function to find the event for a given timestamp
-    // Check for gaps in the history where events could be hiding in between
-    // the timestamp given and the event we were able to find locally
-    // let is_event_next_to_backward_gap = false;
-    // let is_event_next_to_forward_gap = false;
-    // let local_event = None;
+}
+
+/// Servers asked at most for an event closer to a timestamp than the local one.
+const TIMESTAMP_TO_EVENT_SERVERS: usize = 5;
+
+/// The event closest to `timestamp` in the direction `dir` (MSC3030).
+///
+/// When the local event borders a gap in the history, or no local event is found, the
+/// servers in the room are asked as well. A closer remote event is fetched, checked and
+/// backfilled before it is returned.
+pub async fn get_event_for_timestamp(
+    room_id: &RoomId,
+    timestamp: UnixMillis,
+    dir: Direction,
+) -> AppResult<(OwnedEventId, UnixMillis)> {
+    let local_event = get_local_event_for_timestamp(room_id, timestamp, dir)
+        .await
+        .optional()?;
+    if let Some((event_id, _)) = &local_event {
+        let pdu = crate::room::timeline::get_pdu(event_id).await?;
+        let next_to_gap = match dir {
+            Direction::Backward => {
+                crate::room::timeline::is_event_next_to_forward_gap(&pdu).await?
+            }
+            Direction::Forward => {
+                crate::room::timeline::is_event_next_to_backward_gap(&pdu).await?
+            }
+        };
+        if !next_to_gap {
+            return Ok(local_event.expect("local event is some"));
+        }
+    }
+
+    // Asking every server in a large room would turn one request into thousands.
+    let mut remote_servers = crate::room::admin_servers(room_id, false).await?;
+    for server in
+        crate::room::oldest_joined_servers(room_id, TIMESTAMP_TO_EVENT_SERVERS as i64).await?
+    {
+        if !remote_servers.contains(&server) {
+            remote_servers.push(server);
+        }
+    }
+    remote_servers.truncate(TIMESTAMP_TO_EVENT_SERVERS);
+    let (remote_server, remote_event) = match handler::remote_timestamp_to_event(
+        &remote_servers,
+        room_id,
+        dir,
+        timestamp,
+        local_event.as_ref(),
+    )
+    .await
+    {
+        Ok(remote) => remote,
+        Err(e) => {
+            debug!("no closer remote event for timestamp {timestamp} in {room_id}: {e}");
+            return local_event.ok_or_else(|| MatrixError::not_found("no event found").into());
+        }
+    };
+    match pull_timestamp_event(&remote_server, room_id, &remote_event.event_id).await {
+        Ok(()) => Ok((remote_event.event_id, remote_event.origin_server_ts)),
+        Err(e) => {
+            warn!(
+                "failed to pull event {} from {remote_server} for timestamp_to_event: {e}",
+                remote_event.event_id
+            );
+            local_event.ok_or_else(|| MatrixError::not_found("no event found").into())
+        }
+    }
+}
+
+/// Fetch and verify an event found by a remote `timestamp_to_event`, then backfill
+/// around it so that it can be paginated from.
+async fn pull_timestamp_event(
+    remote_server: &ServerName,
+    room_id: &RoomId,
+    event_id: &EventId,
+) -> AppResult<()> {
+    let room_version = crate::room::get_version(room_id).await?;
+    let (event_id, event_value) = parse_fetched_pdu(
+        room_id,
+        &room_version,
+        &fetching::fetch_event(remote_server, event_id).await?.pdu,
+    )?;
+    handler::process_pulled_pdu(
+        remote_server,
+        &event_id,
+        room_id,
+        &room_version,
+        event_value.clone(),
+        true,
+    )
+    .await?;
+
+    // Until its prev_events are local the event is an outlier, which keeps it out of
+    // `/messages`, so the history leading up to it is backfilled.
+    if let Err(e) = crate::room::timeline::backfill_from_extremities(
+        room_id,
+        std::slice::from_ref(&event_id),
+        100,
+    )
+    .await
+    {
+        warn!("failed to backfill history around timestamp_to_event result: {e}");
+    }
+
+    // `/backfill` returns the predecessors of its seed, not necessarily the seed itself,
+    // so the event is processed again now that it can be connected to the timeline.
+    if crate::room::timeline::get_non_outlier_pdu(&event_id)
+        .await?
+        .is_none()
+    {
+        handler::process_pulled_pdu(
+            remote_server,
+            &event_id,
+            room_id,
+            &room_version,
+            event_value,
+            true,
+        )
+        .await?;
+    }
+    Ok(())
 }
 
 pub async fn get_event_sn_and_ty(event_id: &EventId) -> AppResult<(Seqnum, String)> {
@@ -31,6 +31,9 @@ use crate::room::{state, timeline};
 use crate::sending::send_federation_request;
 use crate::{AppError, AppResult, MatrixError, room};
 
+/// Seconds to wait for a server's answer to `timestamp_to_event`.
+const TIMESTAMP_TO_EVENT_TIMEOUT_SECS: u64 = 10;
+
 #[tracing::instrument(skip_all)]
 pub(crate) async fn process_incoming_pdu(
     remote_server: &ServerName,
@@ -74,6 +77,14 @@ pub(crate) async fn process_incoming_pdu(
             {
                 warn!("failed to delete event_datas for {}: {}", event_id, e);
             }
+            if let Err(e) = diesel::delete(
+                event_media_refs::table.filter(event_media_refs::event_id.eq(event_id)),
+            )
+            .execute(&mut connect().await?)
+            .await
+            {
+                warn!("failed to delete event_media_refs for {}: {}", event_id, e);
+            }
         }
     }
 
@@ -729,31 +740,38 @@ pub async fn remote_timestamp_to_event(
             },
         )?
         .into_inner();
-        let res_body = send_federation_request(remote_server, request, None)
-            .await?
-            .json::<TimestampToEventResBody>()
-            .await?;
+        let res_body = send_federation_request(
+            remote_server,
+            request,
+            Some(TIMESTAMP_TO_EVENT_TIMEOUT_SECS),
+        )
+        .await?
+        .json::<TimestampToEventResBody>()
+        .await?;
         Ok(res_body)
     }
+    // The first server that answers decides, the others are only asked when it
+    // can't.
     for remote_server in remote_servers {
-        if let Ok(res_body) = remote_event(remote_server, room_id, dir, ts).await {
-            if let Some((_exist_id, exist_ts)) = exist {
-                match dir {
-                    Direction::Forward => {
-                        if res_body.origin_server_ts < *exist_ts {
-                            return Ok((remote_server.to_owned(), res_body));
-                        }
-                    }
-                    Direction::Backward => {
-                        if res_body.origin_server_ts > *exist_ts {
-                            return Ok((remote_server.to_owned(), res_body));
-                        }
-                    }
-                }
-            } else {
-                return Ok((remote_server.to_owned(), res_body));
-            };
+        let res_body = match remote_event(remote_server, room_id, dir, ts).await {
+            Ok(res_body) => res_body,
+            Err(e) => {
+                debug!("{remote_server} failed to answer timestamp_to_event in {room_id}: {e}");
+                continue;
+            }
+        };
+        let right_direction = match dir {
+            Direction::Forward => res_body.origin_server_ts >= ts,
+            Direction::Backward => res_body.origin_server_ts <= ts,
+        };
+        let closer = exist.is_none_or(|(_, exist_ts)| match dir {
+            Direction::Forward => res_body.origin_server_ts < *exist_ts,
+            Direction::Backward => res_body.origin_server_ts > *exist_ts,
+        });
+        if right_direction && closer {
+            return Ok((remote_server.to_owned(), res_body));
         }
+        break;
     }
     Err(AppError::internal(
         "failed to get timestamp to event from remote servers",
@@ -195,6 +195,12 @@ impl SnPduEvent {
         Ok(())
     }
 
+    /// Adds the bundled aggregations of the latest edit and the references of
+    /// this event, see `room::pdu_metadata::bundle_aggregations`.
+    pub async fn add_bundled_aggregations(&mut self, user_id: &UserId) -> AppResult<()> {
+        room::pdu_metadata::bundle_aggregations([self], user_id).await
+    }
+
     pub fn from_canonical_object(
         room_id: &RoomId,
         event_id: &EventId,
@@ -400,6 +406,17 @@ pub struct PduEvent {
 impl PduEvent {
     #[tracing::instrument]
     pub fn redact(&mut self, reason: &PduEvent) -> AppResult<()> {
+        self.prune_content()?;
+        self.unsigned = BTreeMap::new();
+        self.unsigned.insert(
+            "redacted_because".to_owned(),
+            to_raw_value(reason).expect("to_raw_value(PduEvent) always works"),
+        );
+        Ok(())
+    }
+
+    /// Strips the content down to the keys a redaction preserves.
+    pub fn prune_content(&mut self) -> AppResult<()> {
         let allowed: &[&str] = match self.event_ty {
             TimelineEventType::RoomMember => &["join_authorised_via_users_server", "membership"],
             TimelineEventType::RoomCreate => &["creator"],
@@ -430,14 +447,7 @@ impl PduEvent {
             }
         }
 
-        self.unsigned = BTreeMap::new();
-        self.unsigned.insert(
-            "redacted_because".to_owned(),
-            to_raw_value(reason).expect("to_raw_value(PduEvent) always works"),
-        );
-
         self.content = to_raw_value(&new_content).expect("to string always works");
-
         Ok(())
     }
 
@@ -1,30 +1,179 @@
-use std::collections::BTreeMap;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, BTreeSet};
 
 use diesel::prelude::*;
+use diesel::sql_types::{Array, Bool, Float, Text};
 use diesel_async::RunQueryDsl;
 use palpo_core::Seqnum;
 
+use crate::config::{SearchBackend, SearchConfig};
 use crate::core::client::search::{
-    Criteria, EventContextResult, OrderBy, ResultRoomEvents, SearchResult,
+    Criteria, EventContext, EventContextResult, GroupingKey, Groupings, OrderBy,
+    OwnedRoomIdOrUserId, ResultGroup, ResultRoomEvents, SearchResult, UserProfile,
 };
-use crate::core::events::room::member::RoomMemberEventContent;
+use crate::core::events::room::member::{MembershipState, RoomMemberEventContent};
 use crate::core::events::{StateEventType, TimelineEventType};
 use crate::core::identifiers::*;
-use crate::core::serde::CanonicalJsonObject;
 use crate::core::serde::canonical_json::CanonicalJsonValue;
+use crate::core::serde::{CanonicalJsonObject, JsonObject, JsonValue};
+use crate::data::full_text_search::configuration::TsConfigurationByName;
 use crate::data::full_text_search::*;
 use crate::data::schema::*;
-use crate::data::{self, connect};
+use crate::data::{self, connect, connect_replica};
 use crate::event::BatchToken;
-use crate::room::{state, timeline};
-use crate::{AppResult, MatrixError, SnPduEvent};
-
-fn searchable_events<'a>(
-    room_ids: &'a [OwnedRoomId],
-    search_term: &'a str,
-) -> event_searches::BoxedQuery<'a, diesel::pg::Pg> {
-    event_searches::table
-        .filter(event_searches::room_id.eq_any(room_ids))
+use crate::room::{pdu_metadata, state, timeline};
+use crate::{AppError, AppResult, MatrixError, SnPduEvent, config};
+
+/// A room to search, with the last stream position the user may see in it.
+#[derive(Clone, Debug)]
+struct SearchRoom {
+    room_id: OwnedRoomId,
+    /// `None` while the user is joined, otherwise the position of their leave or ban.
+    until_sn: Option<Seqnum>,
+}
+
+/// Position in the result list to continue a search from.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum SearchToken {
+    /// Ordered by recency: `{origin_server_ts}-{event_sn}`.
+    Recent { server_ts: i64, event_sn: Seqnum },
+    /// Ordered by rank: `r{rank}-{event_sn}`.
+    Rank { rank: f32, event_sn: Seqnum },
+}
+
+impl SearchToken {
+    fn parse(token: &str) -> AppResult<Self> {
+        let invalid = || MatrixError::invalid_param("Invalid next_batch token.");
+        let (head, event_sn) = token.split_once('-').ok_or_else(invalid)?;
+        let event_sn = event_sn.parse().map_err(|_| invalid())?;
+        if let Some(rank) = head.strip_prefix('r') {
+            Ok(Self::Rank {
+                rank: rank.parse().map_err(|_| invalid())?,
+                event_sn,
+            })
+        } else {
+            Ok(Self::Recent {
+                server_ts: head.parse().map_err(|_| invalid())?,
+                event_sn,
+            })
+        }
+    }
+}
+
+impl std::fmt::Display for SearchToken {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Recent {
+                server_ts,
+                event_sn,
+            } => write!(f, "{server_ts}-{event_sn}"),
+            Self::Rank { rank, event_sn } => write!(f, "r{rank}-{event_sn}"),
+        }
+    }
+}
+
+/// Whether `c` belongs to a script written without spaces between words.
+fn is_cjk(c: char) -> bool {
+    matches!(c,
+        '\u{2E80}'..='\u{2FDF}' // CJK and Kangxi radicals
+        | '\u{3040}'..='\u{31FF}' // kana, bopomofo and Hangul jamo
+        | '\u{3400}'..='\u{4DBF}' // CJK extension A
+        | '\u{4E00}'..='\u{9FFF}' // CJK unified ideographs
+        | '\u{AC00}'..='\u{D7AF}' // Hangul syllables
+        | '\u{F900}'..='\u{FAFF}' // CJK compatibility ideographs
+        | '\u{FF66}'..='\u{FF9F}' // halfwidth katakana
+        | '\u{20000}'..='\u{2FA1F}' // CJK extensions B and later
+    )
+}
+
+/// Split every run of CJK characters into its characters and overlapping bigrams, so that
+/// Postgres indexes them as words and single-character queries match too. Other text is
+/// left untouched.
+fn cjk_ngrams(text: &str) -> String {
+    fn flush(run: &mut Vec<char>, out: &mut String) {
+        if run.is_empty() {
+            return;
+        }
+        for c in run.iter() {
+            out.push(' ');
+            out.push(*c);
+        }
+        for pair in run.windows(2) {
+            out.push(' ');
+            out.extend(pair);
+        }
+        out.push(' ');
+        run.clear();
+    }
+
+    let mut out = String::with_capacity(text.len() * 2);
+    let mut run = Vec::new();
+    for c in text.chars() {
+        if is_cjk(c) {
+            run.push(c);
+        } else {
+            flush(&mut run, &mut out);
+            out.push(c);
+        }
+    }
+    flush(&mut run, &mut out);
+    out
+}
+
+/// Text as the configured backend hands it to Postgres, for indexing and querying alike.
+fn tokenizable_text<'a>(conf: &SearchConfig, text: &'a str) -> Cow<'a, str> {
+    match conf.backend {
+        SearchBackend::Postgres => Cow::Borrowed(text),
+        SearchBackend::Ngram => Cow::Owned(cjk_ngrams(text)),
+    }
+}
+
+fn searchable_events(
+    rooms: &[SearchRoom],
+    search_term: &str,
+    conf: &'static SearchConfig,
+) -> event_searches::BoxedQuery<'static, diesel::pg::Pg> {
+    let joined_room_ids = rooms
+        .iter()
+        .filter(|room| room.until_sn.is_none())
+        .map(|room| room.room_id.clone())
+        .collect::<Vec<_>>();
+    let mut visible: Box<
+        dyn BoxableExpression<event_searches::table, diesel::pg::Pg, SqlType = Bool>,
+    > = Box::new(event_searches::room_id.eq_any(joined_room_ids));
+    for room in rooms {
+        if let Some(until_sn) = room.until_sn {
+            visible = Box::new(
+                visible.or(event_searches::room_id
+                    .eq(room.room_id.clone())
+                    .and(event_searches::event_sn.le(until_sn))),
+            );
+        }
+    }
+
+    // Each row is matched with the configuration it was indexed with, one branch per
+    // configuration so that the index on `vector` is still used.
+    let search_term = tokenizable_text(conf, search_term).into_owned();
+    let mut matched: Option<
+        Box<dyn BoxableExpression<event_searches::table, diesel::pg::Pg, SqlType = Bool>>,
+    > = None;
+    for language in conf.languages().collect::<BTreeSet<_>>() {
+        let branch = event_searches::language
+            .eq(language)
+            .and(
+                event_searches::vector.matches(websearch_to_tsquery_with_search_config(
+                    TsConfigurationByName(language),
+                    search_term.clone(),
+                )),
+            );
+        matched = Some(match matched {
+            Some(matched) => Box::new(matched.or(branch)),
+            None => Box::new(branch),
+        });
+    }
+
+    let query = event_searches::table
+        .filter(visible)
         .filter(
             event_searches::event_id.eq_any(
                 events::table
@@ -32,8 +181,31 @@ fn searchable_events<'a>(
                     .select(events::id),
             ),
         )
-        .filter(event_searches::vector.matches(websearch_to_tsquery(search_term)))
-        .into_boxed()
+        .into_boxed();
+    match matched {
+        Some(matched) => query.filter(matched),
+        None => query,
+    }
+}
+
+/// The requested rooms the user may search, dropping rooms they never could see.
+async fn search_rooms(user_id: &UserId, room_ids: Vec<OwnedRoomId>) -> AppResult<Vec<SearchRoom>> {
+    let mut rooms = Vec::with_capacity(room_ids.len());
+    for room_id in room_ids {
+        let until_sn = match crate::room::user::membership(user_id, &room_id).await {
+            Ok(MembershipState::Join) => None,
+            Ok(MembershipState::Leave | MembershipState::Ban) => {
+                match crate::room::user::left_sn(&room_id, user_id).await {
+                    Ok(left_sn) => Some(left_sn),
+                    Err(_) => continue,
+                }
+            }
+            _ if crate::room::is_world_readable(&room_id).await => None,
+            _ => continue,
+        };
+        rooms.push(SearchRoom { room_id, until_sn });
+    }
+    Ok(rooms)
 }
 
 pub async fn search_pdus(
@@ -47,73 +219,93 @@ pub async fn search_pdus(
         Some(rooms) => rooms,
         None => data::user::joined_rooms(user_id).await.unwrap_or_default(),
     };
+    let rooms = search_rooms(user_id, room_ids).await?;
 
     // Use limit or else 10, with maximum 100
     let limit = filter.limit.unwrap_or(10).min(100);
+    let order_by_rank = criteria.order_by == Some(OrderBy::Rank);
+    let conf = &config::get().search;
+    let search_term = tokenizable_text(conf, &criteria.search_term);
+    let rank = || {
+        diesel::dsl::sql::<Float>(
+            "ts_rank_cd(event_searches.vector, \
+             websearch_to_tsquery(event_searches.language::regconfig, ",
+        )
+        .bind::<Text, _>(search_term.to_string())
+        .sql("))")
+    };
 
-    for room_id in &room_ids {
-        if !crate::room::user::is_joined(user_id, room_id).await? {
-            return Err(MatrixError::forbidden(
-                "you don't have permission to view this room",
-                None,
-            )
-            .into());
+    let mut data_query = searchable_events(&rooms, &criteria.search_term, conf);
+    match next_batch.map(SearchToken::parse).transpose()? {
+        Some(SearchToken::Recent {
+            server_ts,
+            event_sn,
+        }) => {
+            data_query = data_query.filter(
+                event_searches::origin_server_ts
+                    .lt(server_ts)
+                    .or(event_searches::origin_server_ts
+                        .eq(server_ts)
+                        .and(event_searches::event_sn.lt(event_sn))),
+            );
         }
-    }
-
-    let mut data_query = searchable_events(&room_ids, &criteria.search_term);
-    if let Some(mut next_batch) = next_batch.map(|nb| nb.split('-')) {
-        let server_ts: i64 = next_batch.next().map(str::parse).transpose()?.unwrap_or(0);
-        let event_sn: i64 = next_batch.next().map(str::parse).transpose()?.unwrap_or(0);
-        data_query = data_query
-            .filter(event_searches::origin_server_ts.le(server_ts))
-            .filter(event_searches::event_sn.lt(event_sn));
+        Some(SearchToken::Rank {
+            rank: last_rank,
+            event_sn,
+        }) => {
+            data_query = data_query.filter(
+                rank().lt(last_rank).or(rank()
+                    .eq(last_rank)
+                    .and(event_searches::event_sn.lt(event_sn))),
+            );
+        }
+        None => {}
     }
     let data_query = data_query
         .select((
-            ts_rank_cd(
-                event_searches::vector,
-                websearch_to_tsquery(&criteria.search_term),
-            ),
-            // event_searches::room_id,
+            rank(),
             event_searches::event_id,
             event_searches::event_sn,
             event_searches::origin_server_ts,
-            // event_searches::stream_ordering,
         ))
         .limit(limit as i64);
-    let items = if criteria.order_by == Some(OrderBy::Rank) {
+    let items = if order_by_rank {
         data_query
-            .order_by(diesel::dsl::sql::<diesel::sql_types::Int8>("1"))
-            .load::<(f32, OwnedEventId, i64, i64)>(&mut connect().await?)
+            .order_by(rank().desc())
+            .then_order_by(event_searches::event_sn.desc())
+            .load::<(f32, OwnedEventId, i64, i64)>(&mut connect_replica().await?)
             .await?
     } else {
         data_query
             .order_by(event_searches::origin_server_ts.desc())
             .then_order_by(event_searches::event_sn.desc())
-            .load::<(f32, OwnedEventId, i64, i64)>(&mut connect().await?)
+            .load::<(f32, OwnedEventId, i64, i64)>(&mut connect_replica().await?)
             .await?
     };
-    // let _ids: Vec<i64> = event_searches::table
-    //     .select(event_searches::id)
-    //     .load(&mut connect()?)?;
-    let count: i64 = searchable_events(&room_ids, &criteria.search_term)
+    let count: i64 = searchable_events(&rooms, &criteria.search_term, conf)
         .count()
-        .first(&mut connect().await?)
+        .first(&mut connect_replica().await?)
         .await?;
     let next_batch = if items.len() < limit {
         None
-    } else if let Some(last) = items.last() {
-        if criteria.order_by == Some(OrderBy::Recent) || criteria.order_by.is_none() {
-            Some(format!("{}-{}", last.3, last.2))
-        } else {
-            None
-        }
     } else {
-        None
+        items.last().map(|(rank, _, event_sn, server_ts)| {
+            if order_by_rank {
+                SearchToken::Rank {
+                    rank: *rank,
+                    event_sn: *event_sn,
+                }
+            } else {
+                SearchToken::Recent {
+                    server_ts: *server_ts,
+                    event_sn: *event_sn,
+                }
+            }
+            .to_string()
+        })
     };
 
-    let mut results: Vec<_> = Vec::new();
+    let mut hits = Vec::new();
     for (rank, event_id, ..) in items {
         let Ok(pdu) = timeline::get_pdu(&event_id).await else {
             continue;
@@ -124,8 +316,37 @@ pub async fn search_pdus(
         {
             continue;
         }
+        hits.push((rank, pdu));
+    }
+
+    let highlights = highlights(conf, &search_term, &hits).await?;
+    let groups = group_results(&criteria.groupings, &hits);
+    let mut state = BTreeMap::new();
+    if criteria.include_state == Some(true) {
+        for (_, pdu) in &hits {
+            if state.contains_key(&pdu.room_id) {
+                continue;
+            }
+            let until_sn = rooms
+                .iter()
+                .find(|room| room.room_id == pdu.room_id)
+                .and_then(|room| room.until_sn);
+            let Ok(frame_id) = crate::event::get_last_frame_id(&pdu.room_id, until_sn).await else {
+                continue;
+            };
+            let events = state::get_full_state(frame_id)
+                .await?
+                .into_values()
+                .map(|pdu| pdu.to_state_event())
+                .collect();
+            state.insert(pdu.room_id.clone(), events);
+        }
+    }
+
+    let mut results = Vec::with_capacity(hits.len());
+    for (rank, pdu) in hits {
         results.push(SearchResult {
-            context: calc_event_context(user_id, &pdu.room_id, pdu.event_sn, 10, 10, false)
+            context: calc_event_context(user_id, &pdu, &criteria.event_context)
                 .await
                 .unwrap_or_default(),
             rank: Some(rank as f64),
@@ -135,28 +356,118 @@ pub async fn search_pdus(
 
     Ok(ResultRoomEvents {
         count: Some(count as u64),
-        groups: BTreeMap::new(), // TODO
+        groups,
         next_batch,
         results,
-        state: BTreeMap::new(), // TODO
-        highlights: criteria
-            .search_term
-            .split_terminator(|c: char| !c.is_alphanumeric())
-            .map(str::to_lowercase)
-            .collect(),
+        state,
+        highlights,
     })
 }
 
+/// Group the ids of the results by room or sender, as asked by the client.
+///
+/// A group's `order` is the position of its first result.
+fn group_results(
+    groupings: &Groupings,
+    hits: &[(f32, SnPduEvent)],
+) -> BTreeMap<GroupingKey, BTreeMap<OwnedRoomIdOrUserId, ResultGroup>> {
+    let mut groups = BTreeMap::new();
+    for grouping in &groupings.group_by {
+        let Some(key) = &grouping.key else {
+            continue;
+        };
+        let mut group: BTreeMap<OwnedRoomIdOrUserId, ResultGroup> = BTreeMap::new();
+        for (order, (_, pdu)) in hits.iter().enumerate() {
+            let id = match key {
+                GroupingKey::RoomId => OwnedRoomIdOrUserId::RoomId(pdu.room_id.clone()),
+                GroupingKey::Sender => OwnedRoomIdOrUserId::UserId(pdu.sender.clone()),
+                _ => break,
+            };
+            group
+                .entry(id)
+                .or_insert_with(|| ResultGroup {
+                    order: Some(order as u64),
+                    ..Default::default()
+                })
+                .results
+                .push(pdu.event_id.clone());
+        }
+        if !group.is_empty() {
+            groups.insert(key.clone(), group);
+        }
+    }
+    groups
+}
+
+const HIGHLIGHT_START: char = '\u{1}';
+const HIGHLIGHT_STOP: char = '\u{2}';
+
+#[derive(QueryableByName)]
+struct Headline {
+    #[diesel(sql_type = Text)]
+    headline: String,
+}
+
+/// The words of the results that matched the search, as marked by `ts_headline`.
+///
+/// Deriving them from the query keeps stemmed matches ("running" for "run") highlighted.
+async fn highlights(
+    conf: &SearchConfig,
+    search_term: &str,
+    hits: &[(f32, SnPduEvent)],
+) -> AppResult<Vec<String>> {
+    let (texts, languages): (Vec<_>, Vec<_>) = hits
+        .iter()
+        .filter_map(|(_, pdu)| {
+            let content = pdu.get_content::<JsonObject>().ok()?;
+            let (_, text) = searchable_content(&pdu.event_ty, &content)?;
+            Some((
+                tokenizable_text(conf, text).into_owned(),
+                conf.language(&pdu.room_id).to_owned(),
+            ))
+        })
+        .unzip();
+    if texts.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let headlines = diesel::sql_query(
+        "SELECT ts_headline(h.language::regconfig, h.t, \
+            websearch_to_tsquery(h.language::regconfig, $3), $4) AS headline \
+        FROM unnest($1::text[], $2::text[]) AS h(t, language)",
+    )
+    .bind::<Array<Text>, _>(texts)
+    .bind::<Array<Text>, _>(languages)
+    .bind::<Text, _>(search_term)
+    .bind::<Text, _>(format!(
+        "StartSel={HIGHLIGHT_START}, StopSel={HIGHLIGHT_STOP}, HighlightAll=true"
+    ))
+    .load::<Headline>(&mut connect_replica().await?)
+    .await?;
+
+    let mut words = BTreeSet::new();
+    for headline in &headlines {
+        words.extend(marked_words(&headline.headline).map(str::to_owned));
+    }
+    Ok(words.into_iter().collect())
+}
+
+fn marked_words(headline: &str) -> impl Iterator<Item = &str> {
+    headline
+        .split(HIGHLIGHT_START)
+        .skip(1)
+        .filter_map(|part| part.split_once(HIGHLIGHT_STOP).map(|(word, _)| word))
+        .filter(|word| !word.is_empty())
+}
+
 // Calculates the contextual events for any search results.
 async fn calc_event_context(
     user_id: &UserId,
-    room_id: &RoomId,
-    event_sn: Seqnum,
-    before_limit: usize,
-    after_limit: usize,
-    include_profile: bool,
+    pdu: &SnPduEvent,
+    context: &EventContext,
 ) -> AppResult<EventContextResult> {
-    let (before_boundary, after_boundary) = context_boundaries(event_sn);
+    let room_id = &pdu.room_id;
+    let (before_boundary, after_boundary) = context_boundaries(pdu.event_sn);
     let before_pdus = timeline::stream::load_pdus_backward(
         Some(user_id),
         room_id,
@@ -165,7 +476,7 @@ async fn calc_event_context(
         Some(before_boundary),
         None,
         None,
-        before_limit,
+        context.before_limit as usize,
     )
     .await?;
     let after_pdus = timeline::stream::load_pdus_forward(
@@ -176,22 +487,38 @@ async fn calc_event_context(
         Some(after_boundary),
         None,
         None,
-        after_limit,
+        context.after_limit as usize,
     )
     .await?;
-    let mut profile = BTreeMap::new();
-    if include_profile && let Ok(frame_id) = crate::event::get_frame_id(room_id, event_sn).await {
-        let RoomMemberEventContent {
-            display_name,
-            avatar_url,
-            ..
-        } = state::get_state_content(frame_id, &StateEventType::RoomMember, user_id.as_str())
-            .await?;
-        if let Some(display_name) = display_name {
-            profile.insert("displayname".to_string(), display_name);
-        }
-        if let Some(avatar_url) = avatar_url {
-            profile.insert("avatar_url".to_string(), avatar_url.to_string());
+
+    // Profiles of the senders as of the search hit.
+    let mut profile_info = BTreeMap::new();
+    if context.include_profile
+        && let Ok(frame_id) = crate::event::get_frame_id(room_id, pdu.event_sn).await
+    {
+        let senders = before_pdus
+            .iter()
+            .chain(after_pdus.iter())
+            .map(|(_, pdu)| &pdu.sender)
+            .chain([&pdu.sender])
+            .collect::<BTreeSet<_>>();
+        for sender in senders {
+            let Ok(RoomMemberEventContent {
+                display_name,
+                avatar_url,
+                ..
+            }) = state::get_state_content(frame_id, &StateEventType::RoomMember, sender.as_str())
+                .await
+            else {
+                continue;
+            };
+            profile_info.insert(
+                sender.clone(),
+                UserProfile {
+                    avatar_url,
+                    display_name,
+                },
+            );
         }
     }
 
@@ -211,7 +538,7 @@ async fn calc_event_context(
             .into_iter()
             .map(|(_, pdu)| pdu.to_room_event())
             .collect(),
-        profile_info: BTreeMap::new(),
+        profile_info,
     };
 
     Ok(context)
@@ -225,10 +552,124 @@ fn context_boundaries(event_sn: Seqnum) -> (BatchToken, BatchToken) {
 }
 
 pub async fn save_pdu(pdu: &SnPduEvent, pdu_json: &CanonicalJsonObject) -> AppResult<()> {
+    // An edit is not indexed itself: its new content replaces the indexed content
+    // of the original event, as long as it is the latest edit of that event.
+    if let Some((original_id, new_content)) = pdu_metadata::replacement_new_content(pdu) {
+        let Ok(original) = timeline::get_pdu(&original_id).await else {
+            return Ok(());
+        };
+        let is_latest_edit = pdu_metadata::latest_replacement(&original)
+            .await?
+            .is_some_and(|edit| edit.event_id == pdu.event_id);
+        if is_latest_edit
+            && let Some((key, value)) = searchable_content(&original.event_ty, &new_content)
+        {
+            upsert_search(&original, key, value).await?;
+        }
+        return Ok(());
+    }
+
     let Some(CanonicalJsonValue::Object(content)) = pdu_json.get("content") else {
         return Ok(());
     };
-    let Some((key, vector)) = (match pdu.event_ty {
+    let JsonValue::Object(content) = CanonicalJsonValue::Object(content.clone()).into() else {
+        return Ok(());
+    };
+    let Some((key, value)) = searchable_content(&pdu.event_ty, &content) else {
+        return Ok(());
+    };
+    upsert_search(pdu, key, value).await
+}
+
+#[derive(QueryableByName)]
+struct TsConfigName {
+    #[diesel(sql_type = Text)]
+    cfgname: String,
+}
+
+/// Check that every configured search language is a text search configuration of
+/// the database, since indexing an event with an unknown one fails and the event
+/// would not be stored.
+pub async fn check_languages() -> AppResult<()> {
+    let known = diesel::sql_query("SELECT cfgname::text AS cfgname FROM pg_ts_config")
+        .load::<TsConfigName>(&mut connect().await?)
+        .await?
+        .into_iter()
+        .map(|config| config.cfgname)
+        .collect::<BTreeSet<_>>();
+    // Names may be schema-qualified, e.g. `pg_catalog.english`.
+    let unknown = config::get().search.languages().find(|language| {
+        let name = language.rsplit('.').next().unwrap_or(language);
+        !known.contains(name)
+    });
+    match unknown {
+        Some(language) => Err(AppError::internal(format!(
+            "search language `{language}` is not a text search configuration of the database"
+        ))),
+        None => Ok(()),
+    }
+}
+
+/// Rebuild the search index of every indexed event with the current search settings,
+/// returning how many events were reindexed.
+///
+/// Progress is posted to the admin room every `PROGRESS_INTERVAL` events.
+pub async fn reindex_all() -> AppResult<usize> {
+    const BATCH_SIZE: i64 = 500;
+    const PROGRESS_INTERVAL: usize = 10_000;
+
+    let mut last_id = 0;
+    let mut count = 0;
+    let mut reported = 0;
+    loop {
+        let batch = event_searches::table
+            .filter(event_searches::id.gt(last_id))
+            .order_by(event_searches::id.asc())
+            .select((event_searches::id, event_searches::event_id))
+            .limit(BATCH_SIZE)
+            .load::<(i64, OwnedEventId)>(&mut connect().await?)
+            .await?;
+        let Some((id, _)) = batch.last() else {
+            break;
+        };
+        last_id = *id;
+
+        for (_, event_id) in batch {
+            let Ok(pdu) = timeline::get_pdu(&event_id).await else {
+                continue;
+            };
+            // Edited events are indexed with the content of their latest edit.
+            let content = match pdu_metadata::latest_replacement(&pdu).await? {
+                Some(edit) => pdu_metadata::replacement_new_content(&edit).map(|(_, c)| c),
+                None => pdu.get_content::<JsonObject>().ok(),
+            };
+            if let Some((key, value)) = content
+                .as_ref()
+                .and_then(|content| searchable_content(&pdu.event_ty, content))
+            {
+                upsert_search(&pdu, key, value).await?;
+                count += 1;
+            }
+        }
+        if count >= reported + PROGRESS_INTERVAL {
+            reported = count;
+            tracing::info!("reindexed {count} events");
+            if let Err(e) =
+                crate::admin::send_text(&format!("Reindexed {count} events so far.")).await
+            {
+                tracing::warn!("failed to report search reindex progress: {e}");
+            }
+        }
+    }
+    Ok(count)
+}
+
+/// The index key and text of the searchable part of an event's content.
+fn searchable_content<'a>(
+    event_ty: &TimelineEventType,
+    content: &'a JsonObject,
+) -> Option<(&'static str, &'a str)> {
+    match event_ty {
         TimelineEventType::RoomName => content
             .get("name")
             .and_then(|v| v.as_str())
@@ -243,23 +684,23 @@ pub async fn save_pdu(pdu: &SnPduEvent, pdu_json: &CanonicalJsonObject) -> AppRe
             .map(|v| ("content.message", v)),
         // Redaction events themselves have no searchable content. Applying a
         // redaction removes the target from the index in `timeline::redact_pdu`.
-        TimelineEventType::RoomRedaction => return Ok(()),
-        _ => {
-            return Ok(());
-        }
-    }) else {
-        return Ok(());
-    };
-    diesel::sql_query("INSERT INTO event_searches (event_id, event_sn, room_id, sender_id, key, vector, origin_server_ts) VALUES ($1, $2, $3, $4, $5, to_tsvector('english', $6), $7) ON CONFLICT (event_id) DO UPDATE SET vector = to_tsvector('english', $6), origin_server_ts = $7")
+        _ => None,
+    }
+}
+
+async fn upsert_search(pdu: &SnPduEvent, key: &str, value: &str) -> AppResult<()> {
+    let conf = &config::get().search;
+    let language = conf.language(&pdu.room_id);
+    let value = tokenizable_text(conf, value);
+    diesel::sql_query("INSERT INTO event_searches (event_id, event_sn, room_id, sender_id, key, vector, origin_server_ts, language) VALUES ($1, $2, $3, $4, $5, to_tsvector($8::regconfig, $6), $7, $8) ON CONFLICT (event_id) DO UPDATE SET vector = to_tsvector($8::regconfig, $6), origin_server_ts = $7, language = $8")
         .bind::<diesel::sql_types::Text, _>(pdu.event_id.as_str())
         .bind::<diesel::sql_types::Nullable<diesel::sql_types::Int8>, _>(pdu.event_sn)
         .bind::<diesel::sql_types::Text, _>(&pdu.room_id)
         .bind::<diesel::sql_types::Text, _>(&pdu.sender)
         .bind::<diesel::sql_types::Text, _>(key)
-        .bind::<diesel::sql_types::Text, _>(vector)
-        .bind::<diesel::sql_types::Int8, _>(pdu.origin_server_ts)
-        .bind::<diesel::sql_types::Text, _>(vector)
+        .bind::<diesel::sql_types::Text, _>(value.as_ref())
         .bind::<diesel::sql_types::Int8, _>(pdu.origin_server_ts)
+        .bind::<diesel::sql_types::Text, _>(language)
         .execute(&mut connect().await?)
         .await?;
 
@@ -271,14 +712,24 @@ mod tests {
     use diesel::debug_query;
     use diesel::pg::Pg;
 
-    use super::{context_boundaries, searchable_events};
+    use std::sync::LazyLock;
+
+    use super::{
+        SearchRoom, SearchToken, cjk_ngrams, context_boundaries, marked_words, searchable_events,
+    };
+    use crate::config::SearchConfig;
     use crate::core::identifiers::RoomId;
     use crate::event::BatchToken;
 
+    static SEARCH_CONFIG: LazyLock<SearchConfig> = LazyLock::new(SearchConfig::default);
+
     #[test]
     fn search_query_excludes_redacted_events() {
-        let room_ids = vec![RoomId::parse("!room:example.org").unwrap().to_owned()];
-        let query = searchable_events(&room_ids, "needle");
+        let rooms = vec![SearchRoom {
+            room_id: RoomId::parse("!room:example.org").unwrap().to_owned(),
+            until_sn: None,
+        }];
+        let query = searchable_events(&rooms, "needle", &SEARCH_CONFIG);
         let sql = debug_query::<Pg, _>(&query).to_string();
 
         assert!(sql.contains("\"events\".\"is_redacted\" ="), "{sql}");
@@ -295,4 +746,59 @@ mod tests {
             (BatchToken::new_live(42), BatchToken::new_live(43))
         );
     }
+
+    #[test]
+    fn search_query_bounds_left_rooms_by_their_leave() {
+        let rooms = vec![SearchRoom {
+            room_id: RoomId::parse("!left:example.org").unwrap().to_owned(),
+            until_sn: Some(7),
+        }];
+        let query = searchable_events(&rooms, "needle", &SEARCH_CONFIG);
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains("\"event_searches\".\"event_sn\" <="), "{sql}");
+    }
+
+    #[test]
+    fn search_tokens_round_trip() {
+        for token in [
+            SearchToken::Recent {
+                server_ts: 1_700_000_000_000,
+                event_sn: 42,
+            },
+            SearchToken::Rank {
+                rank: 0.1,
+                event_sn: 42,
+            },
+        ] {
+            assert_eq!(SearchToken::parse(&token.to_string()).unwrap(), token);
+        }
+        assert!(SearchToken::parse("garbage").is_err());
+    }
+
+    #[test]
+    fn highlights_are_the_marked_words() {
+        let headline = "I was \u{1}running\u{2} to the \u{1}Run\u{2} club";
+        assert_eq!(
+            marked_words(headline).collect::<Vec<_>>(),
+            ["running", "Run"]
+        );
+    }
+
+    #[test]
+    fn cjk_runs_are_split_into_unigrams_and_bigrams() {
+        assert_eq!(cjk_ngrams("我爱北京"), " 我 爱 北 京 我爱 爱北 北京 ");
+        assert_eq!(cjk_ngrams("hello 世界!"), "hello  世 界 世界 !");
+        assert_eq!(cjk_ngrams("a中b"), "a 中 b");
+        assert_eq!(cjk_ngrams("plain text"), "plain text");
+    }
+
+    #[test]
+    fn search_query_matches_rows_with_their_own_language() {
+        let query = searchable_events(&[], "needle", &SEARCH_CONFIG);
+        let sql = debug_query::<Pg, _>(&query).to_string();
+
+        assert!(sql.contains("\"event_searches\".\"language\" ="), "{sql}");
+        assert!(sql.contains("websearch_to_tsquery('english'"), "{sql}");
+    }
 }
@@ -91,6 +91,43 @@ impl GetUrlOrigin for ServerName {
     }
 }
 
+/// The cached resolution of `destination`, with the addresses its hostname was delegated
+/// to through an SRV record, if any.
+pub fn cached_destination(
+    destination: &ServerName,
+) -> Option<(DestinationResponse, Option<(Vec<IpAddr>, u16)>)> {
+    let response = crate::ACTUAL_DESTINATION_CACHE
+        .read()
+        .unwrap()
+        .get(destination)
+        .cloned()?;
+    let srv_override = crate::TLS_NAME_OVERRIDE
+        .read()
+        .unwrap()
+        .get(&response.actual_destination.hostname())
+        .cloned();
+    Some((response, srv_override))
+}
+
+/// Forgets how to reach `destination`, so the next request resolves it again.
+///
+/// The DNS records cache is cleared as well, as it is shared by every destination.
+pub fn flush_destination(destination: &ServerName) -> bool {
+    crate::dns_resolver().clear_cache();
+    let Some(response) = crate::ACTUAL_DESTINATION_CACHE
+        .write()
+        .unwrap()
+        .remove(destination)
+    else {
+        return false;
+    };
+    crate::TLS_NAME_OVERRIDE
+        .write()
+        .unwrap()
+        .remove(&response.actual_destination.hostname());
+    true
+}
+
 /// Wraps either an literal IP address plus port, or a hostname plus complement
 /// (colon-plus-port if it was specified).
 ///
@@ -123,14 +160,14 @@ impl FedDest {
         }
     }
 
-    fn into_uri_string(self) -> String {
+    pub fn into_uri_string(self) -> String {
         match self {
             Self::Literal(addr) => addr.to_string(),
             Self::Named(host, ref port) => host + port,
         }
     }
 
-    fn hostname(&self) -> String {
+    pub fn hostname(&self) -> String {
         match &self {
             Self::Literal(addr) => addr.ip().to_string(),
             Self::Named(host, _) => host.clone(),
@@ -65,7 +65,7 @@ pub(crate) async fn send_request(
 
     signatures::sign_json(
         config::get().server_name.as_str(),
-        config::keypair(),
+        &*config::keypair(),
         &mut request_json,
     )
     .expect("our request json is what palpo expects");
@@ -3,7 +3,7 @@ use std::future::Future;
 use std::net::IpAddr;
 use std::sync::atomic::AtomicBool;
 use std::sync::{Arc, LazyLock, OnceLock, RwLock};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use diesel::prelude::*;
 use diesel_async::RunQueryDsl;
@@ -40,7 +40,8 @@ pub type RoomMutexMap = MutexMap<OwnedRoomId, ()>;
 pub type RoomMutexGuard = MutexMapGuard<OwnedRoomId, ()>;
 
 pub type LazyRwLock<T> = LazyLock<RwLock<T>>;
-pub static TLS_NAME_OVERRIDE: LazyRwLock<TlsNameMap> = LazyLock::new(Default::default);
+/// Addresses of hostnames delegated to through SRV records, shared with the federation client.
+pub static TLS_NAME_OVERRIDE: LazyLock<Arc<RwLock<TlsNameMap>>> = LazyLock::new(Default::default);
 pub static BAD_EVENT_RATE_LIMITER: LazyRwLock<HashMap<OwnedEventId, RateLimitState>> =
     LazyLock::new(Default::default);
 pub static BAD_SIGNATURE_RATE_LIMITER: LazyRwLock<HashMap<Vec<String>, RateLimitState>> =
@@ -103,12 +104,58 @@ pub fn seqnum_reach(sn: Seqnum) -> SeqnumQueueFuture {
 pub fn dns_resolver() -> &'static HickoryResolver<TokioRuntimeProvider> {
     static DNS_RESOLVER: OnceLock<HickoryResolver<TokioRuntimeProvider>> = OnceLock::new();
     DNS_RESOLVER.get_or_init(|| {
-        HickoryResolver::builder_with_config(
-            ResolverConfig::default(),
-            TokioRuntimeProvider::default(),
-        )
-        .build()
-        .expect("failed to build DNS resolver")
+        let conf = crate::config::get();
+        let (system_config, mut opts) = hickory_resolver::system_conf::read_system_conf()
+            .unwrap_or_else(|e| {
+                warn!("failed to read system DNS configuration, using defaults: {e}");
+                (ResolverConfig::default(), ResolverOpts::default())
+            });
+
+        let mut nameservers = conf.dns.nameservers.clone();
+        if nameservers.is_empty() {
+            for ns in system_config.name_servers() {
+                if !nameservers.contains(&ns.ip) {
+                    nameservers.push(ns.ip);
+                }
+            }
+        }
+        let nameservers = nameservers
+            .into_iter()
+            .map(|ip| {
+                let mut ns = if conf.query_over_tcp_only {
+                    NameServerConfig::tcp(ip)
+                } else if conf.dns.tcp_fallback {
+                    NameServerConfig::udp_and_tcp(ip)
+                } else {
+                    NameServerConfig::udp(ip)
+                };
+                ns.trust_negative_responses = !conf.query_all_nameservers;
+                ns
+            })
+            .collect();
+        let resolver_config = ResolverConfig::from_parts(
+            system_config.domain().cloned(),
+            system_config.search().to_vec(),
+            nameservers,
+        );
+
+        opts.cache_size = conf.dns.cache_entries.into();
+        opts.positive_min_ttl = Some(Duration::from_secs(conf.dns.min_ttl));
+        opts.negative_min_ttl = Some(Duration::from_secs(conf.dns.min_ttl_nxdomain));
+        opts.attempts = conf.dns.attempts.into();
+        opts.timeout = Duration::from_secs(conf.dns.timeout);
+        opts.ip_strategy = match conf.ip_lookup_strategy {
+            1 => LookupIpStrategy::Ipv4Only,
+            2 => LookupIpStrategy::Ipv6Only,
+            3 => LookupIpStrategy::Ipv4AndIpv6,
+            4 => LookupIpStrategy::Ipv6thenIpv4,
+            _ => LookupIpStrategy::Ipv4thenIpv6,
+        };
+
+        HickoryResolver::builder_with_config(resolver_config, TokioRuntimeProvider::default())
+            .with_options(opts)
+            .build()
+            .expect("failed to build DNS resolver")
     })
 }
 
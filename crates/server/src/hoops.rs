@@ -90,6 +90,45 @@ impl RateLimiter {
     }
 }
 
+/// Takes the client address from the reverse proxy headers, for connections accepted
+/// on UNIX socket listeners and on listeners with `x_forwarded` enabled.
+#[handler]
+pub async fn forwarded_remote_addr(req: &mut Request) {
+    let trusts_forwarded = |local_addr: std::net::SocketAddr| {
+        crate::config::get()
+            .listeners
+            .iter()
+            .any(|listener| listener.x_forwarded && listener.is_bound_to(&local_addr))
+    };
+    let trusted = match req.local_addr() {
+        #[cfg(unix)]
+        salvo::conn::SocketAddr::Unix(_) => true,
+        salvo::conn::SocketAddr::IPv4(a) => trusts_forwarded((*a).into()),
+        salvo::conn::SocketAddr::IPv6(a) => trusts_forwarded((*a).into()),
+        _ => false,
+    };
+    if !trusted {
+        return;
+    }
+
+    // The proxy appends the address it saw to `X-Forwarded-For`, the entries before
+    // it are whatever the client sent.
+    let forwarded_ip = req
+        .headers()
+        .get("X-Forwarded-For")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.rsplit(',').next())
+        .or_else(|| {
+            req.headers()
+                .get("X-Real-IP")
+                .and_then(|value| value.to_str().ok())
+        })
+        .and_then(|ip| ip.trim().parse::<std::net::IpAddr>().ok());
+    if let Some(ip) = forwarded_ip {
+        *req.remote_addr_mut() = std::net::SocketAddr::new(ip, 0).into();
+    }
+}
+
 fn extract_ip(req: &Request) -> Option<String> {
     match req.remote_addr() {
         salvo::conn::SocketAddr::IPv4(a) => Some(a.ip().to_string()),
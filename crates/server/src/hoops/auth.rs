@@ -66,7 +66,10 @@ pub async fn auth_by_signatures(
     auth_by_signatures_inner(req, depot).await
 }
 
-async fn auth_by_access_token_inner(aa: AuthArgs, depot: &mut Depot) -> AppResult<()> {
+/// Authenticates the access token of `aa`, storing the result in `depot`.
+///
+/// Also used by routes where authentication is optional.
+pub(crate) async fn auth_by_access_token_inner(aa: AuthArgs, depot: &mut Depot) -> AppResult<()> {
     let token = aa.require_access_token()?;
 
     if auth_by_local_token(token, &aa, depot).await? {
@@ -260,7 +263,7 @@ async fn get_or_create_appservice_user(user_id: &UserId, appservice_id: &str) ->
         created_at: UnixMillis::now(),
     };
 
-    connect()
+    let user = connect()
         .await?
         .transaction::<_, AppError, _>(async |conn| {
             // The upsert locks the user row until this transaction commits. Concurrent
@@ -297,7 +300,9 @@ async fn get_or_create_appservice_user(user_id: &UserId, appservice_id: &str) ->
 
             Ok(user)
         })
-        .await
+        .await?;
+    crate::data::user::directory::sync_profile(user_id).await?;
+    Ok(user)
 }
 
 /// Get or create a device for an appservice user
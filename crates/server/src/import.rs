@@ -0,0 +1,2 @@
+//! Importers for data created by other homeservers.
+pub mod synapse;
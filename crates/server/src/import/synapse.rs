@@ -0,0 +1,891 @@
+//! One-shot migration from a Synapse deployment.
+//!
+//! The importer reads the Synapse PostgreSQL database directly and writes into the
+//! Palpo database through the regular data layer, so it must run against the
+//! target Palpo configuration with the server stopped. Every step is idempotent:
+//! rows that already exist are left untouched, which makes it safe to re-run an
+//! import that was interrupted.
+//!
+//! Synapse must have been configured with the same `server_name`. Password hashes
+//! are carried over as-is (bcrypt) and upgraded to argon2 on the next successful
+//! login; deployments that set Synapse's `password_config.pepper` cannot reuse
+//! them and users will need to reset their passwords.
+mod room;
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Nullable, Text};
+use diesel_async::{AsyncConnection, AsyncPgConnection, RunQueryDsl};
+use serde_json::json;
+
+use crate::core::UnixMillis;
+use crate::core::events::GlobalAccountDataEventType;
+use crate::core::events::push_rules::PushRulesEventContent;
+use crate::core::identifiers::*;
+use crate::core::push::Ruleset;
+use crate::core::serde::JsonValue;
+use crate::data::media::NewDbMetadata;
+use crate::data::schema::*;
+use crate::data::user::{
+    NewDbAccessToken, NewDbCrossSignature, NewDbCrossSigningKey, NewDbDeviceKey, NewDbFallbackKey,
+    NewDbOneTimeKey, NewDbProfile, NewDbRoomKey, NewDbRoomKeysVersion, NewDbUser, NewDbUserDevice,
+};
+use crate::data::{connect, diesel_exists};
+use crate::media::media_storage_key;
+use crate::{AppError, AppResult, config, data, storage, utils};
+
+/// Number of rows fetched from Synapse per round trip for the large tables.
+const BATCH_SIZE: i64 = 1000;
+
+/// Runs a complete import from the Synapse database at `database_url`.
+///
+/// `media_store` is Synapse's `media_store_path`; when it is omitted only media
+/// metadata is imported and the files have to be copied separately.
+pub async fn run(database_url: &str, media_store: Option<&Path>) -> AppResult<()> {
+    let mut src = AsyncPgConnection::establish(database_url)
+        .await
+        .map_err(|e| AppError::public(format!("failed to connect to synapse database: {e}")))?;
+
+    check_server_name(&mut src).await?;
+
+    info!("importing users");
+    let users = import_users(&mut src).await?;
+    info!("imported {} active users", users.len());
+
+    for user_id in &users {
+        if let Err(e) = import_user_data(&mut src, user_id).await {
+            error!(%user_id, "failed to import user data: {e}");
+        }
+    }
+
+    info!("importing rooms");
+    room::import_rooms(&mut src).await?;
+
+    info!("importing media");
+    import_media(&mut src, media_store).await?;
+
+    info!("synapse import finished");
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct ServerNameRow {
+    #[diesel(sql_type = Nullable<Text>)]
+    name: Option<String>,
+}
+
+/// Refuses to import a database that belongs to a different server name, since
+/// every user, room alias and media ID would end up pointing at the wrong host.
+async fn check_server_name(src: &mut AsyncPgConnection) -> AppResult<()> {
+    let row = diesel::sql_query("SELECT MIN(name) AS name FROM users WHERE appservice_id IS NULL")
+        .get_result::<ServerNameRow>(src)
+        .await?;
+    let Some(name) = row.name else {
+        return Ok(());
+    };
+    let user_id = UserId::parse(&name)?;
+    if user_id.server_name() != config::server_name() {
+        return Err(AppError::public(format!(
+            "synapse server name `{}` does not match configured server name `{}`",
+            user_id.server_name(),
+            config::server_name()
+        )));
+    }
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct UserRow {
+    #[diesel(sql_type = Text)]
+    name: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    password_hash: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    creation_ts: Option<i64>,
+    #[diesel(sql_type = Bool)]
+    is_admin: bool,
+    #[diesel(sql_type = Bool)]
+    is_guest: bool,
+    #[diesel(sql_type = Nullable<Text>)]
+    appservice_id: Option<String>,
+    #[diesel(sql_type = Bool)]
+    deactivated: bool,
+    #[diesel(sql_type = Bool)]
+    shadow_banned: bool,
+    #[diesel(sql_type = Nullable<Text>)]
+    displayname: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    avatar_url: Option<String>,
+}
+
+async fn import_users(src: &mut AsyncPgConnection) -> AppResult<Vec<OwnedUserId>> {
+    let rows = diesel::sql_query(
+        "SELECT u.name, u.password_hash, u.creation_ts, \
+                COALESCE(u.admin, 0) <> 0 AS is_admin, \
+                COALESCE(u.is_guest, 0) <> 0 AS is_guest, \
+                u.appservice_id, \
+                COALESCE(u.deactivated, 0) <> 0 AS deactivated, \
+                COALESCE(u.shadow_banned, FALSE) AS shadow_banned, \
+                p.displayname, p.avatar_url \
+         FROM users u \
+         LEFT JOIN profiles p ON '@' || p.user_id || ':' || $1 = u.name \
+         ORDER BY u.name",
+    )
+    .bind::<Text, _>(config::server_name().as_str())
+    .load::<UserRow>(src)
+    .await?;
+
+    let mut user_ids = Vec::with_capacity(rows.len());
+    for row in rows {
+        let user_id = match OwnedUserId::try_from(row.name.as_str()) {
+            Ok(user_id) => user_id,
+            Err(e) => {
+                warn!(name = row.name, "skipping user with invalid id: {e}");
+                continue;
+            }
+        };
+        let created_at = UnixMillis(row.creation_ts.unwrap_or_default().max(0) as u64 * 1000);
+        data::user::create_user(&NewDbUser {
+            id: user_id.clone(),
+            ty: None,
+            is_admin: row.is_admin,
+            is_guest: row.is_guest,
+            is_local: true,
+            localpart: user_id.localpart().to_owned(),
+            server_name: user_id.server_name().to_owned(),
+            appservice_id: row.appservice_id,
+            created_at,
+        })
+        .await?;
+        if row.shadow_banned {
+            diesel::update(users::table.find(&user_id))
+                .set(users::shadow_banned.eq(true))
+                .execute(&mut connect().await?)
+                .await?;
+        }
+
+        if data::user::get_profile(&user_id, None).await?.is_none() {
+            data::user::create_profile(&NewDbProfile {
+                user_id: user_id.clone(),
+                room_id: None,
+                display_name: row.displayname,
+                avatar_url: row.avatar_url.map(Into::into),
+                blurhash: None,
+            })
+            .await?;
+        }
+
+        if let Some(hash) = row.password_hash.filter(|hash| !hash.is_empty())
+            && data::user::get_password_hash(&user_id).await.is_err()
+        {
+            data::user::set_password_hash(&user_id, &hash).await?;
+        }
+        // Deactivated accounts keep their ID reserved but have nothing else worth
+        // carrying over.
+        if row.deactivated {
+            data::user::deactivate(&user_id).await?;
+            continue;
+        }
+        user_ids.push(user_id);
+    }
+    Ok(user_ids)
+}
+
+/// Imports everything keyed by a single local user: devices and their sessions,
+/// E2EE keys, key backups, account data and push rules.
+async fn import_user_data(src: &mut AsyncPgConnection, user_id: &UserId) -> AppResult<()> {
+    import_devices(src, user_id).await?;
+    import_device_keys(src, user_id).await?;
+    import_cross_signing(src, user_id).await?;
+    import_key_backups(src, user_id).await?;
+    import_account_data(src, user_id).await?;
+    import_push_rules(src, user_id).await?;
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct DeviceRow {
+    #[diesel(sql_type = Text)]
+    device_id: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    display_name: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    last_seen: Option<i64>,
+    #[diesel(sql_type = Nullable<Text>)]
+    ip: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    user_agent: Option<String>,
+    #[diesel(sql_type = Bool)]
+    hidden: bool,
+}
+
+#[derive(QueryableByName)]
+struct AccessTokenRow {
+    #[diesel(sql_type = Text)]
+    device_id: String,
+    #[diesel(sql_type = Text)]
+    token: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    puppets_user_id: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    last_validated: Option<i64>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    valid_until_ms: Option<i64>,
+}
+
+async fn import_devices(src: &mut AsyncPgConnection, user_id: &UserId) -> AppResult<()> {
+    let devices = diesel::sql_query(
+        "SELECT device_id, display_name, last_seen, ip, user_agent, \
+                COALESCE(hidden, FALSE) AS hidden \
+         FROM devices WHERE user_id = $1",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<DeviceRow>(src)
+    .await?;
+    for device in devices {
+        let last_seen_at = device.last_seen.map(|ts| UnixMillis(ts.max(0) as u64));
+        diesel::insert_into(user_devices::table)
+            .values(NewDbUserDevice {
+                user_id: user_id.to_owned(),
+                device_id: device.device_id.into(),
+                display_name: device.display_name,
+                user_agent: device.user_agent,
+                is_hidden: device.hidden,
+                last_seen_ip: device.ip,
+                last_seen_at,
+                created_at: last_seen_at.unwrap_or_else(UnixMillis::now),
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut connect().await?)
+            .await?;
+    }
+
+    // Palpo keeps one access token per device while Synapse may hold several;
+    // the newest one is the session the client is still using.
+    let tokens = diesel::sql_query(
+        "SELECT DISTINCT ON (device_id) device_id, token, puppets_user_id, last_validated, \
+                valid_until_ms \
+         FROM access_tokens \
+         WHERE user_id = $1 AND device_id IS NOT NULL \
+         ORDER BY device_id, id DESC",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<AccessTokenRow>(src)
+    .await?;
+    for token in tokens {
+        diesel::insert_into(user_access_tokens::table)
+            .values(NewDbAccessToken {
+                user_id: user_id.to_owned(),
+                device_id: token.device_id.into(),
+                token: token.token,
+                puppets_user_id: token
+                    .puppets_user_id
+                    .and_then(|id| OwnedUserId::try_from(id).ok()),
+                last_validated: token.last_validated.map(|ts| UnixMillis(ts.max(0) as u64)),
+                refresh_token_id: None,
+                is_used: false,
+                expires_at: token.valid_until_ms.map(|ts| UnixMillis(ts.max(0) as u64)),
+                created_at: UnixMillis::now(),
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut connect().await?)
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct DeviceKeyRow {
+    #[diesel(sql_type = Text)]
+    device_id: String,
+    #[diesel(sql_type = BigInt)]
+    ts_added_ms: i64,
+    #[diesel(sql_type = Text)]
+    key_json: String,
+}
+
+#[derive(QueryableByName)]
+struct OneTimeKeyRow {
+    #[diesel(sql_type = Text)]
+    device_id: String,
+    #[diesel(sql_type = Text)]
+    algorithm: String,
+    #[diesel(sql_type = Text)]
+    key_id: String,
+    #[diesel(sql_type = BigInt)]
+    ts_added_ms: i64,
+    #[diesel(sql_type = Text)]
+    key_json: String,
+}
+
+#[derive(QueryableByName)]
+struct FallbackKeyRow {
+    #[diesel(sql_type = Text)]
+    device_id: String,
+    #[diesel(sql_type = Text)]
+    algorithm: String,
+    #[diesel(sql_type = Text)]
+    key_id: String,
+    #[diesel(sql_type = Text)]
+    key_json: String,
+    #[diesel(sql_type = Bool)]
+    used: bool,
+}
+
+async fn import_device_keys(src: &mut AsyncPgConnection, user_id: &UserId) -> AppResult<()> {
+    let device_keys = diesel::sql_query(
+        "SELECT device_id, ts_added_ms, key_json FROM e2e_device_keys_json WHERE user_id = $1",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<DeviceKeyRow>(src)
+    .await?;
+    for row in device_keys {
+        let key_data: JsonValue = serde_json::from_str(&row.key_json)?;
+        diesel::insert_into(e2e_device_keys::table)
+            .values(NewDbDeviceKey {
+                user_id: user_id.to_owned(),
+                device_id: row.device_id.into(),
+                stream_id: 0,
+                display_name: None,
+                key_data,
+                created_at: UnixMillis(row.ts_added_ms.max(0) as u64),
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut connect().await?)
+            .await?;
+    }
+
+    let one_time_keys = diesel::sql_query(
+        "SELECT device_id, algorithm, key_id, ts_added_ms, key_json \
+         FROM e2e_one_time_keys_json WHERE user_id = $1",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<OneTimeKeyRow>(src)
+    .await?;
+    for row in one_time_keys {
+        let Ok(key_id) = OwnedDeviceKeyId::try_from(format!("{}:{}", row.algorithm, row.key_id))
+        else {
+            warn!(%user_id, key_id = row.key_id, "skipping one-time key with invalid id");
+            continue;
+        };
+        diesel::insert_into(e2e_one_time_keys::table)
+            .values(NewDbOneTimeKey {
+                user_id: user_id.to_owned(),
+                device_id: row.device_id.into(),
+                algorithm: row.algorithm,
+                key_id,
+                key_data: serde_json::from_str(&row.key_json)?,
+                created_at: UnixMillis(row.ts_added_ms.max(0) as u64),
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut connect().await?)
+            .await?;
+    }
+
+    let fallback_keys = diesel::sql_query(
+        "SELECT device_id, algorithm, key_id, key_json, used \
+         FROM e2e_fallback_keys_json WHERE user_id = $1",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<FallbackKeyRow>(src)
+    .await?;
+    for row in fallback_keys {
+        let Ok(key_id) = OwnedDeviceKeyId::try_from(format!("{}:{}", row.algorithm, row.key_id))
+        else {
+            warn!(%user_id, key_id = row.key_id, "skipping fallback key with invalid id");
+            continue;
+        };
+        diesel::insert_into(e2e_fallback_keys::table)
+            .values(NewDbFallbackKey {
+                user_id: user_id.to_owned(),
+                device_id: row.device_id.into(),
+                algorithm: row.algorithm,
+                key_id,
+                key_data: serde_json::from_str(&row.key_json)?,
+                used_at: row.used.then(|| UnixMillis::now().get() as i64),
+                created_at: UnixMillis::now(),
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut connect().await?)
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct CrossSigningKeyRow {
+    #[diesel(sql_type = Text)]
+    keytype: String,
+    #[diesel(sql_type = Text)]
+    keydata: String,
+}
+
+#[derive(QueryableByName)]
+struct CrossSigningSigRow {
+    #[diesel(sql_type = Text)]
+    key_id: String,
+    #[diesel(sql_type = Text)]
+    target_user_id: String,
+    #[diesel(sql_type = Text)]
+    target_device_id: String,
+    #[diesel(sql_type = Text)]
+    signature: String,
+}
+
+async fn import_cross_signing(src: &mut AsyncPgConnection, user_id: &UserId) -> AppResult<()> {
+    let keys = diesel::sql_query(
+        "SELECT DISTINCT ON (keytype) keytype, keydata \
+         FROM e2e_cross_signing_keys WHERE user_id = $1 \
+         ORDER BY keytype, stream_id DESC",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<CrossSigningKeyRow>(src)
+    .await?;
+    for row in keys {
+        let exists = diesel_exists!(
+            e2e_cross_signing_keys::table
+                .filter(e2e_cross_signing_keys::user_id.eq(user_id))
+                .filter(e2e_cross_signing_keys::key_type.eq(&row.keytype)),
+            &mut connect().await?
+        )?;
+        if exists {
+            continue;
+        }
+        diesel::insert_into(e2e_cross_signing_keys::table)
+            .values(NewDbCrossSigningKey {
+                user_id: user_id.to_owned(),
+                key_type: row.keytype,
+                key_data: serde_json::from_str(&row.keydata)?,
+            })
+            .execute(&mut connect().await?)
+            .await?;
+    }
+
+    let sigs = diesel::sql_query(
+        "SELECT key_id, target_user_id, target_device_id, signature \
+         FROM e2e_cross_signing_signatures WHERE user_id = $1",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<CrossSigningSigRow>(src)
+    .await?;
+    for row in sigs {
+        let (Ok(origin_key_id), Ok(target_user_id)) = (
+            OwnedDeviceKeyId::try_from(row.key_id),
+            OwnedUserId::try_from(row.target_user_id),
+        ) else {
+            continue;
+        };
+        diesel::insert_into(e2e_cross_signing_sigs::table)
+            .values(NewDbCrossSignature {
+                origin_user_id: user_id.to_owned(),
+                origin_key_id,
+                target_user_id,
+                target_device_id: row.target_device_id.into(),
+                signature: row.signature,
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut connect().await?)
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct BackupVersionRow {
+    #[diesel(sql_type = BigInt)]
+    version: i64,
+    #[diesel(sql_type = Text)]
+    algorithm: String,
+    #[diesel(sql_type = Text)]
+    auth_data: String,
+}
+
+#[derive(QueryableByName)]
+struct RoomKeyRow {
+    #[diesel(sql_type = Text)]
+    room_id: String,
+    #[diesel(sql_type = Text)]
+    session_id: String,
+    #[diesel(sql_type = BigInt)]
+    version: i64,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    first_message_index: Option<i64>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    forwarded_count: Option<i64>,
+    #[diesel(sql_type = Bool)]
+    is_verified: bool,
+    #[diesel(sql_type = Text)]
+    session_data: String,
+}
+
+async fn import_key_backups(src: &mut AsyncPgConnection, user_id: &UserId) -> AppResult<()> {
+    let versions = diesel::sql_query(
+        "SELECT version, algorithm, auth_data FROM e2e_room_keys_versions \
+         WHERE user_id = $1 AND deleted = 0",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<BackupVersionRow>(src)
+    .await?;
+    for row in versions {
+        // Synapse stores the bare algorithm name next to `auth_data`, while Palpo
+        // keeps the whole `BackupAlgorithm` object in `algorithm`.
+        let auth_data: JsonValue = serde_json::from_str(&row.auth_data)?;
+        let algorithm = json!({ "algorithm": row.algorithm, "auth_data": auth_data });
+        diesel::insert_into(e2e_room_keys_versions::table)
+            .values(NewDbRoomKeysVersion {
+                user_id: user_id.to_owned(),
+                version: row.version,
+                algorithm,
+                auth_data,
+                created_at: UnixMillis::now(),
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut connect().await?)
+            .await?;
+    }
+
+    let keys = diesel::sql_query(
+        "SELECT room_id, session_id, version, first_message_index::bigint AS first_message_index, \
+                forwarded_count::bigint AS forwarded_count, \
+                COALESCE(is_verified, FALSE) AS is_verified, session_data \
+         FROM e2e_room_keys WHERE user_id = $1",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<RoomKeyRow>(src)
+    .await?;
+    for row in keys {
+        let Ok(room_id) = OwnedRoomId::try_from(row.room_id) else {
+            continue;
+        };
+        diesel::insert_into(e2e_room_keys::table)
+            .values(NewDbRoomKey {
+                user_id: user_id.to_owned(),
+                room_id,
+                session_id: row.session_id,
+                version: row.version,
+                first_message_index: row.first_message_index,
+                forwarded_count: row.forwarded_count,
+                is_verified: row.is_verified,
+                session_data: serde_json::from_str(&row.session_data)?,
+                created_at: UnixMillis::now(),
+            })
+            .on_conflict_do_nothing()
+            .execute(&mut connect().await?)
+            .await?;
+    }
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct AccountDataRow {
+    #[diesel(sql_type = Nullable<Text>)]
+    room_id: Option<String>,
+    #[diesel(sql_type = Text)]
+    account_data_type: String,
+    #[diesel(sql_type = Text)]
+    content: String,
+}
+
+async fn import_account_data(src: &mut AsyncPgConnection, user_id: &UserId) -> AppResult<()> {
+    let rows = diesel::sql_query(
+        "SELECT NULL::text AS room_id, account_data_type, content \
+         FROM account_data WHERE user_id = $1 \
+         UNION ALL \
+         SELECT room_id, account_data_type, content \
+         FROM room_account_data WHERE user_id = $1",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<AccountDataRow>(src)
+    .await?;
+    for row in rows {
+        let room_id = match row.room_id.map(OwnedRoomId::try_from).transpose() {
+            Ok(room_id) => room_id,
+            Err(_) => continue,
+        };
+        let content: JsonValue = serde_json::from_str(&row.content)?;
+        // Synapse marks deleted account data with an empty object.
+        if content.as_object().is_some_and(|obj| obj.is_empty()) {
+            continue;
+        }
+        data::user::set_data(user_id, room_id, &row.account_data_type, content).await?;
+    }
+    Ok(())
+}
+
+#[derive(QueryableByName)]
+struct PushRuleRow {
+    #[diesel(sql_type = Text)]
+    rule_id: String,
+    #[diesel(sql_type = BigInt)]
+    priority_class: i64,
+    #[diesel(sql_type = Text)]
+    conditions: String,
+    #[diesel(sql_type = Text)]
+    actions: String,
+    #[diesel(sql_type = Nullable<Bool>)]
+    enabled: Option<bool>,
+}
+
+/// Rebuilds the user's `m.push_rules` account data from Synapse's `push_rules`
+/// and `push_rules_enable` tables, layered over the server-default ruleset.
+async fn import_push_rules(src: &mut AsyncPgConnection, user_id: &UserId) -> AppResult<()> {
+    let rows = diesel::sql_query(
+        "SELECT r.rule_id, r.priority_class::bigint AS priority_class, r.conditions, r.actions, \
+                e.enabled::int <> 0 AS enabled \
+         FROM push_rules r \
+         LEFT JOIN push_rules_enable e ON e.user_name = r.user_name AND e.rule_id = r.rule_id \
+         WHERE r.user_name = $1 \
+         ORDER BY r.priority_class DESC, r.priority DESC",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<PushRuleRow>(src)
+    .await?;
+    let enables = diesel::sql_query(
+        "SELECT rule_id, -1::bigint AS priority_class, '[]' AS conditions, '[]' AS actions, \
+                enabled::int <> 0 AS enabled \
+         FROM push_rules_enable WHERE user_name = $1",
+    )
+    .bind::<Text, _>(user_id.as_str())
+    .load::<PushRuleRow>(src)
+    .await?;
+    if rows.is_empty() && enables.is_empty() {
+        return Ok(());
+    }
+
+    let mut ruleset = serde_json::to_value(Ruleset::server_default(user_id))?;
+    let mut custom: HashMap<&str, Vec<JsonValue>> = HashMap::new();
+    for row in &rows {
+        let Some((kind, rule_id)) = split_rule_id(&row.rule_id) else {
+            continue;
+        };
+        let actions: JsonValue = serde_json::from_str(&row.actions)?;
+        // Synapse stores action overrides for server-default rules as rows with a
+        // priority class of -1.
+        if row.priority_class < 0 || rule_id.starts_with('.') {
+            if let Some(rule) = find_rule(&mut ruleset, kind, rule_id) {
+                rule["actions"] = actions;
+            }
+            continue;
+        }
+        let conditions: JsonValue = serde_json::from_str(&row.conditions)?;
+        let mut rule = json!({
+            "rule_id": rule_id,
+            "default": false,
+            "enabled": row.enabled.unwrap_or(true),
+            "actions": actions,
+        });
+        match kind {
+            "override" | "underride" => rule["conditions"] = conditions,
+            "content" => {
+                if let Some(pattern) = conditions.get(0).and_then(|c| c.get("pattern")) {
+                    rule["pattern"] = pattern.clone();
+                }
+            }
+            "room" | "sender" => {}
+            _ => continue,
+        }
+        custom.entry(kind).or_default().push(rule);
+    }
+    for (kind, rules) in custom {
+        let Some(list) = ruleset.get_mut(kind).and_then(JsonValue::as_array_mut) else {
+            continue;
+        };
+        // User rules take precedence over server defaults, except `.m.rule.master`.
+        let at = list
+            .iter()
+            .take_while(|rule| rule["rule_id"] == ".m.rule.master")
+            .count();
+        list.splice(at..at, rules);
+    }
+    for row in &enables {
+        if let Some((kind, rule_id)) = split_rule_id(&row.rule_id)
+            && let Some(rule) = find_rule(&mut ruleset, kind, rule_id)
+        {
+            rule["enabled"] = row.enabled.unwrap_or(true).into();
+        }
+    }
+
+    let ruleset: Ruleset = serde_json::from_value(ruleset)?;
+    data::user::set_data(
+        user_id,
+        None,
+        &GlobalAccountDataEventType::PushRules.to_string(),
+        serde_json::to_value(PushRulesEventContent::new(ruleset))?,
+    )
+    .await?;
+    Ok(())
+}
+
+/// Splits Synapse's `global/<kind>/<rule_id>` notation.
+fn split_rule_id(rule_id: &str) -> Option<(&str, &str)> {
+    let mut parts = rule_id.splitn(3, '/');
+    let _scope = parts.next()?;
+    Some((parts.next()?, parts.next()?))
+}
+
+fn find_rule<'a>(
+    ruleset: &'a mut JsonValue,
+    kind: &str,
+    rule_id: &str,
+) -> Option<&'a mut JsonValue> {
+    ruleset
+        .get_mut(kind)?
+        .as_array_mut()?
+        .iter_mut()
+        .find(|rule| rule["rule_id"] == rule_id)
+}
+
+#[derive(QueryableByName)]
+struct MediaRow {
+    #[diesel(sql_type = Text)]
+    origin: String,
+    #[diesel(sql_type = Text)]
+    media_id: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    media_type: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    media_length: Option<i64>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    created_ts: Option<i64>,
+    #[diesel(sql_type = Nullable<Text>)]
+    upload_name: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    user_id: Option<String>,
+    #[diesel(sql_type = Nullable<Text>)]
+    filesystem_id: Option<String>,
+}
+
+async fn import_media(src: &mut AsyncPgConnection, media_store: Option<&Path>) -> AppResult<()> {
+    let server_name = config::server_name();
+    let mut last = (String::new(), String::new());
+    let mut imported = 0usize;
+    loop {
+        // URL preview downloads are a cache Palpo rebuilds on demand.
+        let rows = diesel::sql_query(
+            "SELECT * FROM ( \
+                SELECT $1::text AS origin, media_id, media_type, \
+                       media_length::bigint AS media_length, created_ts, upload_name, user_id, \
+                       NULL::text AS filesystem_id \
+                FROM local_media_repository WHERE url_cache IS NULL \
+                UNION ALL \
+                SELECT media_origin, media_id, media_type, media_length::bigint, created_ts, \
+                       upload_name, NULL::text, filesystem_id \
+                FROM remote_media_cache \
+             ) m \
+             WHERE (origin, media_id) > ($2, $3) \
+             ORDER BY origin, media_id LIMIT $4",
+        )
+        .bind::<Text, _>(server_name.as_str())
+        .bind::<Text, _>(&last.0)
+        .bind::<Text, _>(&last.1)
+        .bind::<BigInt, _>(BATCH_SIZE)
+        .load::<MediaRow>(src)
+        .await?;
+        let Some(row) = rows.last() else {
+            break;
+        };
+        last = (row.origin.clone(), row.media_id.clone());
+
+        for row in rows {
+            if let Err(e) = import_media_item(row, media_store).await {
+                warn!("failed to import media: {e}");
+            } else {
+                imported += 1;
+            }
+        }
+    }
+    info!("imported {imported} media items");
+    Ok(())
+}
+
+async fn import_media_item(row: MediaRow, media_store: Option<&Path>) -> AppResult<()> {
+    let origin_server = OwnedServerName::try_from(row.origin)?;
+    if data::media::get_metadata(&origin_server, &row.media_id)
+        .await?
+        .is_some()
+    {
+        return Ok(());
+    }
+
+    if let Some(media_store) = media_store {
+        let path = match &row.filesystem_id {
+            Some(fs_id) => media_store
+                .join("remote_content")
+                .join(origin_server.as_str())
+                .join(sharded_path(fs_id)),
+            None => media_store
+                .join("local_content")
+                .join(sharded_path(&row.media_id)),
+        };
+        match tokio::fs::read(&path).await {
+            Ok(content) => {
+                let key = media_storage_key(&origin_server, &row.media_id);
+                storage::write(&key, &content).await?;
+            }
+            // Remote media is only a cache, it will be fetched again when requested.
+            Err(e) if row.filesystem_id.is_some() => {
+                debug!("skipping missing remote media file {}: {e}", path.display());
+                return Ok(());
+            }
+            Err(e) => {
+                return Err(AppError::public(format!(
+                    "failed to read media file {}: {e}",
+                    path.display()
+                )));
+            }
+        }
+    }
+
+    let file_extension = row.upload_name.as_deref().map(utils::fs::get_file_ext);
+    data::media::insert_metadata(&NewDbMetadata {
+        media_id: row.media_id,
+        origin_server,
+        content_type: row.media_type,
+        disposition_type: None,
+        file_name: row.upload_name,
+        file_extension,
+        file_size: row.media_length.unwrap_or_default(),
+        file_hash: None,
+        created_by: row.user_id.and_then(|id| OwnedUserId::try_from(id).ok()),
+        created_at: UnixMillis(row.created_ts.unwrap_or_default().max(0) as u64),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Synapse shards media files as `ab/cd/efgh...`.
+fn sharded_path(id: &str) -> PathBuf {
+    if id.len() < 5 || !id.is_char_boundary(2) || !id.is_char_boundary(4) {
+        return PathBuf::from(id);
+    }
+    PathBuf::from(&id[..2]).join(&id[2..4]).join(&id[4..])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn sharded_path_splits_like_synapse() {
+        assert_eq!(
+            sharded_path("GerZNDnDZVjsOtardLuwfIBg"),
+            PathBuf::from("Ge/rZ/NDnDZVjsOtardLuwfIBg")
+        );
+        assert_eq!(sharded_path("abc"), PathBuf::from("abc"));
+    }
+
+    #[test]
+    fn split_rule_id_parses_scope_and_kind() {
+        assert_eq!(
+            split_rule_id("global/override/.m.rule.master"),
+            Some(("override", ".m.rule.master"))
+        );
+        assert_eq!(
+            split_rule_id("global/content/my/rule"),
+            Some(("content", "my/rule"))
+        );
+        assert_eq!(split_rule_id("global"), None);
+    }
+}
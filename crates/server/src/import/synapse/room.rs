@@ -0,0 +1,542 @@
+//! Rooms, their event graph and state.
+//!
+//! Events are copied in topological order so that each event's ancestors and the
+//! events referenced by its state are already present. Synapse keeps one state
+//! group per event describing the state *after* it; those groups are expanded
+//! and stored as Palpo state frames for every non-outlier event. The state
+//! before a state event is the group Synapse derived its group from.
+use std::borrow::Borrow;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use diesel::prelude::*;
+use diesel::sql_types::{BigInt, Bool, Nullable, Text};
+use diesel_async::{AsyncPgConnection, RunQueryDsl};
+
+use super::BATCH_SIZE;
+use crate::core::Seqnum;
+use crate::core::events::room::member::MembershipState;
+use crate::core::events::{StateEventType, TimelineEventType};
+use crate::core::identifiers::*;
+use crate::core::serde::CanonicalJsonObject;
+use crate::data::room::{DbEventData, NewDbEvent, NewDbEventEdge};
+use crate::event::ensure_event_sn;
+use crate::room::state::{self, CompressedEvent, CompressedState};
+use crate::room::timeline;
+use crate::{AppResult, MatrixError, RoomMutexGuard, SnPduEvent, membership, room};
+
+/// Maximum number of expanded state groups kept in memory per room.
+const STATE_GROUP_CACHE_SIZE: usize = 256;
+
+type StateIds = BTreeMap<(String, String), OwnedEventId>;
+
+#[derive(QueryableByName)]
+struct RoomRow {
+    #[diesel(sql_type = Text)]
+    room_id: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    room_version: Option<String>,
+    #[diesel(sql_type = Bool)]
+    is_public: bool,
+}
+
+pub(super) async fn import_rooms(src: &mut AsyncPgConnection) -> AppResult<()> {
+    let rooms = diesel::sql_query(
+        "SELECT room_id, room_version, COALESCE(is_public, FALSE) AS is_public \
+         FROM rooms ORDER BY room_id",
+    )
+    .load::<RoomRow>(src)
+    .await?;
+    let total = rooms.len();
+    for (index, row) in rooms.into_iter().enumerate() {
+        info!(
+            room_id = row.room_id,
+            "importing room {}/{total}",
+            index + 1
+        );
+        if let Err(e) = import_room(src, &row).await {
+            error!(room_id = row.room_id, "failed to import room: {e}");
+        }
+    }
+    import_aliases(src).await
+}
+
+#[derive(QueryableByName)]
+struct EventRow {
+    #[diesel(sql_type = Text)]
+    event_id: String,
+    #[diesel(sql_type = BigInt)]
+    topological_ordering: i64,
+    #[diesel(sql_type = BigInt)]
+    stream_ordering: i64,
+    #[diesel(sql_type = Bool)]
+    outlier: bool,
+    #[diesel(sql_type = Text)]
+    json: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    rejection_reason: Option<String>,
+    #[diesel(sql_type = Nullable<BigInt>)]
+    state_group: Option<i64>,
+}
+
+#[derive(QueryableByName)]
+struct StateRow {
+    #[diesel(sql_type = Text)]
+    #[diesel(column_name = "type")]
+    ty: String,
+    #[diesel(sql_type = Text)]
+    state_key: String,
+    #[diesel(sql_type = Text)]
+    event_id: String,
+}
+
+#[derive(QueryableByName)]
+struct PrevGroupRow {
+    #[diesel(sql_type = BigInt)]
+    prev_state_group: i64,
+}
+
+#[derive(QueryableByName)]
+struct EventIdRow {
+    #[diesel(sql_type = Text)]
+    event_id: String,
+}
+
+async fn import_room(src: &mut AsyncPgConnection, row: &RoomRow) -> AppResult<()> {
+    let room_id = OwnedRoomId::try_from(row.room_id.as_str())?;
+    let room_version = RoomVersionId::try_from(row.room_version.as_deref().unwrap_or("1"))?;
+    room::ensure_room(&room_id, &room_version).await?;
+    let state_lock = room::lock_state(&room_id).await;
+
+    let mut import = RoomImport {
+        room_id: room_id.clone(),
+        room_version,
+        event_sns: HashMap::new(),
+        event_groups: HashMap::new(),
+        field_ids: HashMap::new(),
+        groups: HashMap::new(),
+        redactions: Vec::new(),
+    };
+
+    let mut last = (i64::MIN, i64::MIN);
+    loop {
+        let rows = diesel::sql_query(
+            "SELECT e.event_id, e.topological_ordering, e.stream_ordering, \
+                    COALESCE(e.outlier, FALSE) AS outlier, j.json, \
+                    r.reason AS rejection_reason, g.state_group \
+             FROM events e \
+             JOIN event_json j ON j.event_id = e.event_id \
+             LEFT JOIN rejections r ON r.event_id = e.event_id \
+             LEFT JOIN event_to_state_groups g ON g.event_id = e.event_id \
+             WHERE e.room_id = $1 AND (e.topological_ordering, e.stream_ordering) > ($2, $3) \
+             ORDER BY e.topological_ordering, e.stream_ordering LIMIT $4",
+        )
+        .bind::<Text, _>(room_id.as_str())
+        .bind::<BigInt, _>(last.0)
+        .bind::<BigInt, _>(last.1)
+        .bind::<BigInt, _>(BATCH_SIZE)
+        .load::<EventRow>(src)
+        .await?;
+        let Some(row) = rows.last() else {
+            break;
+        };
+        last = (row.topological_ordering, row.stream_ordering);
+
+        for row in rows {
+            let event_id = row.event_id.clone();
+            if let Err(e) = import.import_event(src, row).await {
+                warn!(%event_id, "failed to import event: {e}");
+            }
+        }
+    }
+
+    import.finish(src, &state_lock, row.is_public).await
+}
+
+struct RoomImport {
+    room_id: OwnedRoomId,
+    room_version: RoomVersionId,
+    event_sns: HashMap<OwnedEventId, Seqnum>,
+    /// State group after each event imported with state.
+    event_groups: HashMap<OwnedEventId, i64>,
+    field_ids: HashMap<(String, String), i64>,
+    groups: HashMap<i64, Arc<StateIds>>,
+    redactions: Vec<SnPduEvent>,
+}
+
+impl RoomImport {
+    async fn import_event(&mut self, src: &mut AsyncPgConnection, row: EventRow) -> AppResult<()> {
+        let room_id = &self.room_id.clone();
+        let event_id = OwnedEventId::try_from(row.event_id)?;
+        let json: CanonicalJsonObject = serde_json::from_str(&row.json)?;
+        let (event_sn, _event_guard) = ensure_event_sn(room_id, &event_id).await?;
+        self.event_sns.insert(event_id.clone(), event_sn);
+
+        // Events that never got a state group were persisted as outliers.
+        let is_outlier = row.outlier || row.state_group.is_none();
+        let is_rejected = row.rejection_reason.is_some();
+        let mut new_event = NewDbEvent::from_canonical_json_with_room_id(
+            &event_id, event_sn, &json, false, room_id,
+        )?;
+        new_event.is_outlier = is_outlier;
+        new_event.is_rejected = is_rejected;
+        new_event.rejection_reason = row.rejection_reason;
+        new_event.save().await?;
+        DbEventData {
+            event_id: event_id.clone(),
+            event_sn,
+            room_id: room_id.clone(),
+            internal_metadata: None,
+            json_data: serde_json::to_value(&json)?,
+            format_version: None,
+        }
+        .save()
+        .await?;
+
+        let Some(state_group) = row.state_group.filter(|_| !is_outlier && !is_rejected) else {
+            return Ok(());
+        };
+        let pdu = SnPduEvent::from_canonical_object(
+            room_id,
+            &event_id,
+            event_sn,
+            json.clone(),
+            false,
+            false,
+            false,
+        )?;
+
+        for prev_id in &pdu.prev_events {
+            NewDbEventEdge {
+                room_id: room_id.clone(),
+                event_id: event_id.clone(),
+                event_sn,
+                event_depth: pdu.depth as i64,
+                prev_id: prev_id.clone(),
+            }
+            .save()
+            .await?;
+        }
+
+        let state_after = self.group_state(src, state_group).await?;
+        let frame_after = self.compress(&state_after).await?;
+        let frame_before = if let Some(state_key) = &pdu.state_key {
+            let key = (pdu.event_ty.to_string(), state_key.clone());
+            let candidates = self.before_candidates(src, state_group, &pdu).await?;
+            let candidates = candidates.iter().map(|state| &**state).collect::<Vec<_>>();
+            self.compress(&state_before_event(&state_after, &key, &candidates))
+                .await?
+        } else {
+            frame_after.clone()
+        };
+        self.event_groups.insert(event_id.clone(), state_group);
+        state::set_event_state_before(&event_id, room_id, frame_before).await?;
+        let frame_id = state::set_event_state_after(&event_id, room_id, frame_after).await?;
+        state::set_room_state(room_id, frame_id).await?;
+        state::update_backward_extremities(&pdu).await?;
+
+        timeline::save_relations(&pdu).await?;
+        crate::event::search::save_pdu(&pdu, &json).await?;
+
+        match pdu.event_ty {
+            TimelineEventType::RoomMember => {
+                #[derive(serde::Deserialize)]
+                struct ExtractMembership {
+                    membership: MembershipState,
+                }
+                if let Some(state_key) = &pdu.state_key
+                    && let Ok(user_id) = UserId::parse(state_key)
+                    && let Ok(content) = pdu.get_content::<ExtractMembership>()
+                {
+                    membership::update_membership(
+                        &event_id,
+                        event_sn,
+                        room_id,
+                        &user_id,
+                        content.membership,
+                        &pdu.sender,
+                        None,
+                    )
+                    .await?;
+                }
+            }
+            TimelineEventType::RoomRedaction => self.redactions.push(pdu),
+            _ => {}
+        }
+        Ok(())
+    }
+
+    /// Applies redactions, the final room state and extremities once every event
+    /// of the room has been stored.
+    async fn finish(
+        mut self,
+        src: &mut AsyncPgConnection,
+        state_lock: &RoomMutexGuard,
+        is_public: bool,
+    ) -> AppResult<()> {
+        let room_id = &self.room_id.clone();
+        // Synapse applies redactions when serving events; Palpo prunes the stored
+        // JSON, so do that now that the redacted events exist.
+        for redaction in &self.redactions {
+            if let Some(redacts) = redaction.redacts_id(&self.room_version) {
+                timeline::redact_pdu(&redacts, redaction).await?;
+            }
+        }
+
+        let current = diesel::sql_query(
+            "SELECT type, state_key, event_id FROM current_state_events WHERE room_id = $1",
+        )
+        .bind::<Text, _>(room_id.as_str())
+        .load::<StateRow>(src)
+        .await?;
+        let current = self.compress(&to_state_ids(current)).await?;
+        if current.is_empty() {
+            return Err(
+                MatrixError::not_found("room has no current state, nothing to import").into(),
+            );
+        }
+        let frame_id = state::save_state_frame(room_id, current.clone()).await?;
+        state::force_state(room_id, frame_id, current, Arc::new(CompressedState::new())).await?;
+
+        let extremities =
+            diesel::sql_query("SELECT event_id FROM event_forward_extremities WHERE room_id = $1")
+                .bind::<Text, _>(room_id.as_str())
+                .load::<EventIdRow>(src)
+                .await?
+                .into_iter()
+                .filter_map(|row| OwnedEventId::try_from(row.event_id).ok())
+                .collect::<Vec<_>>();
+        state::set_forward_extremities(room_id, extremities.iter().map(Borrow::borrow), state_lock)
+            .await?;
+
+        room::directory::set_public(room_id, is_public).await?;
+        Ok(())
+    }
+
+    /// Expands a Synapse state group by walking its delta chain, the same way
+    /// Synapse's own `_get_state_groups_from_groups` does.
+    async fn group_state(
+        &mut self,
+        src: &mut AsyncPgConnection,
+        state_group: i64,
+    ) -> AppResult<Arc<StateIds>> {
+        if let Some(state) = self.groups.get(&state_group) {
+            return Ok(state.clone());
+        }
+        let rows = diesel::sql_query(
+            "WITH RECURSIVE sgs(state_group) AS ( \
+                VALUES ($1::bigint) \
+                UNION ALL \
+                SELECT prev_state_group FROM state_group_edges e, sgs s \
+                WHERE s.state_group = e.state_group \
+             ) \
+             SELECT DISTINCT ON (type, state_key) type, state_key, event_id \
+             FROM state_groups_state \
+             WHERE state_group IN (SELECT state_group FROM sgs) \
+             ORDER BY type, state_key, state_group DESC",
+        )
+        .bind::<BigInt, _>(state_group)
+        .load::<StateRow>(src)
+        .await?;
+        let state = Arc::new(to_state_ids(rows));
+        if self.groups.len() >= STATE_GROUP_CACHE_SIZE {
+            self.groups.clear();
+        }
+        self.groups.insert(state_group, state.clone());
+        Ok(state)
+    }
+
+    /// Candidates for the state before a state event whose state after is
+    /// `state_group`: the group Synapse derived that group from, then the state
+    /// after the event's only prev event.
+    async fn before_candidates(
+        &mut self,
+        src: &mut AsyncPgConnection,
+        state_group: i64,
+        pdu: &SnPduEvent,
+    ) -> AppResult<Vec<Arc<StateIds>>> {
+        let mut candidates = Vec::new();
+        if let Some(prev_group) = self.prev_group(src, state_group).await? {
+            candidates.push(self.group_state(src, prev_group).await?);
+        }
+        if let [prev_id] = pdu.prev_events.as_slice()
+            && let Some(prev_group) = self.event_groups.get(prev_id).copied()
+        {
+            candidates.push(self.group_state(src, prev_group).await?);
+        }
+        Ok(candidates)
+    }
+
+    async fn prev_group(
+        &mut self,
+        src: &mut AsyncPgConnection,
+        state_group: i64,
+    ) -> AppResult<Option<i64>> {
+        let rows = diesel::sql_query(
+            "SELECT prev_state_group FROM state_group_edges WHERE state_group = $1 LIMIT 1",
+        )
+        .bind::<BigInt, _>(state_group)
+        .load::<PrevGroupRow>(src)
+        .await?;
+        Ok(rows.into_iter().next().map(|row| row.prev_state_group))
+    }
+
+    async fn compress(&mut self, state: &StateIds) -> AppResult<Arc<CompressedState>> {
+        let mut compressed = CompressedState::new();
+        for ((ty, state_key), event_id) in state {
+            let event_sn = match self.event_sns.get(event_id) {
+                Some(event_sn) => *event_sn,
+                None => match crate::event::get_event_sn(event_id).await {
+                    Ok(event_sn) => event_sn,
+                    Err(_) => {
+                        warn!(%event_id, "state event was not imported, leaving it out of state");
+                        continue;
+                    }
+                },
+            };
+            let field_id = match self.field_ids.get(&(ty.clone(), state_key.clone())) {
+                Some(field_id) => *field_id,
+                None => {
+                    let field_id =
+                        state::ensure_field_id(&StateEventType::from(ty.as_str()), state_key)
+                            .await?;
+                    self.field_ids
+                        .insert((ty.clone(), state_key.clone()), field_id);
+                    field_id
+                }
+            };
+            compressed.insert(CompressedEvent::new(field_id, event_sn));
+        }
+        Ok(Arc::new(compressed))
+    }
+}
+
+/// The state before a state event stored under `key`, given the state after it.
+///
+/// That is the first of `candidates` that only differs from `state_after` by the
+/// event's own entry. Groups rewritten by a state compressor don't qualify, and
+/// without any candidate the entry is dropped, as for a new state key.
+fn state_before_event(
+    state_after: &StateIds,
+    key: &(String, String),
+    candidates: &[&StateIds],
+) -> StateIds {
+    let others = |state: &StateIds| {
+        state
+            .iter()
+            .filter(|(state_key, _)| *state_key != key)
+            .map(|(state_key, event_id)| (state_key.clone(), event_id.clone()))
+            .collect::<StateIds>()
+    };
+    let expected = others(state_after);
+    candidates
+        .iter()
+        .find(|candidate| others(candidate) == expected)
+        .map(|candidate| (*candidate).clone())
+        .unwrap_or(expected)
+}
+
+fn to_state_ids(rows: Vec<StateRow>) -> StateIds {
+    rows.into_iter()
+        .filter_map(|row| {
+            let event_id = OwnedEventId::try_from(row.event_id).ok()?;
+            Some(((row.ty, row.state_key), event_id))
+        })
+        .collect()
+}
+
+#[derive(QueryableByName)]
+struct AliasRow {
+    #[diesel(sql_type = Text)]
+    room_alias: String,
+    #[diesel(sql_type = Text)]
+    room_id: String,
+    #[diesel(sql_type = Nullable<Text>)]
+    creator: Option<String>,
+}
+
+async fn import_aliases(src: &mut AsyncPgConnection) -> AppResult<()> {
+    let rows = diesel::sql_query("SELECT room_alias, room_id, creator FROM room_aliases")
+        .load::<AliasRow>(src)
+        .await?;
+    let server_user = crate::config::server_user_id();
+    for row in rows {
+        let (Ok(alias_id), Ok(room_id)) = (
+            OwnedRoomAliasId::try_from(row.room_alias),
+            OwnedRoomId::try_from(row.room_id),
+        ) else {
+            continue;
+        };
+        if room::alias::resolve_local_alias(&alias_id).await.is_ok() {
+            continue;
+        }
+        let creator = row
+            .creator
+            .and_then(|id| OwnedUserId::try_from(id).ok())
+            .unwrap_or_else(|| server_user.to_owned());
+        room::alias::set_alias(room_id, alias_id, creator).await?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(entries: &[(&str, &str, &str)]) -> StateIds {
+        entries
+            .iter()
+            .map(|(ty, state_key, event_id)| {
+                (
+                    (ty.to_string(), state_key.to_string()),
+                    OwnedEventId::try_from(*event_id).unwrap(),
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn state_before_a_change_has_the_replaced_event() {
+        let key = ("m.room.name".to_owned(), String::new());
+        let before = state(&[
+            ("m.room.create", "", "$create"),
+            ("m.room.name", "", "$old_name"),
+        ]);
+        let after = state(&[
+            ("m.room.create", "", "$create"),
+            ("m.room.name", "", "$new_name"),
+        ]);
+        assert_eq!(state_before_event(&after, &key, &[&before]), before);
+    }
+
+    #[test]
+    fn state_before_skips_groups_differing_elsewhere() {
+        let key = ("m.room.name".to_owned(), String::new());
+        let after = state(&[
+            ("m.room.create", "", "$create"),
+            ("m.room.topic", "", "$topic"),
+            ("m.room.name", "", "$new_name"),
+        ]);
+        // A compressed group missing the topic does not describe the state before.
+        let compressed = state(&[
+            ("m.room.create", "", "$create"),
+            ("m.room.name", "", "$old_name"),
+        ]);
+        let prev_event = state(&[
+            ("m.room.create", "", "$create"),
+            ("m.room.topic", "", "$topic"),
+            ("m.room.name", "", "$old_name"),
+        ]);
+        assert_eq!(
+            state_before_event(&after, &key, &[&compressed, &prev_event]),
+            prev_event
+        );
+        assert_eq!(
+            state_before_event(&after, &key, &[&compressed]),
+            state(&[
+                ("m.room.create", "", "$create"),
+                ("m.room.topic", "", "$topic")
+            ])
+        );
+    }
+}
@@ -29,6 +29,7 @@ pub mod directory;
 pub mod event;
 pub mod exts;
 pub mod federation;
+pub mod import;
 pub mod media;
 pub mod membership;
 pub mod room;
@@ -36,6 +37,7 @@ pub mod sending;
 pub mod server_key;
 pub mod state;
 pub mod storage;
+pub mod tls;
 pub mod transaction_id;
 pub mod uiaa;
 pub mod user;
@@ -63,13 +65,13 @@ pub use diesel::result::Error as DieselError;
 use dotenvy::dotenv;
 pub use error::AppError;
 use figment::providers::Env;
+use futures_util::FutureExt as _;
 pub use jsonwebtoken as jwt;
 pub use palpo_core as core;
 pub use palpo_data as data;
 pub use palpo_server_macros as macros;
 use salvo::catcher::Catcher;
 use salvo::compression::{Compression, CompressionLevel};
-use salvo::conn::rustls::{Keycert, RustlsConfig};
 use salvo::conn::tcp::DynTcpAcceptors;
 use salvo::cors::{AllowHeaders, AllowOrigin, Cors, CorsHandler};
 use salvo::http::Method;
@@ -77,7 +79,7 @@ use salvo::logging::Logger;
 use salvo::prelude::*;
 use tracing_futures::Instrument;
 
-use crate::config::ServerConfig;
+use crate::config::{AcmeChallenge, ServerConfig};
 
 pub type AppResult<T> = Result<T, crate::AppError>;
 pub type DieselResult<T> = Result<T, diesel::result::Error>;
@@ -125,6 +127,32 @@ fn cors_handler(allowed_origins: &[String]) -> CorsHandler {
         .into_handler()
 }
 
+fn build_service(conf: &ServerConfig, router: Router) -> Service {
+    let catcher = Catcher::default().hoop(hoops::catch_status_error);
+    let service = Service::new(router)
+        .catcher(catcher)
+        .hoop(hoops::forwarded_remote_addr)
+        .hoop(hoops::default_accept_json)
+        .hoop(Logger::new())
+        .hoop(cors_handler(&conf.allowed_origins))
+        .hoop(hoops::remove_json_utf8);
+    if conf.compression.is_enabled() {
+        let mut compression = Compression::new();
+        if conf.compression.enable_brotli {
+            compression = compression.enable_zstd(CompressionLevel::Fastest);
+        }
+        if conf.compression.enable_zstd {
+            compression = compression.enable_zstd(CompressionLevel::Fastest);
+        }
+        if conf.compression.enable_gzip {
+            compression = compression.enable_gzip(CompressionLevel::Fastest);
+        }
+        service.hoop(compression)
+    } else {
+        service
+    }
+}
+
 pub trait OptionalExtension<T> {
     fn optional(self) -> AppResult<Option<T>>;
 }
@@ -169,6 +197,14 @@ pub(crate) struct Args {
 
     #[arg(long, short, num_args(1), default_value_t = true)]
     pub(crate) server: bool,
+
+    /// Import a Synapse PostgreSQL database into the configured database and exit.
+    #[arg(long, value_name = "DATABASE_URL")]
+    pub(crate) import_synapse: Option<String>,
+
+    /// Synapse `media_store_path` to copy media files from during the import.
+    #[arg(long, value_name = "PATH", requires = "import_synapse")]
+    pub(crate) synapse_media_store: Option<PathBuf>,
 }
 
 const TOKIO_WORKER_STACK_SIZE: usize = 8 * 1024 * 1024;
@@ -209,10 +245,17 @@ async fn async_main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     crate::logging::init()?;
     crate::data::init(&conf.db.clone().into_data_db_config());
     crate::storage::init(&conf.storage).expect("Failed to initialize storage backend");
+    crate::signing_keys::refresh().await?;
+    crate::event::search::check_languages().await?;
     // Force-load appservice registrations during startup so database rows
     // are up-to-date with the configured registration directory.
     let _ = crate::appservices().await;
 
+    if let Some(database_url) = &args.import_synapse {
+        crate::import::synapse::run(database_url, args.synapse_media_store.as_deref()).await?;
+        return Ok(());
+    }
+
     // Startup admin commands can enqueue federation, appservice, or push work,
     // so prepare the wakeup queue for those durable requests before executing
     // them. The network worker starts only after the no-server exit below.
@@ -261,38 +304,61 @@ async fn async_main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
         }
     });
 
-    let router = routing::root();
-    // let doc = OpenApi::new("palpo api", "0.0.1").merge_router(&router);
-    // let router = router
-    //     .unshift(doc.into_router("/api-doc/openapi.json"))
-    //     .unshift(
-    //         Scalar::new("/api-doc/openapi.json")
-    //             .title("Palpo - Scalar")
-    //             .into_router("/scalar"),
-    //     )
-    //     .unshift(SwaggerUi::new("/api-doc/openapi.json").into_router("/swagger-ui"));
-    let catcher = Catcher::default().hoop(hoops::catch_status_error);
-    let service = Service::new(router)
-        .catcher(catcher)
-        .hoop(hoops::default_accept_json)
-        .hoop(Logger::new())
-        .hoop(cors_handler(&conf.allowed_origins))
-        .hoop(hoops::remove_json_utf8);
-    let service = if conf.compression.is_enabled() {
-        let mut compression = Compression::new();
-        if conf.compression.enable_brotli {
-            compression = compression.enable_zstd(CompressionLevel::Fastest);
-        }
-        if conf.compression.enable_zstd {
-            compression = compression.enable_zstd(CompressionLevel::Fastest);
+    // Pick up signing keys rotated by other server instances.
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(60));
+        interval.tick().await;
+        loop {
+            interval.tick().await;
+            if let Err(e) = crate::signing_keys::refresh().await {
+                tracing::error!("failed to refresh signing keys: {e}");
+            }
         }
-        if conf.compression.enable_gzip {
-            compression = compression.enable_gzip(CompressionLevel::Fastest);
+    });
+
+    // Finish pruning the events of users erased before a restart.
+    if let Err(e) = crate::user::resume_erasures().await {
+        tracing::error!("failed to resume user erasures: {e}");
+    }
+
+    // Move users whose presence timed out to unavailable or offline.
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(std::time::Duration::from_secs(15));
+        loop {
+            interval.tick().await;
+            if let Err(e) = crate::user::presence::expire_presences().await {
+                tracing::error!("failed to expire presences: {e}");
+            }
         }
-        service.hoop(compression)
-    } else {
-        service
-    };
+    });
+
+    // Purge messages that outlived their room's retention policy.
+    if crate::config::get().retention.enabled {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_millis(
+                crate::config::get().retention.purge_interval,
+            ));
+            loop {
+                interval.tick().await;
+                if let Err(e) = crate::room::retention::purge_expired().await {
+                    tracing::error!("failed to purge expired events: {e}");
+                }
+            }
+        });
+    }
+
+    // Track replica lag so lag-tolerant reads only use replicas that are close
+    // enough to the primary.
+    if crate::data::has_replicas() {
+        tokio::spawn(async move {
+            let mut interval = tokio::time::interval(std::time::Duration::from_secs(1));
+            loop {
+                interval.tick().await;
+                crate::data::refresh_replica_lag().await;
+            }
+        });
+    }
+
     // In a clustered deployment, do NOT clear all presence on startup.
     // Other instances may still be serving users who are online.
     // Stale presence will be cleaned up by the presence timeout logic.
@@ -301,15 +367,48 @@ async fn async_main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
     salvo::http::request::set_global_secure_max_size(8 * 1024 * 1024);
     let conf = crate::config::get();
     let mut acceptors = vec![];
+    let mut servers = Vec::new();
+    let mut router = routing::root();
+    // The HTTP-01 challenge route. It answers for every ACME listener, tokens are
+    // looked up in the ACME client's shared challenge map.
+    let mut acme_challenges = Router::new();
+    let mut serves_acme_http = false;
     for listener_conf in &conf.listeners {
-        if let Some(tls_conf) = listener_conf.enabled_tls() {
+        // Rejected by the config check on other platforms.
+        #[cfg(unix)]
+        if let Some(path) = &listener_conf.unix_socket_path {
+            servers.push(serve_unix_socket(path, listener_conf.unix_socket_mode()?, conf).await?);
+            continue;
+        }
+        if listener_conf.acme {
+            tracing::info!("Listening on: {} with ACME TLS", listener_conf.address);
+            let acme = &conf.acme;
+            let mut listener = TcpListener::new(&listener_conf.address)
+                .acme()
+                .directory("palpo", &acme.directory_url)
+                .cache_path(&acme.cache_path)
+                .add_domain(conf.server_name.as_str());
+            for domain in &acme.domains {
+                listener = listener.add_domain(domain);
+            }
+            for contact in &acme.contacts {
+                listener = listener.add_contact(contact);
+            }
+            if acme.challenge == AcmeChallenge::Http01 {
+                if serves_acme_http {
+                    // Only switches this listener to HTTP-01, the route exists already.
+                    listener = listener.http01_challenge(&mut Router::new());
+                } else {
+                    listener = listener.http01_challenge(&mut acme_challenges);
+                    serves_acme_http = true;
+                }
+            }
+            acceptors.push(listener.bind().await.into_boxed());
+        } else if let Some(tls_conf) = listener_conf.enabled_tls() {
             tracing::info!("Listening on: {} with TLS", listener_conf.address);
+            let initial = crate::tls::load_config(tls_conf)?;
             let acceptor = TcpListener::new(&listener_conf.address)
-                .rustls(RustlsConfig::new(
-                    Keycert::new()
-                        .cert_from_path(&tls_conf.cert)?
-                        .key_from_path(&tls_conf.key)?,
-                ))
+                .rustls(crate::tls::config_stream(tls_conf, initial))
                 .bind()
                 .await
                 .into_boxed();
@@ -323,14 +422,77 @@ async fn async_main() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
             acceptors.push(acceptor);
         }
     }
+    // HTTP-01 challenges are answered over plain HTTP. A plain listener configured
+    // on that address answers them next to the API, otherwise a dedicated one
+    // answers only them and redirects everything else to HTTPS.
+    if serves_acme_http {
+        if conf
+            .listeners
+            .iter()
+            .any(|l| l.address == conf.acme.http_address && !l.acme && l.enabled_tls().is_none())
+        {
+            router.routers.insert(0, acme_challenges);
+        } else {
+            tracing::info!(
+                "Listening on: {} for ACME challenges",
+                conf.acme.http_address
+            );
+            let acceptor = TcpListener::new(&conf.acme.http_address).bind().await;
+            servers.push(
+                Server::new(acceptor)
+                    .serve(routing::acme_http_router(acme_challenges))
+                    .boxed(),
+            );
+        }
+    }
+    if !acceptors.is_empty() {
+        servers.push(
+            Server::new(DynTcpAcceptors::new(acceptors))
+                .serve(build_service(conf, router))
+                .boxed(),
+        );
+    }
 
-    Server::new(DynTcpAcceptors::new(acceptors))
-        .serve(service)
+    futures_util::future::join_all(servers)
         .instrument(tracing::info_span!("server.serve"))
         .await;
     Ok(())
 }
 
+/// Binds a UNIX socket listener, replacing a stale socket file left at `path`.
+#[cfg(unix)]
+async fn serve_unix_socket(
+    path: &std::path::Path,
+    mode: u32,
+    conf: &ServerConfig,
+) -> Result<futures_util::future::BoxFuture<'static, ()>, Box<dyn std::error::Error + Send + Sync>>
+{
+    use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+
+    use salvo::conn::UnixListener;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            tracing::info!("Removing stale UNIX socket {}", path.display());
+            std::fs::remove_file(path)?;
+        }
+        Ok(_) => {
+            return Err(format!("{} exists and is not a UNIX socket", path.display()).into());
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {}
+        Err(e) => return Err(e.into()),
+    }
+
+    tracing::info!("Listening on UNIX socket: {}", path.display());
+    let acceptor = UnixListener::new(path.to_owned())
+        .permissions(std::fs::Permissions::from_mode(mode))
+        .try_bind()
+        .await?;
+    Ok(Server::new(acceptor)
+        .serve(build_service(conf, routing::root()))
+        .boxed())
+}
+
 #[cfg(test)]
 mod tests {
     use salvo::http::header::{ACCESS_CONTROL_ALLOW_ORIGIN, ORIGIN};
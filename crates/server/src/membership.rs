@@ -332,5 +332,10 @@ pub async fn update_membership(
     if let Err(e) = crate::room::update_currents(room_id).await {
         error!("failed to update statistics for room {room_id}: {e}");
     }
+    if matches!(membership, MembershipState::Leave | MembershipState::Ban)
+        && let Err(e) = crate::user::untrack_remote_device_lists_after_leave(room_id, user_id).await
+    {
+        error!("failed to untrack remote device lists for room {room_id}: {e}");
+    }
     Ok(())
 }
@@ -50,7 +50,7 @@ pub async fn banned_room_check(
 
                 let all_joined_rooms: Vec<OwnedRoomId> = data::user::joined_rooms(user_id).await?;
 
-                crate::user::full_user_deactivate(user_id, &all_joined_rooms).await?;
+                crate::user::full_user_deactivate(user_id, &all_joined_rooms, false).await?;
             }
 
             return Err(
@@ -79,7 +79,7 @@ pub async fn banned_room_check(
             }
 
             let all_joined_rooms = data::user::joined_rooms(user_id).await?;
-            crate::user::full_user_deactivate(user_id, &all_joined_rooms).await?;
+            crate::user::full_user_deactivate(user_id, &all_joined_rooms, false).await?;
         }
 
         return Err(MatrixError::forbidden(
@@ -28,7 +28,7 @@ use crate::core::state::events::RoomCreateEvent;
 use crate::core::{Seqnum, UnixMillis};
 use crate::data::room::{DbRoomCurrent, NewDbRoom};
 use crate::data::schema::*;
-use crate::data::{connect, diesel_exists};
+use crate::data::{connect, connect_replica, diesel_exists};
 use crate::{
     APPSERVICE_IN_ROOM_CACHE, AppError, AppResult, IsRemoteOrLocal, RoomMutexGuard, RoomMutexMap,
     SnPduEvent, config, data, membership, room, utils,
@@ -42,6 +42,7 @@ pub mod directory;
 pub mod lazy_loading;
 pub mod pdu_metadata;
 pub mod receipt;
+pub mod retention;
 pub mod space;
 pub mod state;
 pub mod timeline;
@@ -375,6 +376,21 @@ pub async fn joined_servers(room_id: &RoomId) -> AppResult<Vec<OwnedServerName>>
         .await
         .map_err(Into::into)
 }
+/// The `limit` servers that have been joined to the room the longest.
+pub async fn oldest_joined_servers(
+    room_id: &RoomId,
+    limit: i64,
+) -> AppResult<Vec<OwnedServerName>> {
+    room_joined_servers::table
+        .filter(room_joined_servers::room_id.eq(room_id))
+        .filter(room_joined_servers::server_id.ne(config::server_name()))
+        .order_by(room_joined_servers::occur_sn.asc())
+        .select(room_joined_servers::server_id)
+        .limit(limit)
+        .load::<OwnedServerName>(&mut connect().await?)
+        .await
+        .map_err(Into::into)
+}
 pub async fn has_any_other_server(room_id: &RoomId, server: &ServerName) -> AppResult<bool> {
     let query = room_joined_servers::table
         .filter(room_joined_servers::room_id.eq(room_id))
@@ -521,14 +537,14 @@ pub async fn public_room_ids() -> AppResult<Vec<OwnedRoomId>> {
         .filter(rooms::is_public.eq(true))
         .select(rooms::id)
         .order_by(rooms::sn.desc())
-        .load(&mut connect().await?)
+        .load(&mut connect_replica().await?)
         .await
         .map_err(Into::into)
 }
 pub async fn all_room_ids() -> AppResult<Vec<OwnedRoomId>> {
     rooms::table
         .select(rooms::id)
-        .load(&mut connect().await?)
+        .load(&mut connect_replica().await?)
         .await
         .map_err(Into::into)
 }
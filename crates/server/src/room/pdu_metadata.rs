@@ -1,13 +1,17 @@
+use std::collections::HashMap;
+
 use palpo_core::Seqnum;
 use serde::Deserialize;
+use serde_json::value::to_raw_value;
 
 use crate::core::Direction;
 use crate::core::client::relation::RelationEventsResBody;
 use crate::core::events::TimelineEventType;
-use crate::core::events::relation::RelationType;
+use crate::core::events::relation::{BundledReference, ReferenceChunk, RelationType};
 use crate::core::identifiers::*;
-use crate::data::room::NewDbEventRelation;
-use crate::event::{BatchToken, SnPduEvent};
+use crate::core::serde::{JsonObject, JsonValue};
+use crate::data::room::{DbBundledRelation, NewDbEventRelation};
+use crate::event::{BatchToken, PduEvent, SnPduEvent};
 use crate::room::timeline;
 use crate::{AppResult, data};
 
@@ -148,6 +152,169 @@ pub async fn get_relations(
     Ok(pdus)
 }
 
+/// The latest valid `m.replace` edit of `pdu`, if it has been edited.
+///
+/// State events and redacted events can't be edited.
+pub async fn latest_replacement(pdu: &SnPduEvent) -> AppResult<Option<SnPduEvent>> {
+    if !is_bundling_target(pdu) {
+        return Ok(None);
+    }
+    let relations = data::room::bundled_relations(&[pdu.event_id.as_str()]).await?;
+    let Some(replace_id) = latest_replacement_id(pdu, relations.iter()) else {
+        return Ok(None);
+    };
+    Ok(timeline::get_pdu(replace_id).await.ok())
+}
+
+/// Adds the [bundled aggregations] of the latest edit and the references of each
+/// of `pdus` to its `unsigned.m.relations`, next to the thread summary stored by
+/// `room::thread::add_to_thread` whose `current_user_participated` is set for
+/// `user_id`. The relations, thread participations and edits of all of them are
+/// looked up at once.
+///
+/// [bundled aggregations]: https://spec.matrix.org/latest/client-server-api/#aggregations-of-child-events
+pub async fn bundle_aggregations<'a>(
+    pdus: impl IntoIterator<Item = &'a mut SnPduEvent>,
+    user_id: &UserId,
+) -> AppResult<()> {
+    let mut pdus = pdus
+        .into_iter()
+        .filter(|pdu| is_bundling_target(pdu))
+        .collect::<Vec<_>>();
+    let event_ids = pdus
+        .iter()
+        .map(|pdu| pdu.event_id.as_str())
+        .collect::<Vec<_>>();
+    let relations = data::room::bundled_relations(&event_ids).await?;
+    let mut relations_by_parent = HashMap::<&EventId, Vec<&DbBundledRelation>>::new();
+    for relation in &relations {
+        relations_by_parent
+            .entry(&relation.event_id)
+            .or_default()
+            .push(relation);
+    }
+
+    // Only thread roots have participants, so all the events can be looked up.
+    let participated = data::room::thread::participated_threads(&event_ids, user_id).await?;
+    let replace_ids = pdus
+        .iter()
+        .filter_map(|pdu| {
+            let relations = relations_by_parent.get(&*pdu.event_id)?;
+            latest_replacement_id(pdu, relations.iter().copied()).map(ToOwned::to_owned)
+        })
+        .collect::<Vec<_>>();
+    let mut replacements = timeline::get_pdus(&replace_ids).await?;
+
+    for pdu in &mut pdus {
+        let relations = relations_by_parent
+            .get(&*pdu.event_id)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        let mut bundled = pdu
+            .unsigned
+            .get("m.relations")
+            .and_then(|relations| serde_json::from_str::<JsonObject>(relations.get()).ok())
+            .unwrap_or_default();
+
+        if bundled.contains_key("m.thread") {
+            set_thread_participation(&mut bundled, participated.contains(&pdu.event_id));
+        }
+
+        if let Some(replace_id) = latest_replacement_id(pdu, relations.iter().copied())
+            && let Some(mut replacement) = replacements.remove(replace_id)
+        {
+            if replacement.sender != user_id {
+                replacement.remove_transaction_id()?;
+            }
+            bundled.insert(
+                "m.replace".to_owned(),
+                serde_json::to_value(replacement.to_message_like_event())?,
+            );
+        }
+
+        bundle_references(&mut bundled, relations)?;
+
+        if !bundled.is_empty() {
+            pdu.unsigned
+                .insert("m.relations".to_owned(), to_raw_value(&bundled)?);
+        }
+    }
+    Ok(())
+}
+
+/// Sets `current_user_participated` of the stored thread summary in `bundled`,
+/// which depends on the user the event is served to.
+fn set_thread_participation(bundled: &mut JsonObject, participated: bool) {
+    if let Some(JsonValue::Object(thread)) = bundled.get_mut("m.thread") {
+        thread.insert(
+            "current_user_participated".to_owned(),
+            JsonValue::Bool(participated),
+        );
+    }
+}
+
+/// Adds the `m.reference` children among `relations` to `bundled`.
+fn bundle_references(bundled: &mut JsonObject, relations: &[&DbBundledRelation]) -> AppResult<()> {
+    let references = relations
+        .iter()
+        .filter(|relation| relation.rel_type.as_deref() == Some("m.reference"))
+        .map(|relation| BundledReference::new(relation.child_id.clone()))
+        .collect::<Vec<_>>();
+    if !references.is_empty() {
+        bundled.insert(
+            "m.reference".to_owned(),
+            serde_json::to_value(ReferenceChunk::new(references))?,
+        );
+    }
+    Ok(())
+}
+
+fn is_bundling_target(pdu: &PduEvent) -> bool {
+    pdu.state_key.is_none() && !pdu.unsigned.contains_key("redacted_because")
+}
+
+/// The latest valid edit among the `relations` of `pdu`: sent by the original
+/// sender with the original event type, ordered by `origin_server_ts` with ties
+/// broken by event id.
+fn latest_replacement_id<'a>(
+    pdu: &PduEvent,
+    relations: impl Iterator<Item = &'a DbBundledRelation>,
+) -> Option<&'a EventId> {
+    let event_ty = pdu.event_ty.to_string();
+    relations
+        .filter(|relation| {
+            relation.event_id == pdu.event_id
+                && relation.rel_type.as_deref() == Some("m.replace")
+                && relation.sender_id.as_deref() == Some(&*pdu.sender)
+                && relation.ty == event_ty
+                && relation.state_key.is_none()
+        })
+        .max_by(|a, b| (a.origin_server_ts, &a.child_id).cmp(&(b.origin_server_ts, &b.child_id)))
+        .map(|relation| &*relation.child_id)
+}
+
+/// The content an edit replaces the original event's content with.
+///
+/// Returns `None` if `pdu` isn't an `m.replace` edit carrying `m.new_content`.
+pub fn replacement_new_content(pdu: &PduEvent) -> Option<(OwnedEventId, JsonObject)> {
+    #[derive(Deserialize)]
+    struct ExtractReplacement {
+        #[serde(rename = "m.relates_to")]
+        relates_to: ExtractReplacementRelation,
+        #[serde(rename = "m.new_content")]
+        new_content: JsonObject,
+    }
+    #[derive(Deserialize)]
+    struct ExtractReplacementRelation {
+        rel_type: String,
+        event_id: OwnedEventId,
+    }
+
+    let content = pdu.get_content::<ExtractReplacement>().ok()?;
+    (content.relates_to.rel_type == "m.replace")
+        .then_some((content.relates_to.event_id, content.new_content))
+}
+
 // #[tracing::instrument(skip(room_id, event_ids))]
 // pub fn mark_as_referenced(room_id: &RoomId, event_ids: &[OwnedEventId]) -> AppResult<()> {
 // for prev in event_ids {
@@ -174,3 +341,112 @@ pub async fn mark_event_soft_failed(event_id: &EventId) -> AppResult<()> {
 pub async fn is_event_soft_failed(event_id: &EventId) -> AppResult<bool> {
     Ok(data::room::is_event_soft_failed(event_id).await?)
 }
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::{
+        bundle_references, latest_replacement_id, replacement_new_content, set_thread_participation,
+    };
+    use crate::core::{UnixMillis, event_id, room_id, user_id};
+    use crate::data::room::DbBundledRelation;
+    use crate::event::PduEvent;
+
+    fn pdu(content: serde_json::Value) -> PduEvent {
+        PduEvent::from_json_value(
+            room_id!("!room:example.org"),
+            event_id!("$edit:example.org"),
+            json!({
+                "type": "m.room.message",
+                "sender": "@alice:example.org",
+                "origin_server_ts": 1,
+                "content": content,
+                "depth": 1,
+                "hashes": { "sha256": "" },
+            }),
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn edit_exposes_its_new_content() {
+        let edit = pdu(json!({
+            "msgtype": "m.text",
+            "body": "* fixed",
+            "m.new_content": { "msgtype": "m.text", "body": "fixed" },
+            "m.relates_to": { "rel_type": "m.replace", "event_id": "$original:example.org" },
+        }));
+
+        let (original_id, new_content) = replacement_new_content(&edit).unwrap();
+        assert_eq!(original_id, "$original:example.org");
+        assert_eq!(new_content["body"], "fixed");
+    }
+
+    #[test]
+    fn non_edits_have_no_new_content() {
+        let reference = pdu(json!({
+            "body": "see above",
+            "m.new_content": { "body": "ignored" },
+            "m.relates_to": { "rel_type": "m.reference", "event_id": "$original:example.org" },
+        }));
+        assert!(replacement_new_content(&reference).is_none());
+
+        let plain = pdu(json!({ "msgtype": "m.text", "body": "hello" }));
+        assert!(replacement_new_content(&plain).is_none());
+    }
+
+    fn relation(child: &str, rel_type: &str, sender: &str, ts: u64) -> DbBundledRelation {
+        DbBundledRelation {
+            event_id: event_id!("$edit:example.org").to_owned(),
+            child_id: child.try_into().unwrap(),
+            child_sn: 1,
+            rel_type: Some(rel_type.to_owned()),
+            sender_id: Some(sender.try_into().unwrap()),
+            ty: "m.room.message".to_owned(),
+            state_key: None,
+            origin_server_ts: UnixMillis(ts),
+        }
+    }
+
+    #[test]
+    fn latest_edit_is_the_newest_by_the_original_sender() {
+        let original = pdu(json!({ "msgtype": "m.text", "body": "hello" }));
+        let alice = user_id!("@alice:example.org").as_str();
+        let relations = [
+            relation("$first:example.org", "m.replace", alice, 10),
+            relation("$latest:example.org", "m.replace", alice, 20),
+            relation(
+                "$forged:example.org",
+                "m.replace",
+                "@mallory:example.org",
+                30,
+            ),
+            relation("$reference:example.org", "m.reference", alice, 40),
+        ];
+
+        let latest = latest_replacement_id(&original, relations.iter()).unwrap();
+        assert_eq!(latest, "$latest:example.org");
+        assert!(latest_replacement_id(&original, relations[2..].iter()).is_none());
+    }
+
+    #[test]
+    fn thread_root_without_other_relations_keeps_its_summary() {
+        let mut bundled = json!({
+            "m.thread": {
+                "latest_event": { "type": "m.room.message", "content": { "body": "reply" } },
+                "count": 2,
+            },
+        })
+        .as_object()
+        .unwrap()
+        .clone();
+
+        bundle_references(&mut bundled, &[]).unwrap();
+        set_thread_participation(&mut bundled, false);
+
+        assert_eq!(bundled.keys().collect::<Vec<_>>(), ["m.thread"]);
+        assert_eq!(bundled["m.thread"]["count"], 2);
+        assert_eq!(bundled["m.thread"]["current_user_participated"], false);
+    }
+}
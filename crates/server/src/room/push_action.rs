@@ -3,6 +3,7 @@ use diesel_async::RunQueryDsl;
 
 use crate::AppResult;
 use crate::core::Seqnum;
+use crate::core::events::receipt::ReceiptThread;
 use crate::core::identifiers::*;
 use crate::data::connect;
 use crate::data::room::NewDbEventPushAction;
@@ -34,10 +35,11 @@ pub async fn increment_notification_counts(
                     .filter(event_push_summaries::room_id.eq(&room_id))
                     .filter(event_push_summaries::thread_id.eq(thread_id)),
             )
-            .set(
+            .set((
                 event_push_summaries::notification_count
                     .eq(event_push_summaries::notification_count + 1),
-            )
+                event_push_summaries::unread_count.eq(event_push_summaries::unread_count + 1),
+            ))
             .execute(&mut connect().await?)
             .await?
         } else {
@@ -47,10 +49,11 @@ pub async fn increment_notification_counts(
                     .filter(event_push_summaries::room_id.eq(&room_id))
                     .filter(event_push_summaries::thread_id.is_null()),
             )
-            .set(
+            .set((
                 event_push_summaries::notification_count
                     .eq(event_push_summaries::notification_count + 1),
-            )
+                event_push_summaries::unread_count.eq(event_push_summaries::unread_count + 1),
+            ))
             .execute(&mut connect().await?)
             .await?
         };
@@ -143,7 +146,9 @@ pub async fn upsert_push_action(
         stream_ordering,
         notify,
         highlight,
-        unread: false,
+        // Counted as unread alongside `notification_count` in
+        // `increment_notification_counts`, so `refresh_notify_summary` agrees.
+        unread: notify,
         thread_id,
     })
     .await?;
@@ -151,31 +156,32 @@ pub async fn upsert_push_action(
     Ok(())
 }
 
+/// Clear the push actions a read receipt at `event_sn` covers: every action for an
+/// unthreaded receipt, otherwise only those of the receipt's thread.
 pub async fn remove_actions_until(
     user_id: &UserId,
     room_id: &RoomId,
     event_sn: Seqnum,
-    thread_id: Option<&EventId>,
+    thread: &ReceiptThread,
 ) -> AppResult<()> {
-    if let Some(thread_id) = thread_id {
-        diesel::delete(
-            event_push_actions::table
-                .filter(event_push_actions::user_id.eq(user_id))
-                .filter(event_push_actions::room_id.eq(room_id))
-                .filter(event_push_actions::thread_id.eq(thread_id))
-                .filter(event_push_actions::event_sn.le(event_sn)),
-        )
-        .execute(&mut connect().await?)
-        .await?;
-    } else {
-        diesel::delete(
-            event_push_actions::table
-                .filter(event_push_actions::user_id.eq(user_id))
-                .filter(event_push_actions::room_id.eq(room_id))
-                .filter(event_push_actions::event_sn.le(event_sn)),
-        )
-        .execute(&mut connect().await?)
-        .await?;
+    let query = event_push_actions::table
+        .filter(event_push_actions::user_id.eq(user_id))
+        .filter(event_push_actions::room_id.eq(room_id))
+        .filter(event_push_actions::event_sn.le(event_sn));
+    match thread {
+        ReceiptThread::Thread(thread_id) => {
+            diesel::delete(query.filter(event_push_actions::thread_id.eq(thread_id)))
+                .execute(&mut connect().await?)
+                .await?;
+        }
+        ReceiptThread::Main => {
+            diesel::delete(query.filter(event_push_actions::thread_id.is_null()))
+                .execute(&mut connect().await?)
+                .await?;
+        }
+        _ => {
+            diesel::delete(query).execute(&mut connect().await?).await?;
+        }
     }
     Ok(())
 }
@@ -2,7 +2,8 @@ use std::collections::BTreeMap;
 
 use crate::core::UnixMillis;
 use crate::core::events::receipt::{
-    Receipt, ReceiptContent, ReceiptData, ReceiptEvent, ReceiptEventContent, ReceiptMap, Receipts,
+    Receipt, ReceiptContent, ReceiptData, ReceiptEvent, ReceiptEventContent, ReceiptMap,
+    ReceiptThread, ReceiptType, Receipts,
 };
 use crate::core::federation::transaction::Edu;
 use crate::core::identifiers::*;
@@ -18,6 +19,7 @@ pub async fn update_read(
     event: &ReceiptEvent,
     broadcast: bool,
 ) -> AppResult<()> {
+    let mut sent_receipt = None;
     for (event_id, receipts) in event.content.clone() {
         let Ok(event_sn) = crate::event::get_event_sn(&event_id).await else {
             continue;
@@ -25,9 +27,12 @@ pub async fn update_read(
         for (receipt_ty, user_receipts) in receipts {
             if let Some(receipt) = user_receipts.get(user_id) {
                 let thread_id = match &receipt.thread {
-                    crate::core::events::receipt::ReceiptThread::Thread(id) => Some(id.clone()),
+                    ReceiptThread::Thread(id) => Some(id.clone()),
                     _ => None,
                 };
+                if receipt_ty == ReceiptType::Read {
+                    sent_receipt = Some(receipt.clone());
+                }
                 let receipt_at = receipt.ts.unwrap_or_else(UnixMillis::now);
                 let receipt = DbReceipt {
                     sn: next_sn().await?,
@@ -47,52 +52,46 @@ pub async fn update_read(
         }
     }
 
-    let receipts = BTreeMap::from_iter([(
-        room_id.to_owned(),
-        ReceiptMap::new(BTreeMap::from_iter([(
-            user_id.to_owned(),
-            ReceiptData::new(
-                Receipt::new(UnixMillis::now()),
-                event.content.0.keys().cloned().collect(),
-            ),
-        )])),
-    )]);
-    let edu = Edu::Receipt(ReceiptContent::new(receipts));
     if broadcast {
+        let mut receipt = sent_receipt.unwrap_or_else(|| Receipt::new(UnixMillis::now()));
+        receipt.ts.get_or_insert_with(UnixMillis::now);
+        let receipts = BTreeMap::from_iter([(
+            room_id.to_owned(),
+            ReceiptMap::new(BTreeMap::from_iter([(
+                user_id.to_owned(),
+                ReceiptData::new(receipt, event.content.0.keys().cloned().collect()),
+            )])),
+        )]);
+        let edu = Edu::Receipt(ReceiptContent::new(receipts));
         sending::send_edu_room(room_id, &edu).await?;
     }
     Ok(())
 }
 
-/// Gets the latest private read receipt from the user in the room
+/// Gets the latest private read receipts from the user in the room, one per thread.
 pub async fn last_private_read(
     user_id: &UserId,
     room_id: &RoomId,
 ) -> AppResult<ReceiptEventContent> {
-    let Some(event_id) = data::room::receipt::last_private_read_event_id(user_id, room_id).await?
-    else {
+    let rows = data::room::receipt::last_private_reads(user_id, room_id).await?;
+    if rows.is_empty() {
         return Err(MatrixError::not_found("No private read receipt.").into());
-    };
-
-    // let room_sn = crate::room::get_room_sn(room_id)
-    //     .map_err(|e| MatrixError::bad_state(format!("room does not exist in database for
-    // {room_id}: {e}")))?;
-
-    // let pdu = timeline::get_pdu(&event_id)?;
+    }
 
-    let user_id: OwnedUserId = user_id.to_owned();
-    let content: BTreeMap<OwnedEventId, Receipts> = BTreeMap::from_iter([(
-        event_id,
-        BTreeMap::from_iter([(
-            crate::core::events::receipt::ReceiptType::ReadPrivate,
-            BTreeMap::from_iter([(
-                user_id,
-                crate::core::events::receipt::Receipt {
-                    ts: None, // TODO: start storing the timestamp so we can return one
-                    thread: crate::core::events::receipt::ReceiptThread::Unthreaded,
-                },
-            )]),
-        )]),
-    )]);
+    let mut content: BTreeMap<OwnedEventId, Receipts> = BTreeMap::new();
+    for row in rows {
+        let mut receipt =
+            serde_json::from_value::<Receipt>(row.json_data).unwrap_or_else(|_| Receipt {
+                ts: None,
+                thread: row.thread_id.map(ReceiptThread::Thread).unwrap_or_default(),
+            });
+        receipt.ts.get_or_insert(row.receipt_at);
+        content
+            .entry(row.event_id)
+            .or_default()
+            .entry(ReceiptType::ReadPrivate)
+            .or_default()
+            .insert(user_id.to_owned(), receipt);
+    }
     Ok(ReceiptEventContent(content))
 }
@@ -0,0 +1,108 @@
+//! Message retention policies (`m.room.retention`).
+//!
+//! A room's policy is read from its current state and clamped by the
+//! server-wide limits in [`RetentionConfig`](crate::config::RetentionConfig).
+//! The purge job removes expired non-state events, prunes the content of
+//! expired forward extremities, which the room still builds on, and deletes
+//! local media that is no longer referenced by any remaining event.
+
+use std::collections::HashSet;
+
+use serde::Deserialize;
+
+use crate::core::UnixMillis;
+use crate::core::events::StateEventType;
+use crate::core::identifiers::*;
+use crate::{AppResult, config, data, room};
+
+/// Content of an `m.room.retention` state event.
+#[derive(Deserialize, Clone, Debug, Default)]
+pub struct RoomRetentionEventContent {
+    /// The minimum lifetime of events in the room, in milliseconds.
+    #[serde(default)]
+    pub min_lifetime: Option<u64>,
+
+    /// The maximum lifetime of events in the room, in milliseconds.
+    #[serde(default)]
+    pub max_lifetime: Option<u64>,
+}
+
+/// Get the `m.room.retention` policy of a room, if any.
+pub async fn get_policy(room_id: &RoomId) -> Option<RoomRetentionEventContent> {
+    room::get_state_content::<RoomRetentionEventContent>(
+        room_id,
+        &StateEventType::from("m.room.retention"),
+        "",
+        None,
+    )
+    .await
+    .ok()
+}
+
+/// Get the effective maximum lifetime of events in a room, or `None` when
+/// events are kept forever.
+pub async fn max_lifetime(room_id: &RoomId) -> Option<u64> {
+    let policy = get_policy(room_id).await.and_then(|p| p.max_lifetime);
+    config::get().retention.effective_max_lifetime(policy)
+}
+
+/// Purge expired events in every room, then delete the local media they
+/// referenced which no remaining event or profile references.
+pub async fn purge_expired() -> AppResult<()> {
+    let mut mxcs = HashSet::new();
+    for room_id in room::all_room_ids().await? {
+        match purge_room(&room_id).await {
+            Ok(room_mxcs) => mxcs.extend(room_mxcs),
+            Err(e) => tracing::warn!(%room_id, "failed to purge expired events: {e}"),
+        }
+    }
+    delete_unreferenced_media(mxcs).await
+}
+
+/// Purge expired events in a room, returning the local media URIs found in
+/// the purged or pruned events.
+async fn purge_room(room_id: &RoomId) -> AppResult<HashSet<String>> {
+    let mut mxcs = HashSet::new();
+    let Some(max_lifetime) = max_lifetime(room_id).await else {
+        return Ok(mxcs);
+    };
+    let before_ts = UnixMillis(UnixMillis::now().get().saturating_sub(max_lifetime));
+    let purged = data::room::timeline::purge_expired_events(room_id, before_ts).await?;
+    if !purged.is_empty() {
+        tracing::info!(%room_id, count = purged.len(), "purged expired events");
+    }
+    for event in &purged {
+        if let Some(content) = event.get("content") {
+            data::media::collect_mxc_uris(content, &mut mxcs);
+        }
+    }
+
+    // Forward extremities stay for the room DAG, with their content pruned.
+    let extremities = data::room::timeline::expired_forward_extremities(room_id, before_ts).await?;
+    for (event_id, event) in &extremities {
+        if let Some(content) = event.get("content") {
+            data::media::collect_mxc_uris(content, &mut mxcs);
+        }
+        room::timeline::prune_pdu(event_id).await?;
+    }
+
+    let local_prefix = format!("mxc://{}/", config::server_name());
+    mxcs.retain(|mxc| mxc.starts_with(&local_prefix));
+    Ok(mxcs)
+}
+
+/// Delete the given local media unless still referenced.
+async fn delete_unreferenced_media(mxcs: HashSet<String>) -> AppResult<()> {
+    let mxcs: Vec<String> = mxcs.into_iter().collect();
+    let referenced = data::media::referenced_mxcs(&mxcs).await?;
+    for mxc in mxcs {
+        if referenced.contains(&mxc) {
+            continue;
+        }
+        let mxc = OwnedMxcUri::from(mxc);
+        if let (Ok(server_name), Ok(media_id)) = (mxc.server_name(), mxc.media_id()) {
+            crate::media::delete_media(server_name, media_id).await?;
+        }
+    }
+    Ok(())
+}
@@ -98,6 +98,10 @@ pub async fn force_state(
                     None,
                 )
                 .await?;
+                if let Err(e) = crate::user::directory::update_member_profile(&pdu, &user_id).await
+                {
+                    error!("failed to update user directory for {user_id}: {e}");
+                }
             }
             TimelineEventType::SpaceChild => {
                 let mut cache = room::space::ROOM_ID_SPACE_CHUNK_CACHE.lock().unwrap();
@@ -109,6 +113,9 @@ pub async fn force_state(
     }
 
     set_room_state(room_id, frame_id).await?;
+    if let Err(e) = crate::user::directory::update_public_room(room_id).await {
+        error!("failed to update user directory for room {room_id}: {e}");
+    }
     if let Err(e) = room::update_currents(room_id).await {
         error!("failed to update statistics for room {room_id}: {e}");
     }
@@ -133,49 +140,73 @@ pub async fn set_event_state_before(
     event_id: &EventId,
     room_id: &RoomId,
     state_ids_compressed: Arc<CompressedState>,
+) -> AppResult<i64> {
+    let frame_id = save_state_frame(room_id, state_ids_compressed).await?;
+    update_before_frame_id(event_id, frame_id).await?;
+    Ok(frame_id)
+}
+
+/// Associates the event with a frame holding exactly the given state after it.
+///
+/// Used when the resulting state is already known, e.g. when importing events
+/// whose state was resolved by another server implementation.
+#[tracing::instrument(skip(state_ids_compressed), level = "debug")]
+pub async fn set_event_state_after(
+    event_id: &EventId,
+    room_id: &RoomId,
+    state_ids_compressed: Arc<CompressedState>,
+) -> AppResult<i64> {
+    let frame_id = save_state_frame(room_id, state_ids_compressed).await?;
+    update_frame_id(event_id, frame_id).await?;
+    Ok(frame_id)
+}
+
+/// Returns the frame for the given full state, storing it as a delta against the
+/// current room frame when it does not exist yet.
+pub async fn save_state_frame(
+    room_id: &RoomId,
+    state_ids_compressed: Arc<CompressedState>,
 ) -> AppResult<i64> {
     let prev_frame_id = get_room_frame_id(room_id, None).await.ok();
     let hash_data = utils::hash_keys(state_ids_compressed.iter().map(|s| &s[..]));
     if let Ok(frame_id) = get_frame_id(room_id, &hash_data).await {
-        update_before_frame_id(event_id, frame_id).await?;
-        Ok(frame_id)
+        return Ok(frame_id);
+    }
+
+    let frame_id = ensure_frame(room_id, hash_data).await?;
+    let states_parents = if let Some(prev_frame_id) = prev_frame_id {
+        load_frame_info(prev_frame_id).await?
     } else {
-        let frame_id = ensure_frame(room_id, hash_data).await?;
-        let states_parents = if let Some(prev_frame_id) = prev_frame_id {
-            load_frame_info(prev_frame_id).await?
-        } else {
-            Vec::new()
-        };
+        Vec::new()
+    };
 
-        let (appended, disposed) = if let Some(parent_state_info) = states_parents.last() {
-            let appended: CompressedState = state_ids_compressed
-                .difference(&parent_state_info.full_state)
-                .copied()
-                .collect();
+    let (appended, disposed) = if let Some(parent_state_info) = states_parents.last() {
+        let appended: CompressedState = state_ids_compressed
+            .difference(&parent_state_info.full_state)
+            .copied()
+            .collect();
 
-            let disposed: CompressedState = parent_state_info
-                .full_state
-                .difference(&state_ids_compressed)
-                .copied()
-                .collect();
+        let disposed: CompressedState = parent_state_info
+            .full_state
+            .difference(&state_ids_compressed)
+            .copied()
+            .collect();
 
-            (Arc::new(appended), Arc::new(disposed))
-        } else {
-            (state_ids_compressed, Arc::new(CompressedState::new()))
-        };
+        (Arc::new(appended), Arc::new(disposed))
+    } else {
+        (state_ids_compressed, Arc::new(CompressedState::new()))
+    };
 
-        update_before_frame_id(event_id, frame_id).await?;
-        calc_and_save_state_delta(
-            room_id,
-            frame_id,
-            appended,
-            disposed,
-            1_000_000,
-            states_parents,
-        )
-        .await?;
-        Ok(frame_id)
-    }
+    calc_and_save_state_delta(
+        room_id,
+        frame_id,
+        appended,
+        disposed,
+        1_000_000,
+        states_parents,
+    )
+    .await?;
+    Ok(frame_id)
 }
 
 /// Generates a new StateHash and associates it with the incoming event.
@@ -1,26 +1,63 @@
+use std::collections::BTreeMap;
+
+use serde::{Deserialize, Serialize};
 use serde_json::json;
 
-use crate::core::client::room::IncludeThreads;
-use crate::core::events::relation::BundledThread;
+use crate::core::client::room::{
+    IncludeThreads, ThreadSubscription, ThreadSubscriptionsChangesResBody, ThreadUnsubscription,
+};
+use crate::core::error::ErrorKind;
+use crate::core::events::AnyMessageLikeEvent;
 use crate::core::identifiers::*;
-use crate::core::serde::CanonicalJsonValue;
+use crate::core::serde::{CanonicalJsonValue, RawJson};
+use crate::core::{MatrixError, Seqnum};
 use crate::data::room::DbThread;
-use crate::room::timeline;
-use crate::{AppResult, SnPduEvent, data};
+use crate::data::room::thread::DbThreadSubscription;
+use crate::room::{state, timeline};
+use crate::{AppResult, IsRemoteOrLocal, SnPduEvent, data};
+
+/// The thread summary stored in the root's `unsigned.m.relations`.
+///
+/// `current_user_participated` of a [`BundledThread`] depends on the user the
+/// root is served to, it is added by `room::pdu_metadata::bundle_aggregations`.
+///
+/// [`BundledThread`]: crate::core::events::relation::BundledThread
+#[derive(Deserialize, Serialize)]
+struct ThreadSummary {
+    latest_event: RawJson<AnyMessageLikeEvent>,
+    count: u64,
+}
 
+/// List the thread roots of a room visible to `user_id`, newest activity first.
+///
+/// The returned token is the `last_sn` bound to continue from, if the page was full.
 pub async fn get_threads(
+    user_id: &UserId,
     room_id: &RoomId,
-    _include: &IncludeThreads,
+    include: &IncludeThreads,
     limit: i64,
     from_token: Option<i64>,
-) -> AppResult<(Vec<(OwnedEventId, SnPduEvent)>, Option<i64>)> {
-    let items = data::room::list_threads(room_id, from_token, limit).await?;
-    let next_token = items.last().map(|(_, sn)| *sn - 1);
+) -> AppResult<(Vec<SnPduEvent>, Option<i64>)> {
+    let participant = matches!(include, IncludeThreads::Participated).then_some(user_id);
+    let items = data::room::list_threads(room_id, participant, from_token, limit).await?;
+    let next_token = if items.len() as i64 >= limit {
+        items.last().map(|(_, sn)| *sn - 1)
+    } else {
+        None
+    };
 
     let mut events = Vec::with_capacity(items.len());
     for (event_id, _) in items {
-        if let Ok(pdu) = timeline::get_pdu(&event_id).await {
-            events.push((event_id, pdu));
+        if !state::user_can_see_event(user_id, &event_id)
+            .await
+            .unwrap_or(false)
+        {
+            continue;
+        }
+        if let Ok(mut pdu) = timeline::get_pdu(&event_id).await {
+            pdu.add_age().ok();
+            pdu.add_bundled_aggregations(user_id).await?;
+            events.push(pdu);
         }
     }
     Ok((events, next_token))
@@ -33,43 +70,23 @@ pub async fn add_to_thread(thread_id: &EventId, pdu: &SnPduEvent) -> AppResult<(
         .entry("unsigned".to_owned())
         .or_insert_with(|| CanonicalJsonValue::Object(Default::default()))
     {
-        if let Some(mut relations) = unsigned
+        let count = unsigned
             .get("m.relations")
             .and_then(|r| r.as_object())
             .and_then(|r| r.get("m.thread"))
-            .and_then(|relations| {
-                serde_json::from_value::<BundledThread>(relations.clone().into()).ok()
-            })
-        {
-            // Thread already existed
-            relations.count += 1;
-            relations.latest_event = pdu.to_message_like_event();
-
-            let content = serde_json::to_value(relations).expect("to_value always works");
-
-            unsigned.insert(
-                "m.relations".to_owned(),
-                json!({ "m.thread": content })
-                    .try_into()
-                    .expect("thread is valid json"),
-            );
-        } else {
-            // New thread
-            let relations = BundledThread {
-                latest_event: pdu.to_message_like_event(),
-                count: 1,
-                current_user_participated: true,
-            };
-
-            let content = serde_json::to_value(relations).expect("to_value always works");
-
-            unsigned.insert(
-                "m.relations".to_owned(),
-                json!({ "m.thread": content })
-                    .try_into()
-                    .expect("thread is valid json"),
-            );
-        }
+            .and_then(|thread| serde_json::from_value::<ThreadSummary>(thread.clone().into()).ok())
+            .map_or(1, |thread| thread.count + 1);
+        let summary = ThreadSummary {
+            latest_event: pdu.to_message_like_event(),
+            count,
+        };
+        let content = serde_json::to_value(summary).expect("to_value always works");
+        unsigned.insert(
+            "m.relations".to_owned(),
+            json!({ "m.thread": content })
+                .try_into()
+                .expect("thread is valid json"),
+        );
 
         timeline::replace_pdu(thread_id, &root_pdu_json).await?;
     }
@@ -84,5 +101,162 @@ pub async fn add_to_thread(thread_id: &EventId, pdu: &SnPduEvent) -> AppResult<(
         last_sn: pdu.event_sn,
     })
     .await?;
+
+    for sender in [&root_pdu.sender, &pdu.sender] {
+        data::room::thread::add_participant(&root_pdu.room_id, thread_id, sender).await?;
+        // Local participants follow the thread unless they already chose otherwise.
+        if sender.is_local()
+            && data::room::thread::get_subscription(sender, thread_id)
+                .await?
+                .is_none()
+        {
+            data::room::thread::subscribe(sender, &root_pdu.room_id, thread_id, true).await?;
+        }
+    }
     Ok(())
 }
+
+/// Look up a thread root the user is allowed to see.
+async fn visible_thread_root(
+    user_id: &UserId,
+    room_id: &RoomId,
+    thread_root: &EventId,
+) -> AppResult<SnPduEvent> {
+    let pdu = timeline::get_pdu(thread_root)
+        .await
+        .map_err(|_| MatrixError::not_found("Thread root not found."))?;
+    if pdu.room_id != room_id
+        || !state::user_can_see_event(user_id, thread_root)
+            .await
+            .unwrap_or(false)
+    {
+        return Err(MatrixError::not_found("Thread root not found.").into());
+    }
+    Ok(pdu)
+}
+
+/// Get the user's subscription to a thread, `None` if not subscribed.
+pub async fn get_subscription(
+    user_id: &UserId,
+    room_id: &RoomId,
+    thread_root: &EventId,
+) -> AppResult<Option<DbThreadSubscription>> {
+    visible_thread_root(user_id, room_id, thread_root).await?;
+    Ok(data::room::thread::get_subscription(user_id, thread_root)
+        .await?
+        .filter(|s| s.subscribed))
+}
+
+/// Subscribe the user to a thread as described in [MSC4306].
+///
+/// An automatic subscription names the thread event that caused it. It is rejected when
+/// that event is not in the thread or when the user unsubscribed after seeing it, and it
+/// never downgrades an existing manual subscription.
+///
+/// [MSC4306]: https://github.com/matrix-org/matrix-spec-proposals/pull/4306
+pub async fn subscribe(
+    user_id: &UserId,
+    room_id: &RoomId,
+    thread_root: &EventId,
+    automatic: Option<&EventId>,
+) -> AppResult<()> {
+    visible_thread_root(user_id, room_id, thread_root).await?;
+    let existing = data::room::thread::get_subscription(user_id, thread_root).await?;
+
+    let Some(automatic) = automatic else {
+        data::room::thread::subscribe(user_id, room_id, thread_root, false).await?;
+        return Ok(());
+    };
+
+    let event_sn = automatic_event_sn(thread_root, automatic).await?;
+    match existing {
+        Some(existing) if existing.subscribed => Ok(()),
+        Some(DbThreadSubscription {
+            unsubscribed_at_sn: Some(unsubscribed_at_sn),
+            ..
+        }) if event_sn <= unsubscribed_at_sn => Err(MatrixError::new(
+            ErrorKind::ConflictingUnsubscription,
+            "The user unsubscribed from the thread after this event.",
+        )
+        .into()),
+        _ => {
+            data::room::thread::subscribe(user_id, room_id, thread_root, true).await?;
+            Ok(())
+        }
+    }
+}
+
+/// Stream position of the event causing an automatic subscription, which must be the
+/// thread root or one of its replies.
+async fn automatic_event_sn(thread_root: &EventId, event_id: &EventId) -> AppResult<Seqnum> {
+    let not_in_thread = || {
+        MatrixError::new(
+            ErrorKind::NotInThread,
+            "The event is not part of the thread.",
+        )
+    };
+    let event_sn = crate::event::get_event_sn(event_id)
+        .await
+        .map_err(|_| not_in_thread())?;
+    if event_id != thread_root
+        && data::room::event_thread_id(event_id).await?.as_deref() != Some(thread_root)
+    {
+        return Err(not_in_thread().into());
+    }
+    Ok(event_sn)
+}
+
+/// Unsubscribe the user from a thread, remembering its latest event so that older
+/// automatic subscriptions are refused.
+pub async fn unsubscribe(
+    user_id: &UserId,
+    room_id: &RoomId,
+    thread_root: &EventId,
+) -> AppResult<()> {
+    let root = visible_thread_root(user_id, room_id, thread_root).await?;
+    let last_sn = data::room::get_thread(thread_root)
+        .await?
+        .map(|thread| thread.last_sn)
+        .unwrap_or(root.event_sn);
+    data::room::thread::unsubscribe(user_id, thread_root, last_sn).await?;
+    Ok(())
+}
+
+/// Thread subscription changes of the user with `to < bump_stamp < from`, newest first.
+///
+/// `end` is set to the `from` of the next page when `limit` was reached.
+pub async fn subscription_changes(
+    user_id: &UserId,
+    from: Option<Seqnum>,
+    to: Option<Seqnum>,
+    limit: i64,
+) -> AppResult<ThreadSubscriptionsChangesResBody> {
+    let changes = data::room::thread::subscription_changes(user_id, from, to, limit).await?;
+    let end = if changes.len() as i64 >= limit {
+        changes.last().map(|c| c.occur_sn.to_string())
+    } else {
+        None
+    };
+
+    let mut subscribed: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+    let mut unsubscribed: BTreeMap<_, BTreeMap<_, _>> = BTreeMap::new();
+    for change in changes {
+        if change.subscribed {
+            subscribed.entry(change.room_id).or_default().insert(
+                change.thread_id,
+                ThreadSubscription::new(change.automatic, change.occur_sn),
+            );
+        } else {
+            unsubscribed
+                .entry(change.room_id)
+                .or_default()
+                .insert(change.thread_id, ThreadUnsubscription::new(change.occur_sn));
+        }
+    }
+
+    Ok(ThreadSubscriptionsChangesResBody {
+        subscribed,
+        unsubscribed,
+        end,
+    })
+}
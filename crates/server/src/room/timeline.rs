@@ -1,19 +1,23 @@
 use std::borrow::Borrow;
-use std::collections::{BTreeSet, HashSet};
+use std::collections::{BTreeSet, HashMap, HashSet};
 
 use diesel::prelude::*;
 use diesel_async::{AsyncConnection, RunQueryDsl};
 use serde::Deserialize;
+use serde_json::value::to_raw_value;
 
 use crate::core::Seqnum;
+use crate::core::events::relation::{Annotation, Reference};
 use crate::core::events::room::canonical_alias::RoomCanonicalAliasEventContent;
-use crate::core::events::room::encrypted::Relation;
+use crate::core::events::room::encrypted::{Relation, Replacement};
 use crate::core::events::room::member::MembershipState;
 use crate::core::events::{StateEventType, TimelineEventType};
 use crate::core::identifiers::*;
 use crate::core::presence::PresenceState;
 use crate::core::push::{Action, HighlightTweakValue, Tweak};
-use crate::core::serde::{CanonicalJsonObject, CanonicalJsonValue, JsonValue, to_canonical_object};
+use crate::core::serde::{
+    CanonicalJsonObject, CanonicalJsonValue, JsonObject, JsonValue, to_canonical_object,
+};
 use crate::core::state::Event;
 use crate::data::room::{DbEvent, DbEventData, NewDbEventEdge};
 use crate::data::schema::*;
@@ -143,6 +147,52 @@ pub async fn get_pdu(event_id: &EventId) -> AppResult<SnPduEvent> {
     })
 }
 
+/// The stored PDUs among `event_ids`, loaded at once.
+pub async fn get_pdus(event_ids: &[OwnedEventId]) -> AppResult<HashMap<OwnedEventId, SnPduEvent>> {
+    if event_ids.is_empty() {
+        return Ok(HashMap::new());
+    }
+    let mut conn = connect().await?;
+    let mut events = events::table
+        .filter(events::id.eq_any(event_ids))
+        .load::<DbEvent>(&mut conn)
+        .await?
+        .into_iter()
+        .map(|event| (event.id.clone(), event))
+        .collect::<HashMap<_, _>>();
+    let datas = event_datas::table
+        .filter(event_datas::event_id.eq_any(event_ids))
+        .select((
+            event_datas::event_id,
+            event_datas::event_sn,
+            event_datas::room_id,
+            event_datas::json_data,
+        ))
+        .load::<(OwnedEventId, Seqnum, OwnedRoomId, JsonValue)>(&mut conn)
+        .await?;
+
+    let mut pdus = HashMap::with_capacity(datas.len());
+    for (event_id, event_sn, room_id, json) in datas {
+        let Some(event) = events.remove(&event_id) else {
+            continue;
+        };
+        let mut pdu = PduEvent::from_json_value(&room_id, &event_id, json)
+            .map_err(|_e| AppError::internal("invalid pdu in db"))?;
+        pdu.rejection_reason = event.rejection_reason;
+        pdus.insert(
+            event_id,
+            SnPduEvent {
+                pdu,
+                event_sn,
+                is_outlier: event.is_outlier,
+                soft_failed: event.soft_failed,
+                is_backfill: event.stream_ordering < 0,
+            },
+        );
+    }
+    Ok(pdus)
+}
+
 pub async fn get_pdu_and_data(event_id: &EventId) -> AppResult<(SnPduEvent, CanonicalJsonObject)> {
     let event = events::table
         .filter(events::id.eq(event_id))
@@ -212,10 +262,12 @@ pub async fn has_pdu(event_id: &EventId) -> bool {
 /// Removes a pdu and creates a new one with the same id.
 #[tracing::instrument]
 pub async fn replace_pdu(event_id: &EventId, pdu_json: &CanonicalJsonObject) -> AppResult<()> {
+    let json_data = serde_json::to_value(pdu_json)?;
     diesel::update(event_datas::table.filter(event_datas::event_id.eq(event_id)))
-        .set(event_datas::json_data.eq(serde_json::to_value(pdu_json)?))
+        .set(event_datas::json_data.eq(&json_data))
         .execute(&mut connect().await?)
         .await?;
+    data::media::set_event_media_refs(event_id, &json_data).await?;
     // PDU_CACHE.lock().unwrap().remove(&(*pdu.event_id).to_owned());
 
     Ok(())
@@ -320,57 +372,25 @@ pub async fn append_pdu(
         .await?;
     state::update_backward_extremities(pdu).await?;
 
-    #[derive(Deserialize, Clone, Debug)]
-    struct ExtractEventId {
-        event_id: OwnedEventId,
-    }
-    #[derive(Deserialize, Clone, Debug)]
-    struct ExtractRelatesToEventId {
-        #[serde(rename = "m.relates_to")]
-        relates_to: ExtractEventId,
-    }
-    let mut relates_added = false;
-    if let Ok(content) = pdu.get_content::<ExtractRelatesTo>() {
-        let rel_type = content.relates_to.rel_type();
-        match content.relates_to {
-            Relation::Reply { in_reply_to } => {
-                // We need to do it again here, because replies don't have event_id as a top level
-                // field
-                super::pdu_metadata::add_relation(
-                    &pdu.room_id,
-                    &in_reply_to.event_id,
-                    &pdu.event_id,
-                    rel_type,
-                )
-                .await?;
-                relates_added = true;
-            }
-            Relation::Thread(thread) => {
-                super::pdu_metadata::add_relation(
-                    &pdu.room_id,
-                    &thread.event_id,
-                    &pdu.event_id,
-                    rel_type,
-                )
-                .await?;
-                relates_added = true;
-                // thread_id = Some(thread.event_id.clone());
-                super::thread::add_to_thread(&thread.event_id, pdu).await?;
+    save_relations(pdu).await?;
+
+    // Edits are matched against the content they put in place. `m.relates_to` is
+    // kept so that `.m.rule.suppress_edits` still applies to them.
+    let sync_pdu = match super::pdu_metadata::replacement_new_content(pdu) {
+        Some((_, mut new_content)) => {
+            let mut edited = pdu.pdu.clone();
+            if let Some(relates_to) = pdu
+                .get_content::<JsonObject>()
+                .ok()
+                .and_then(|mut content| content.remove("m.relates_to"))
+            {
+                new_content.insert("m.relates_to".to_owned(), relates_to);
             }
-            _ => {} // TODO: Aggregate other types
+            edited.content = to_raw_value(&new_content)?;
+            edited.to_sync_room_event()
         }
-    }
-    if !relates_added && let Ok(content) = pdu.get_content::<ExtractRelatesToEventId>() {
-        super::pdu_metadata::add_relation(
-            &pdu.room_id,
-            &content.relates_to.event_id,
-            &pdu.event_id,
-            None,
-        )
-        .await?;
-    }
-
-    let sync_pdu = pdu.to_sync_room_event();
+        None => pdu.to_sync_room_event(),
+    };
     let mut notifies = Vec::new();
     let mut highlights = Vec::new();
 
@@ -481,6 +501,11 @@ pub async fn append_pdu(
                     stripped_state,
                 )
                 .await?;
+                if let Err(e) =
+                    crate::user::directory::update_member_profile(pdu, &target_user_id).await
+                {
+                    error!("failed to update user directory for {target_user_id}: {e}");
+                }
 
                 // Invalidate appservice room cache when membership changes
                 // This ensures that when a bridge user joins a room, subsequent messages
@@ -526,6 +551,14 @@ pub async fn append_pdu(
                 }
             }
         }
+        TimelineEventType::RoomJoinRules => {
+            if let Err(e) = crate::user::directory::update_public_room(&pdu.room_id).await {
+                error!(
+                    "failed to update user directory for room {}: {e}",
+                    pdu.room_id
+                );
+            }
+        }
         TimelineEventType::RoomTombstone => {
             #[derive(Deserialize)]
             struct ExtractReplacementRoom {
@@ -585,13 +618,6 @@ pub async fn append_pdu(
         }
     }
 
-    // Update Relationships
-    #[derive(Deserialize, Clone, Debug)]
-    struct ExtractRelatesTo {
-        #[serde(rename = "m.relates_to")]
-        relates_to: Relation,
-    }
-
     crate::event::search::save_pdu(pdu, &pdu_json).await?;
 
     let frame_id = state::append_to_state(pdu).await?;
@@ -791,6 +817,74 @@ async fn check_pdu_for_admin_room(pdu: &PduEvent, sender: &UserId) -> AppResult<
     Ok(())
 }
 
+/// Records the relations (replies, threads, edits, ...) an event declares.
+pub async fn save_relations(pdu: &SnPduEvent) -> AppResult<()> {
+    #[derive(Deserialize, Clone, Debug)]
+    struct ExtractRelatesTo {
+        #[serde(rename = "m.relates_to")]
+        relates_to: Relation,
+    }
+    #[derive(Deserialize, Clone, Debug)]
+    struct ExtractEventId {
+        event_id: OwnedEventId,
+    }
+    #[derive(Deserialize, Clone, Debug)]
+    struct ExtractRelatesToEventId {
+        #[serde(rename = "m.relates_to")]
+        relates_to: ExtractEventId,
+    }
+    let mut relates_added = false;
+    if let Ok(content) = pdu.get_content::<ExtractRelatesTo>() {
+        let rel_type = content.relates_to.rel_type();
+        match content.relates_to {
+            Relation::Reply { in_reply_to } => {
+                // We need to do it again here, because replies don't have event_id as a top level
+                // field
+                super::pdu_metadata::add_relation(
+                    &pdu.room_id,
+                    &in_reply_to.event_id,
+                    &pdu.event_id,
+                    rel_type,
+                )
+                .await?;
+                relates_added = true;
+            }
+            Relation::Thread(thread) => {
+                super::pdu_metadata::add_relation(
+                    &pdu.room_id,
+                    &thread.event_id,
+                    &pdu.event_id,
+                    rel_type,
+                )
+                .await?;
+                relates_added = true;
+                // thread_id = Some(thread.event_id.clone());
+                super::thread::add_to_thread(&thread.event_id, pdu).await?;
+            }
+            Relation::Replacement(Replacement { event_id, .. })
+            | Relation::Reference(Reference { event_id, .. })
+            | Relation::Annotation(Annotation { event_id, .. }) => {
+                // Recorded with their rel_type so they can be bundled into the
+                // `m.relations` of the parent, see `SnPduEvent::add_bundled_aggregations`.
+                super::pdu_metadata::add_relation(&pdu.room_id, &event_id, &pdu.event_id, rel_type)
+                    .await?;
+                relates_added = true;
+            }
+            _ => {}
+        }
+    }
+    if !relates_added && let Ok(content) = pdu.get_content::<ExtractRelatesToEventId>() {
+        super::pdu_metadata::add_relation(
+            &pdu.room_id,
+            &content.relates_to.event_id,
+            &pdu.event_id,
+            None,
+        )
+        .await?;
+    }
+    Ok(())
+}
+
 /// Creates a new persisted data unit and adds it to a room.
 #[tracing::instrument(skip_all)]
 pub async fn build_and_append_pdu(
@@ -855,7 +949,27 @@ pub async fn redact_pdu(event_id: &EventId, reason: &PduEvent) -> AppResult<()>
         Err(e) => return Err(e),
     };
     pdu.redact(reason)?;
-    let redacted_json = serde_json::to_value(to_canonical_object(&pdu)?)?;
+    save_redacted_pdu(&pdu).await
+}
+
+/// Prunes the content of an event without a redaction event, for events of an
+/// erased user in rooms they left and can no longer send a redaction to, or
+/// expired events kept as forward extremities. Only the copy on this server is
+/// pruned.
+pub async fn prune_pdu(event_id: &EventId) -> AppResult<()> {
+    let mut pdu = match get_pdu(event_id).await {
+        Ok(pdu) => pdu,
+        Err(e) if e.is_not_found() => return Ok(()),
+        Err(e) => return Err(e),
+    };
+    pdu.prune_content()?;
+    pdu.unsigned.clear();
+    save_redacted_pdu(&pdu).await
+}
+
+async fn save_redacted_pdu(pdu: &SnPduEvent) -> AppResult<()> {
+    let event_id = &*pdu.event_id;
+    let redacted_json = serde_json::to_value(to_canonical_object(pdu)?)?;
     connect()
         .await?
         .transaction::<_, AppError, _>(async |conn| {
@@ -873,7 +987,9 @@ pub async fn redact_pdu(event_id: &EventId, reason: &PduEvent) -> AppResult<()>
 
             Ok(())
         })
-        .await
+        .await?;
+    data::media::set_event_media_refs(event_id, &redacted_json).await?;
+    Ok(())
 }
 
 pub async fn is_event_next_to_backward_gap(event: &PduEvent) -> AppResult<bool> {
@@ -886,12 +1002,25 @@ pub async fn is_event_next_to_backward_gap(event: &PduEvent) -> AppResult<bool>
 }
 
 pub async fn is_event_next_to_forward_gap(event: &PduEvent) -> AppResult<bool> {
-    let mut event_ids = event.prev_events.clone();
-    event_ids.push(event.event_id().to_owned());
+    // A forward extremity is one of the latest events in the room, so nothing is
+    // missing after it.
     let query = event_forward_extremities::table
         .filter(event_forward_extremities::room_id.eq(event.room_id()))
-        .filter(event_forward_extremities::event_id.eq_any(event_ids));
-    Ok(diesel_exists!(query, &mut connect().await?)?)
+        .filter(event_forward_extremities::event_id.eq(event.event_id()));
+    if diesel_exists!(query, &mut connect().await?)? {
+        return Ok(false);
+    }
+    // Otherwise the event borders a gap unless an accepted event builds on it.
+    let query = event_edges::table
+        .filter(event_edges::prev_id.eq(event.event_id()))
+        .filter(
+            event_edges::event_id.eq_any(
+                events::table
+                    .filter(events::is_rejected.eq(false))
+                    .select(events::id),
+            ),
+        );
+    Ok(!diesel_exists!(query, &mut connect().await?)?)
 }
 
 #[cfg(test)]
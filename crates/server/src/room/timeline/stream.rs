@@ -225,5 +225,8 @@ pub async fn load_pdus(
             break;
         }
     }
+    if let Some(user_id) = user_id {
+        crate::room::pdu_metadata::bundle_aggregations(list.values_mut(), user_id).await?;
+    }
     Ok(list)
 }
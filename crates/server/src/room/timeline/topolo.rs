@@ -5,8 +5,8 @@ use indexmap::IndexMap;
 use crate::core::client::filter::{RoomEventFilter, UrlFilter};
 use crate::core::identifiers::*;
 use crate::core::{Direction, Seqnum};
-use crate::data::connect;
 use crate::data::schema::*;
+use crate::data::{connect, connect_replica};
 use crate::event::BatchToken;
 use crate::{AppResult, SnPduEvent, utils};
 
@@ -26,6 +26,7 @@ pub async fn load_pdus_forward(
         limit,
         filter,
         Direction::Forward,
+        false,
     )
     .await
 }
@@ -45,6 +46,7 @@ pub async fn load_pdus_backward(
         limit,
         filter,
         Direction::Backward,
+        false,
     )
     .await
 }
@@ -52,6 +54,9 @@ pub async fn load_pdus_backward(
 /// Returns an iterator over all events and their tokens in a room that happened before the
 /// event with id `until` in reverse-chronological order.
 /// Skips events before user joined the room.
+///
+/// With `from_replica` the event scan may be served by a read replica, so only
+/// set it when the caller does not need to observe its own recent writes.
 #[tracing::instrument]
 pub async fn load_pdus(
     user_id: Option<&UserId>,
@@ -61,6 +66,7 @@ pub async fn load_pdus(
     limit: usize,
     filter: Option<&RoomEventFilter>,
     dir: Direction,
+    from_replica: bool,
 ) -> AppResult<IndexMap<Seqnum, SnPduEvent>> {
     let mut list: IndexMap<Seqnum, SnPduEvent> = IndexMap::with_capacity(limit.clamp(10, 100));
     let ignored_users = match user_id {
@@ -177,6 +183,11 @@ pub async fn load_pdus(
                 query = query.filter(events::ty.eq_any(types));
             }
         }
+        let mut conn = if from_replica {
+            connect_replica().await?
+        } else {
+            connect().await?
+        };
         let events: Vec<(OwnedEventId, Seqnum, i64)> = if dir == Direction::Forward {
             query
                 .order((
@@ -187,7 +198,7 @@ pub async fn load_pdus(
                 .offset(offset)
                 .limit(utils::usize_to_i64(limit))
                 .select((events::id, events::sn, events::stream_ordering))
-                .load::<(OwnedEventId, Seqnum, i64)>(&mut connect().await?)
+                .load::<(OwnedEventId, Seqnum, i64)>(&mut conn)
                 .await?
                 .into_iter()
                 .rev()
@@ -203,11 +214,12 @@ pub async fn load_pdus(
                 .limit(utils::usize_to_i64(limit));
             query
                 .select((events::id, events::sn, events::stream_ordering))
-                .load::<(OwnedEventId, Seqnum, i64)>(&mut connect().await?)
+                .load::<(OwnedEventId, Seqnum, i64)>(&mut conn)
                 .await?
                 .into_iter()
                 .collect()
         };
+        drop(conn);
         if events.is_empty() {
             break;
         }
@@ -241,5 +253,8 @@ pub async fn load_pdus(
             break;
         }
     }
+    if let Some(user_id) = user_id {
+        crate::room::pdu_metadata::bundle_aggregations(list.values_mut(), user_id).await?;
+    }
     Ok(list)
 }
@@ -125,6 +125,7 @@ pub async fn shared_rooms(user_ids: Vec<OwnedUserId>) -> AppResult<Vec<OwnedRoom
     for user_id in user_ids {
         let room_ids = room_users::table
             .filter(room_users::user_id.eq(&user_id))
+            .filter(room_users::membership.eq("join"))
             .select(room_users::room_id)
             .load::<OwnedRoomId>(&mut connect().await?)
             .await?;
@@ -146,6 +147,75 @@ pub async fn shared_rooms(user_ids: Vec<OwnedUserId>) -> AppResult<Vec<OwnedRoom
     Ok(shared_rooms)
 }
 
+/// Users who stopped sharing any joined room with `user_id` after `since_sn`, up to
+/// `until_sn`, either because they left a shared room or because `user_id` did.
+pub async fn left_shared_users(
+    user_id: &UserId,
+    since_sn: Seqnum,
+    until_sn: Option<Seqnum>,
+) -> AppResult<Vec<OwnedUserId>> {
+    let until_sn = until_sn.unwrap_or(i64::MAX);
+    let left_memberships = ["leave", "ban"];
+
+    let rooms_left = room_users::table
+        .filter(room_users::user_id.eq(user_id))
+        .filter(room_users::membership.eq_any(left_memberships))
+        .filter(room_users::event_sn.gt(since_sn))
+        .filter(room_users::event_sn.le(until_sn))
+        .select(room_users::room_id)
+        .load::<OwnedRoomId>(&mut connect().await?)
+        .await?;
+    let mut room_ids = crate::data::user::joined_rooms(user_id).await?;
+    room_ids.extend(rooms_left.iter().cloned());
+
+    // Members who left rooms we are or were in during the range.
+    let mut candidates: HashSet<OwnedUserId> = room_users::table
+        .filter(room_users::room_id.eq_any(&room_ids))
+        .filter(room_users::membership.eq_any(left_memberships))
+        .filter(room_users::event_sn.gt(since_sn))
+        .filter(room_users::event_sn.le(until_sn))
+        .select(room_users::user_id)
+        .load::<OwnedUserId>(&mut connect().await?)
+        .await?
+        .into_iter()
+        .collect();
+    // Members still in the rooms we left during the range.
+    candidates.extend(
+        room_users::table
+            .filter(room_users::room_id.eq_any(&rooms_left))
+            .filter(room_users::membership.eq("join"))
+            .select(room_users::user_id)
+            .load::<OwnedUserId>(&mut connect().await?)
+            .await?,
+    );
+    candidates.remove(user_id);
+
+    let mut left_users = Vec::new();
+    for candidate in candidates {
+        if shared_rooms(vec![user_id.to_owned(), candidate.clone()])
+            .await?
+            .is_empty()
+        {
+            left_users.push(candidate);
+        }
+    }
+    Ok(left_users)
+}
+
+/// Whether any local user is joined to a room with the (usually remote) `user_id`.
+pub async fn shares_room_with_local_users(user_id: &UserId) -> AppResult<bool> {
+    let room_ids = crate::data::user::joined_rooms(user_id).await?;
+    if room_ids.is_empty() {
+        return Ok(false);
+    }
+    let query = room_users::table
+        .filter(room_users::room_id.eq_any(&room_ids))
+        .filter(room_users::membership.eq("join"))
+        .filter(room_users::user_server_id.eq(&crate::config::get().server_name))
+        .filter(room_users::user_id.ne(user_id));
+    diesel_exists!(query, &mut connect().await?).map_err(Into::into)
+}
+
 pub async fn join_sn(user_id: &UserId, room_id: &RoomId) -> AppResult<Seqnum> {
     room_users::table
         .filter(room_users::room_id.eq(room_id))
@@ -3,6 +3,7 @@ mod appservice;
 mod client;
 mod federation;
 mod media;
+mod openapi;
 mod policy;
 
 use bytes::Bytes;
@@ -45,21 +46,17 @@ pub mod prelude {
 }
 
 pub fn root() -> Router {
-    Router::new()
+    let mut router = Router::new()
         .hoop(hoops::ensure_accept)
         .hoop(hoops::ensure_content_type)
         .hoop(hoops::limit_size)
         .get(home)
-        .push(
-            Router::with_path("_matrix")
-                .push(client::router())
-                .push(media::router())
-                .push(federation::router())
-                .push(federation::key::router())
-                .push(policy::router())
-                .push(appservice::router()),
-        )
-        .push(admin::router())
+        .push(matrix_router())
+        .push(admin::router());
+    if config::get().openapi.enable {
+        router = router.push(openapi::router());
+    }
+    router
         .push(
             Router::with_path(".well-known/matrix")
                 .push(Router::with_path("client").get(well_known_client))
@@ -72,6 +69,70 @@ pub fn root() -> Router {
         .push(Router::with_path("{*path}").get(StaticDir::new("./static")))
 }
 
+/// The router of the plain HTTP listener answering ACME HTTP-01 challenges.
+///
+/// Besides the `challenges` route, every request is redirected to HTTPS so the
+/// API and the access tokens sent to it never travel unencrypted.
+pub fn acme_http_router(challenges: Router) -> Router {
+    challenges.push(Router::with_path("{**rest}").goal(redirect_to_https))
+}
+
+#[handler]
+async fn redirect_to_https(req: &mut Request, res: &mut Response) {
+    let conf = config::get();
+    let host = req.uri().host().or_else(|| {
+        req.headers()
+            .get("host")
+            .and_then(|host| host.to_str().ok())
+    });
+    let path = req
+        .uri()
+        .path_and_query()
+        .map(|path| path.as_str())
+        .unwrap_or("/");
+    let location = https_location(host, path, conf.server_name.host(), &conf.acme.domains);
+    match Redirect::with_status_code(StatusCode::PERMANENT_REDIRECT, location) {
+        Ok(redirect) => res.render(redirect),
+        Err(_) => res.render(StatusError::bad_request()),
+    }
+}
+
+/// The HTTPS URL a plain HTTP request for `host` and `path` is redirected to.
+///
+/// Only the names certificates are obtained for are redirected to, any other
+/// host falls back to the server name.
+fn https_location(host: Option<&str>, path: &str, server_host: &str, domains: &[String]) -> String {
+    let host = host
+        .map(host_without_port)
+        .filter(|host| *host == server_host || domains.iter().any(|domain| domain == host))
+        .unwrap_or(server_host);
+    format!("https://{host}{path}")
+}
+
+fn host_without_port(host: &str) -> &str {
+    match host.rsplit_once(':') {
+        Some((name, port))
+            if !port.is_empty()
+                && port.bytes().all(|b| b.is_ascii_digit())
+                && (!name.contains(':') || name.ends_with(']')) =>
+        {
+            name
+        }
+        _ => host,
+    }
+}
+
+/// The client, federation and application service APIs.
+fn matrix_router() -> Router {
+    Router::with_path("_matrix")
+        .push(client::router())
+        .push(media::router())
+        .push(federation::router())
+        .push(federation::key::router())
+        .push(policy::router())
+        .push(appservice::router())
+}
+
 #[handler]
 async fn home(req: &mut Request, res: &mut Response) {
     if let Some(home_page) = &config::get().home_page {
@@ -274,7 +335,12 @@ fn well_known_policy_server() -> JsonResult<PolicyServerResBody> {
 
 #[cfg(test)]
 mod tests {
-    use super::{DEFAULT_HOME_PAGE_BODY, HomePageSource};
+    use salvo::prelude::*;
+    use salvo::test::{ResponseExt, TestClient};
+
+    use super::{
+        DEFAULT_HOME_PAGE_BODY, HomePageSource, acme_http_router, host_without_port, https_location,
+    };
 
     #[test]
     fn home_page_classifies_https_urls_as_remote() {
@@ -308,6 +374,56 @@ mod tests {
     fn default_home_page_matches_expected_text() {
         assert_eq!(DEFAULT_HOME_PAGE_BODY, "Palpo works");
     }
+
+    #[test]
+    fn https_redirect_drops_the_http_port() {
+        assert_eq!(host_without_port("example.com:80"), "example.com");
+        assert_eq!(host_without_port("example.com"), "example.com");
+        assert_eq!(host_without_port("[::1]:80"), "[::1]");
+        assert_eq!(host_without_port("::1"), "::1");
+    }
+
+    #[test]
+    fn https_redirect_keeps_only_certified_hosts() {
+        let domains = vec!["matrix.example.com".to_owned()];
+        assert_eq!(
+            https_location(
+                Some("matrix.example.com:80"),
+                "/_matrix/client/versions?a=b",
+                "example.com",
+                &domains
+            ),
+            "https://matrix.example.com/_matrix/client/versions?a=b"
+        );
+        assert_eq!(
+            https_location(Some("example.com"), "/", "example.com", &domains),
+            "https://example.com/"
+        );
+        assert_eq!(
+            https_location(Some("evil.example.org"), "/", "example.com", &domains),
+            "https://example.com/"
+        );
+        assert_eq!(
+            https_location(None, "/", "example.com", &domains),
+            "https://example.com/"
+        );
+    }
+
+    #[handler]
+    async fn challenge(res: &mut Response) {
+        res.render(Text::Plain("token"));
+    }
+
+    #[tokio::test]
+    async fn acme_http_router_serves_challenges() {
+        let challenges = Router::with_path(".well-known/acme-challenge/{token}").get(challenge);
+        let service = Service::new(acme_http_router(challenges));
+        let mut res = TestClient::get("http://example.com/.well-known/acme-challenge/abc")
+            .send(&service)
+            .await;
+        assert_eq!(res.status_code, Some(StatusCode::OK));
+        assert_eq!(res.take_string().await.unwrap(), "token");
+    }
 }
 
 #[endpoint]
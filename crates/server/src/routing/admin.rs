@@ -7,17 +7,21 @@ mod mas;
 mod media;
 mod register;
 mod registration_token;
+mod report;
 mod room;
+mod room_report;
 mod scheduled_task;
 mod server_notice;
 mod statistic;
 mod user;
 mod user_admin;
 mod user_lookup;
+mod user_report;
 
 use salvo::prelude::*;
 use subtle::ConstantTimeEq;
 
+use crate::routing::openapi;
 use crate::routing::prelude::*;
 
 /// Middleware to require admin privileges
@@ -61,6 +65,7 @@ pub fn router() -> Router {
             Router::with_path(v)
                 .hoop(crate::hoops::auth_by_access_token)
                 .hoop(require_admin)
+                .oapi_security(openapi::access_token())
                 .get(home)
                 .push(appservice::router())
                 .push(debug::router())
@@ -71,12 +76,14 @@ pub fn router() -> Router {
                 .push(register::router())
                 .push(registration_token::router())
                 .push(room::router())
+                .push(room_report::router())
                 .push(scheduled_task::router())
                 .push(server_notice::router())
                 .push(statistic::router())
                 .push(user::router())
                 .push(user_admin::router())
-                .push(user_lookup::router()),
+                .push(user_lookup::router())
+                .push(user_report::router()),
         )
     }
     for v in ["_palpo/mas", "_synapse/mas"] {
@@ -84,6 +91,7 @@ pub fn router() -> Router {
         admin = admin.push(
             Router::with_path(v)
                 .hoop(auth_by_mas_secret)
+                .oapi_security(openapi::mas_secret())
                 .push(mas::router()),
         );
     }
@@ -109,7 +109,7 @@ pub struct UpdateEventReportReqBody {
     pub status: String,
 }
 
-fn normalize_event_report_status(status: &str) -> Option<&'static str> {
+pub(super) fn normalize_report_status(status: &str) -> Option<&'static str> {
     match status.trim() {
         "new" | "New" => Some("new"),
         "in_review" | "In Review" | "in review" => Some("in_review"),
@@ -200,7 +200,7 @@ pub async fn update_event_report_status(
 ) -> JsonResult<EventReportDetailResponse> {
     let report_id = report_id.into_inner();
     let body = body.into_inner();
-    let status = normalize_event_report_status(&body.status).ok_or_else(|| {
+    let status = normalize_report_status(&body.status).ok_or_else(|| {
         MatrixError::invalid_param("status must be one of: new, in_review, resolved")
     })?;
 
@@ -289,10 +289,7 @@ pub async fn delete_user(body: JsonBody<DeleteUserReqBody>) -> EmptyResult {
         return Err(MatrixError::not_found("User not found").into());
     }
     let joined_rooms = data::user::joined_rooms(&user_id).await?;
-    user::full_user_deactivate(&user_id, &joined_rooms).await?;
-    if body.erase {
-        user::delete_all_media(&user_id).await?;
-    }
+    user::full_user_deactivate(&user_id, &joined_rooms, body.erase).await?;
     empty_ok()
 }
 
@@ -0,0 +1,82 @@
+//! Parts shared by the admin room and user reports APIs, which only differ by
+//! what is reported.
+
+use salvo::prelude::*;
+use serde::Deserialize;
+
+use super::event_report::normalize_report_status;
+use crate::core::identifiers::*;
+use crate::data::report::ReportFilter;
+use crate::{AppError, AppResult, MatrixError};
+
+/// Query parameters of a report listing, besides the filter on what is reported.
+#[derive(Debug, Deserialize, ToParameters)]
+pub struct ListReportsQuery {
+    #[serde(default)]
+    pub from: Option<i64>,
+    #[serde(default)]
+    pub limit: Option<i64>,
+    #[serde(default)]
+    pub dir: Option<String>,
+    #[serde(default)]
+    pub user_id: Option<String>,
+    #[serde(default)]
+    pub status: Option<String>,
+}
+
+impl ListReportsQuery {
+    /// Validate the parameters and build the listing filter, `target` being the
+    /// already parsed filter on what is reported.
+    pub fn into_filter<T>(self, target: Option<T>) -> AppResult<ReportFilter<T>> {
+        let from = self.from.unwrap_or(0);
+        if from < 0 {
+            return Err(MatrixError::invalid_param("from must be a non-negative integer").into());
+        }
+        let limit = self.limit.unwrap_or(100);
+        if limit <= 0 || limit > 1000 {
+            return Err(MatrixError::invalid_param("limit must be between 1 and 1000").into());
+        }
+        let user_id = self
+            .user_id
+            .as_deref()
+            .map(UserId::parse)
+            .transpose()
+            .map_err(|_| MatrixError::invalid_param("Invalid user_id"))?;
+        let status = self.status.as_deref().map(parse_status).transpose()?;
+
+        Ok(ReportFilter {
+            from: Some(from),
+            limit: Some(limit),
+            direction: self.dir,
+            user_id,
+            target,
+            status: status.map(ToOwned::to_owned),
+        })
+    }
+}
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateReportReqBody {
+    pub status: String,
+}
+
+pub fn parse_status(status: &str) -> AppResult<&'static str> {
+    normalize_report_status(status).ok_or_else(|| {
+        MatrixError::invalid_param("status must be one of: new, in_review, resolved").into()
+    })
+}
+
+/// The `next_token` of a listing page holding `count` of the `total` reports.
+pub fn next_token<T>(filter: &ReportFilter<T>, count: usize, total: i64) -> Option<i64> {
+    let from = filter.from.unwrap_or(0);
+    if from + filter.limit.unwrap_or(100) < total {
+        Some(from + count as i64)
+    } else {
+        None
+    }
+}
+
+/// `kind` is capitalized, e.g. "Room".
+pub fn not_found(kind: &str, report_id: i64) -> AppError {
+    MatrixError::not_found(format!("{kind} report {report_id} not found")).into()
+}
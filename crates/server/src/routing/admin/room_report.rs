@@ -0,0 +1,131 @@
+//! Admin Room Reports API
+//!
+//! - GET /_synapse/admin/v1/room_reports
+//! - GET /_synapse/admin/v1/room_reports/{report_id}
+//! - PUT /_synapse/admin/v1/room_reports/{report_id}
+//! - DELETE /_synapse/admin/v1/room_reports/{report_id}
+
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::Serialize;
+
+use super::report::{self, ListReportsQuery, UpdateReportReqBody};
+use crate::core::identifiers::*;
+use crate::{EmptyResult, JsonResult, MatrixError, data, empty_ok, json_ok};
+
+pub fn router() -> Router {
+    Router::new()
+        .push(Router::with_path("v1/room_reports").get(list_room_reports))
+        .push(
+            Router::with_path("v1/room_reports/{report_id}")
+                .get(get_room_report)
+                .put(update_room_report_status)
+                .delete(delete_room_report),
+        )
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RoomReport {
+    pub id: i64,
+    pub received_ts: i64,
+    pub status: String,
+    pub room_id: String,
+    pub user_id: String,
+    pub reason: Option<String>,
+}
+
+impl From<data::room::DbRoomReport> for RoomReport {
+    fn from(db: data::room::DbRoomReport) -> Self {
+        Self {
+            id: db.id,
+            received_ts: db.received_ts,
+            status: db.status,
+            room_id: db.room_id.to_string(),
+            user_id: db.user_id.to_string(),
+            reason: db.reason,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct RoomReportsResponse {
+    pub room_reports: Vec<RoomReport>,
+    pub total: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_token: Option<i64>,
+}
+
+/// GET /_synapse/admin/v1/room_reports
+///
+/// List all reported rooms with pagination and filtering
+#[endpoint(operation_id = "list_room_reports")]
+pub async fn list_room_reports(
+    query: ListReportsQuery,
+    room_id: QueryParam<String, false>,
+) -> JsonResult<RoomReportsResponse> {
+    let room_id = room_id
+        .into_inner()
+        .as_deref()
+        .map(RoomId::parse)
+        .transpose()
+        .map_err(|_| MatrixError::invalid_param("Invalid room_id"))?;
+    let filter = query.into_filter(room_id)?;
+
+    let (reports, total) = data::room::list_room_reports(&filter).await?;
+
+    let room_reports: Vec<RoomReport> = reports.into_iter().map(Into::into).collect();
+    let next_token = report::next_token(&filter, room_reports.len(), total);
+
+    json_ok(RoomReportsResponse {
+        room_reports,
+        total,
+        next_token,
+    })
+}
+
+/// GET /_synapse/admin/v1/room_reports/{report_id}
+///
+/// Get details of a specific room report
+#[endpoint(operation_id = "get_room_report")]
+pub async fn get_room_report(report_id: PathParam<i64>) -> JsonResult<RoomReport> {
+    let report_id = report_id.into_inner();
+
+    let report = data::room::get_room_report(report_id)
+        .await?
+        .ok_or_else(|| report::not_found("Room", report_id))?;
+
+    json_ok(report.into())
+}
+
+/// PUT /_synapse/admin/v1/room_reports/{report_id}
+///
+/// Update the moderation status of a room report
+#[endpoint(operation_id = "update_room_report_status")]
+pub async fn update_room_report_status(
+    report_id: PathParam<i64>,
+    body: JsonBody<UpdateReportReqBody>,
+) -> JsonResult<RoomReport> {
+    let report_id = report_id.into_inner();
+    let status = report::parse_status(&body.into_inner().status)?;
+
+    let report = data::room::update_room_report_status(report_id, status)
+        .await?
+        .ok_or_else(|| report::not_found("Room", report_id))?;
+
+    json_ok(report.into())
+}
+
+/// DELETE /_synapse/admin/v1/room_reports/{report_id}
+///
+/// Delete a room report
+#[endpoint(operation_id = "delete_room_report")]
+pub async fn delete_room_report(report_id: PathParam<i64>) -> EmptyResult {
+    let report_id = report_id.into_inner();
+
+    let deleted = data::room::delete_room_report(report_id).await?;
+    if !deleted {
+        return Err(report::not_found("Room", report_id));
+    }
+
+    empty_ok()
+}
@@ -11,13 +11,15 @@
 //! - POST /_synapse/admin/v1/reset_password/{user_id}
 //! - GET/PUT /_synapse/admin/v1/users/{user_id}/admin
 //! - POST/DELETE /_synapse/admin/v1/users/{user_id}/shadow_ban
+//! - GET /_synapse/admin/v1/users/{user_id}/export_data
 
+use salvo::fs::NamedFile;
 use salvo::oapi::extract::*;
 use salvo::prelude::*;
 use serde::{Deserialize, Serialize};
 
 use crate::core::identifiers::*;
-use crate::{EmptyResult, JsonResult, MatrixError, data, empty_ok, json_ok, user};
+use crate::{AppResult, EmptyResult, JsonResult, MatrixError, data, empty_ok, json_ok, user};
 
 // ============================================================================
 // Response/Request Types
@@ -546,14 +548,8 @@ pub async fn deactivate_user(
     // Get all joined rooms before deactivation
     let joined_rooms = data::user::joined_rooms(&user_id).await?;
 
-    // Perform full deactivation
-    user::full_user_deactivate(&user_id, &joined_rooms).await?;
-
-    // Erase user data if requested
-    if body.erase.unwrap_or(false) {
-        // Delete all media
-        user::delete_all_media(&user_id).await?;
-    }
+    // Perform full deactivation, erasing user data if requested
+    user::full_user_deactivate(&user_id, &joined_rooms, body.erase.unwrap_or(false)).await?;
 
     empty_ok()
 }
@@ -771,6 +767,11 @@ pub async fn user_pushers(user_id: PathParam<OwnedUserId>) -> JsonResult<Pushers
                 "kind": p.kind,
                 "lang": p.lang,
                 "pushkey": p.pushkey,
+                "data": p.data,
+                "profile_tag": p.profile_tag,
+                "enabled": p.enabled,
+                "last_success": p.last_success,
+                "failing_since": p.failing_since,
             })
         })
         .collect();
@@ -804,6 +805,32 @@ pub async fn user_account_data(user_id: PathParam<OwnedUserId>) -> JsonResult<Ac
     })
 }
 
+/// GET /_synapse/admin/v1/users/{user_id}/export_data
+///
+/// Export everything stored about a user as a `.tar.gz` archive
+#[endpoint]
+pub async fn export_user_data(
+    user_id: PathParam<OwnedUserId>,
+    req: &mut Request,
+    res: &mut Response,
+) -> AppResult<()> {
+    let user_id = user_id.into_inner();
+
+    if !data::user::user_exists(&user_id).await? {
+        return Err(MatrixError::not_found("User not found").into());
+    }
+
+    let archive = user::export::export_user_data(&user_id).await?;
+    // The file is opened before `archive` is dropped and removed, so it is still
+    // streamed in full.
+    NamedFile::builder(archive.path())
+        .attached_name(format!("{}-export.tar.gz", user_id.localpart()))
+        .content_type("application/gzip".parse().expect("valid mime type"))
+        .send(req.headers(), res)
+        .await;
+    Ok(())
+}
+
 /// GET /_synapse/admin/v1/users/{user_id}/override_ratelimit
 ///
 /// Get ratelimit override for a user
@@ -915,6 +942,8 @@ pub fn router() -> Router {
         .push(Router::with_path("v1/users/{user_id}/pushers").get(user_pushers))
         // v1/users/{user_id}/accountdata
         .push(Router::with_path("v1/users/{user_id}/accountdata").get(user_account_data))
+        // v1/users/{user_id}/export_data
+        .push(Router::with_path("v1/users/{user_id}/export_data").get(export_user_data))
         // v1/users/{user_id}/override_ratelimit
         .push(
             Router::with_path("v1/users/{user_id}/override_ratelimit")
@@ -0,0 +1,131 @@
+//! Admin User Reports API
+//!
+//! - GET /_synapse/admin/v1/user_reports
+//! - GET /_synapse/admin/v1/user_reports/{report_id}
+//! - PUT /_synapse/admin/v1/user_reports/{report_id}
+//! - DELETE /_synapse/admin/v1/user_reports/{report_id}
+
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+use serde::Serialize;
+
+use super::report::{self, ListReportsQuery, UpdateReportReqBody};
+use crate::core::identifiers::*;
+use crate::{EmptyResult, JsonResult, MatrixError, data, empty_ok, json_ok};
+
+pub fn router() -> Router {
+    Router::new()
+        .push(Router::with_path("v1/user_reports").get(list_user_reports))
+        .push(
+            Router::with_path("v1/user_reports/{report_id}")
+                .get(get_user_report)
+                .put(update_user_report_status)
+                .delete(delete_user_report),
+        )
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserReport {
+    pub id: i64,
+    pub received_ts: i64,
+    pub status: String,
+    pub target_user_id: String,
+    pub user_id: String,
+    pub reason: Option<String>,
+}
+
+impl From<data::user::DbUserReport> for UserReport {
+    fn from(db: data::user::DbUserReport) -> Self {
+        Self {
+            id: db.id,
+            received_ts: db.received_ts,
+            status: db.status,
+            target_user_id: db.target_user_id.to_string(),
+            user_id: db.user_id.to_string(),
+            reason: db.reason,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct UserReportsResponse {
+    pub user_reports: Vec<UserReport>,
+    pub total: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_token: Option<i64>,
+}
+
+/// GET /_synapse/admin/v1/user_reports
+///
+/// List all reported users with pagination and filtering
+#[endpoint(operation_id = "list_user_reports")]
+pub async fn list_user_reports(
+    query: ListReportsQuery,
+    target_user_id: QueryParam<String, false>,
+) -> JsonResult<UserReportsResponse> {
+    let target_user_id = target_user_id
+        .into_inner()
+        .as_deref()
+        .map(UserId::parse)
+        .transpose()
+        .map_err(|_| MatrixError::invalid_param("Invalid target_user_id"))?;
+    let filter = query.into_filter(target_user_id)?;
+
+    let (reports, total) = data::user::list_user_reports(&filter).await?;
+
+    let user_reports: Vec<UserReport> = reports.into_iter().map(Into::into).collect();
+    let next_token = report::next_token(&filter, user_reports.len(), total);
+
+    json_ok(UserReportsResponse {
+        user_reports,
+        total,
+        next_token,
+    })
+}
+
+/// GET /_synapse/admin/v1/user_reports/{report_id}
+///
+/// Get details of a specific user report
+#[endpoint(operation_id = "get_user_report")]
+pub async fn get_user_report(report_id: PathParam<i64>) -> JsonResult<UserReport> {
+    let report_id = report_id.into_inner();
+
+    let report = data::user::get_user_report(report_id)
+        .await?
+        .ok_or_else(|| report::not_found("User", report_id))?;
+
+    json_ok(report.into())
+}
+
+/// PUT /_synapse/admin/v1/user_reports/{report_id}
+///
+/// Update the moderation status of a user report
+#[endpoint(operation_id = "update_user_report_status")]
+pub async fn update_user_report_status(
+    report_id: PathParam<i64>,
+    body: JsonBody<UpdateReportReqBody>,
+) -> JsonResult<UserReport> {
+    let report_id = report_id.into_inner();
+    let status = report::parse_status(&body.into_inner().status)?;
+
+    let report = data::user::update_user_report_status(report_id, status)
+        .await?
+        .ok_or_else(|| report::not_found("User", report_id))?;
+
+    json_ok(report.into())
+}
+
+/// DELETE /_synapse/admin/v1/user_reports/{report_id}
+///
+/// Delete a user report
+#[endpoint(operation_id = "delete_user_report")]
+pub async fn delete_user_report(report_id: PathParam<i64>) -> EmptyResult {
+    let report_id = report_id.into_inner();
+
+    let deleted = data::user::delete_user_report(report_id).await?;
+    if !deleted {
+        return Err(report::not_found("User", report_id));
+    }
+
+    empty_ok()
+}
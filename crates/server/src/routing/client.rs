@@ -38,6 +38,7 @@ use crate::core::client::discovery::capabilities::{
 };
 use crate::core::client::discovery::versions::{Server, VersionsResBody};
 use crate::core::client::search::{ResultCategories, SearchReqArgs, SearchReqBody, SearchResBody};
+use crate::routing::openapi;
 use crate::routing::prelude::*;
 
 pub fn router() -> Router {
@@ -61,6 +62,7 @@ pub fn router() -> Router {
             .push(
                 Router::with_path(v)
                     .hoop(hoops::auth_by_access_token)
+                    .oapi_security(openapi::access_token())
                     .push(account::authed_router())
                     .push(register::authed_router())
                     .push(session::authed_router())
@@ -68,6 +70,7 @@ pub fn router() -> Router {
                     .push(room_key::authed_router())
                     .push(room::authed_router())
                     .push(user::authed_router())
+                    .push(user::users_authed_router())
                     .push(directory::authed_router())
                     .push(user_directory::authed_router())
                     .push(key::authed_router())
@@ -105,6 +108,7 @@ pub fn router() -> Router {
                 Router::with_path(v)
                     .hoop(hoops::limit_rate)
                     .hoop(hoops::auth_by_access_token)
+                    .oapi_security(openapi::access_token())
                     .push(Router::with_path("search").post(search))
                     .push(Router::with_path("capabilities").get(get_capabilities))
                     .push(Router::with_path("knock/{room_id_or_alias}").post(room::knock_room)),
@@ -115,6 +119,7 @@ pub fn router() -> Router {
             Router::with_path("v1").push(user::stable_v1_router()).push(
                 Router::with_path("room_summary/{room_id_or_alias}")
                     .hoop(hoops::auth_by_access_token)
+                    .oapi_security(openapi::access_token())
                     .get(room::summary::get_summary),
             ),
         )
@@ -133,7 +138,8 @@ pub fn router() -> Router {
 /// #POST /_matrix/client/r0/search
 /// Searches rooms for messages.
 ///
-/// - Only works if the user is currently joined to the room (TODO: Respect history visibility)
+/// - Rooms the user left are searched up to their leave, other events are filtered by history
+///   visibility
 #[endpoint]
 async fn search(
     _aa: AuthArgs,
@@ -238,6 +244,8 @@ fn supported_versions_body() -> VersionsResBody {
             ("us.cloke.msc4175".to_owned(), true), /* Profile field for user time zone (https://github.com/matrix-org/matrix-spec-proposals/pull/4175) */
             ("org.matrix.simplified_msc3575".to_owned(), true), /* Simplified Sliding sync (https://github.com/matrix-org/matrix-spec-proposals/pull/4186) */
             ("uk.timedout.msc4323".to_owned(), true),           // Account suspension and locking.
+            ("org.matrix.msc4306".to_owned(), true), /* Thread subscriptions (https://github.com/matrix-org/matrix-spec-proposals/pull/4306) */
+            ("org.matrix.msc4308".to_owned(), true), /* Thread subscriptions in sliding sync (https://github.com/matrix-org/matrix-spec-proposals/pull/4308) */
             ("net.zemos.msc4383".to_owned(), true), /* Homeserver implementation metadata (https://github.com/matrix-org/matrix-spec-proposals/pull/4383) */
         ]),
         server: Some(Server::new(
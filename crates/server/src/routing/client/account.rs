@@ -134,7 +134,7 @@ async fn deactivate(
     }
 
     let all_joined_rooms = data::user::joined_rooms(authed.user_id()).await?;
-    crate::user::full_user_deactivate(authed.user_id(), &all_joined_rooms).await?;
+    crate::user::full_user_deactivate(authed.user_id(), &all_joined_rooms, body.erase).await?;
 
     // info!("User {} deactivated their account.", authed.user_id());
     // crate::admin::send_message(RoomMessageEventContent::notice_plain(format!(
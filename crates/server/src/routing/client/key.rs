@@ -62,6 +62,7 @@ async fn query_keys(
 /// Publish end-to-end encryption keys for the sender device.
 ///
 /// - Adds one time keys
+/// - Replaces the fallback key of each uploaded algorithm
 /// - If there are no device keys yet: Adds device keys (TODO: merge with existing keys?)
 #[endpoint]
 async fn upload_keys(
@@ -76,12 +77,15 @@ async fn upload_keys(
             .await?;
     }
 
+    for (key_id, fallback_key) in &body.fallback_keys {
+        crate::user::add_fallback_key(authed.user_id(), authed.device_id(), key_id, fallback_key)
+            .await?;
+    }
+
     if let Some(device_keys) = &body.device_keys {
         crate::user::add_device_keys(authed.user_id(), authed.device_id(), device_keys).await?;
     }
 
-    // TODO: fallback keys. e2e_keys.py 848
-
     json_ok(UploadKeysResBody {
         one_time_key_counts: data::user::count_one_time_keys(authed.user_id(), authed.device_id())
             .await?,
@@ -89,9 +93,8 @@ async fn upload_keys(
 }
 
 /// #POST /_matrix/client/r0/keys/changes
-/// Gets a list of users who have updated their device identity keys since the previous sync token.
-///
-/// - TODO: left users
+/// Gets a list of users who have updated their device identity keys since the previous sync token,
+/// and of those who no longer share any room with the sender.
 #[endpoint]
 async fn get_key_changes(
     _aa: AuthArgs,
@@ -114,8 +117,13 @@ async fn get_key_changes(
             room::keys_changed_users(&room_id, from_tk.event_sn(), Some(to_tk.event_sn())).await?,
         );
     }
+    let left = room::user::left_shared_users(sender_id, from_tk.event_sn(), Some(to_tk.event_sn()))
+        .await?;
+    for user_id in &left {
+        device_list_updates.remove(user_id);
+    }
     json_ok(KeyChangesResBody {
         changed: device_list_updates.into_iter().collect(),
-        left: Vec::new(), // TODO
+        left,
     })
 }
@@ -19,6 +19,7 @@ use crate::data::media::{DbMetadata, DbThumbnail, NewDbMetadata, NewDbThumbnail}
 use crate::data::schema::*;
 use crate::exts::*;
 use crate::media::*;
+use crate::routing::openapi;
 use crate::{
     AppResult, AuthArgs, EmptyResult, JsonResult, MatrixError, config, empty_ok, hoops, json_ok,
     storage, utils,
@@ -30,12 +31,14 @@ pub fn self_auth_router() -> Router {
         .push(
             Router::with_path("download/{server_name}/{media_id}")
                 .hoop(hoops::auth_by_access_token_or_signatures)
+                .oapi_securities([openapi::access_token(), openapi::x_matrix()])
                 .get(get_content)
                 .push(Router::with_path("{filename}").get(get_content_with_filename)),
         )
         .push(
             Router::with_hoop(hoops::limit_rate)
                 .hoop(hoops::auth_by_access_token)
+                .oapi_security(openapi::access_token())
                 .push(Router::with_path("config").get(get_config))
                 .push(Router::with_path("preview_url").get(preview_url))
                 .push(Router::with_path("thumbnail/{server_name}/{media_id}").get(get_thumbnail)),
@@ -339,6 +339,7 @@ async fn set_avatar_url(
             .set(updata_params)
             .execute(&mut connect().await?)
             .await?;
+        data::user::directory::sync_profile(&user_id).await?;
     } else {
         return Err(StatusError::not_found().brief("Profile not found.").into());
     }
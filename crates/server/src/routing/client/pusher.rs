@@ -32,7 +32,8 @@ async fn pushers(depot: &mut Depot) -> JsonResult<PushersResBody> {
 /// #POST /_matrix/client/r0/pushers/set
 /// Adds a pusher for the sender user.
 ///
-/// - TODO: Handle `append`
+/// Unless `append` is set, pushers of other users with the same app id and
+/// pushkey are removed.
 #[endpoint]
 async fn set_pusher(body: JsonBody<SetPusherReqBody>, depot: &mut Depot) -> EmptyResult {
     let authed = depot.authed_info()?;
@@ -3,11 +3,12 @@ pub(super) mod membership;
 mod message;
 mod receipt;
 mod relation;
+mod report;
 mod space;
 mod state;
 pub mod summary;
 mod tag;
-mod thread;
+pub(super) mod thread;
 use std::cmp::max;
 use std::collections::BTreeMap;
 
@@ -127,6 +128,7 @@ pub fn authed_router() -> Router {
                     .push(
                         Router::with_path("send/{event_type}/{txn_id}").put(message::send_message),
                     )
+                    .push(Router::with_path("report").post(report::report_room))
                     .push(Router::with_path("report/{event_id}").post(state::report))
                     .push(Router::with_path("redact/{event_id}/{txn_id}").put(event::send_redact))
                     .push(
@@ -421,8 +423,24 @@ async fn set_read_markers(
 
     if let Some(event_id) = &body.private_read_receipt {
         let (event_sn, _event_guard) = crate::event::ensure_event_sn(&room_id, event_id).await?;
-        data::room::receipt::set_private_read(&room_id, sender_id, event_id, event_sn).await?;
-        push_action::remove_actions_until(sender_id, &room_id, event_sn, None).await?;
+        data::room::receipt::set_private_read(
+            &room_id,
+            sender_id,
+            event_id,
+            event_sn,
+            &Receipt {
+                ts: Some(UnixMillis::now()),
+                thread: ReceiptThread::Unthreaded,
+            },
+        )
+        .await?;
+        push_action::remove_actions_until(
+            sender_id,
+            &room_id,
+            event_sn,
+            &ReceiptThread::Unthreaded,
+        )
+        .await?;
         push_action::refresh_notify_summary(sender_id, &room_id).await?;
     }
 
@@ -453,7 +471,13 @@ async fn set_read_markers(
         )
         .await?;
         let event_sn = crate::event::get_event_sn(event).await?;
-        push_action::remove_actions_until(sender_id, &room_id, event_sn, None).await?;
+        push_action::remove_actions_until(
+            sender_id,
+            &room_id,
+            event_sn,
+            &ReceiptThread::Unthreaded,
+        )
+        .await?;
         push_action::refresh_notify_summary(sender_id, &room_id).await?;
     }
     empty_ok()
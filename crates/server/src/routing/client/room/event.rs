@@ -1,6 +1,5 @@
 use std::collections::HashSet;
 
-use palpo_core::Direction;
 use salvo::oapi::extract::*;
 use salvo::prelude::*;
 use serde_json::json;
@@ -17,14 +16,10 @@ use crate::core::events::room::redaction::RoomRedactionEventContent;
 use crate::core::events::{StateEventType, TimelineEventType};
 use crate::core::room::{RoomEventReqArgs, TimestampToEventReqArgs, TimestampToEventResBody};
 use crate::data::room::DbEvent;
-use crate::event::fetching::fetch_event;
-use crate::event::handler::{process_pulled_pdu, remote_timestamp_to_event};
-use crate::event::parse_fetched_pdu;
 use crate::room::{state, timeline};
 use crate::utils::HtmlEscape;
 use crate::{
-    AuthArgs, DepotExt, EmptyResult, JsonResult, MatrixError, OptionalExtension, PduBuilder,
-    empty_ok, json_ok, room,
+    AuthArgs, DepotExt, EmptyResult, JsonResult, MatrixError, PduBuilder, empty_ok, json_ok, room,
 };
 
 /// #GET /_matrix/client/r0/rooms/{room_id}/event/{event_id}
@@ -67,6 +62,7 @@ pub(super) async fn get_room_event(
 
     let mut event = event.clone();
     event.add_age()?;
+    event.add_bundled_aggregations(authed.user_id()).await?;
 
     json_ok(RoomEventResBody::new(event.to_room_event()))
 }
@@ -168,7 +164,7 @@ pub(super) async fn get_context(
     let base_token = crate::event::get_live_token(&args.event_id)
         .await
         .map_err(|_| MatrixError::not_found("base event id not found"))?;
-    let base_event = timeline::get_pdu(&args.event_id).await?;
+    let mut base_event = timeline::get_pdu(&args.event_id).await?;
     let room_id = base_event.room_id.clone();
 
     if !state::user_can_see_event(sender_id, &args.event_id).await? {
@@ -198,6 +194,7 @@ pub(super) async fn get_context(
 
     // Use limit with maximum 100
     let limit = args.limit.min(100);
+    base_event.add_bundled_aggregations(sender_id).await?;
     let base_event = base_event.to_room_event();
     let events_before_loaded = timeline::stream::load_pdus_backward(
         Some(sender_id),
@@ -376,115 +373,10 @@ pub(super) async fn timestamp_to_event(
     if !room::user::is_joined(authed.user_id(), &args.room_id).await? {
         return Err(MatrixError::forbidden("You are not joined to this room.", None).into());
     }
-    let local_event = crate::event::get_event_for_timestamp(&args.room_id, args.ts, args.dir)
-        .await
-        .optional()?;
-
-    let mut is_event_next_to_backward_gap = false;
-    let mut is_event_next_to_forward_gap = false;
-    if let Some(local_event) = &local_event {
-        let local_event = timeline::get_pdu(&local_event.0).await?;
-        match args.dir {
-            Direction::Backward => {
-                is_event_next_to_forward_gap =
-                    timeline::is_event_next_to_forward_gap(&local_event).await?
-            }
-            Direction::Forward => {
-                is_event_next_to_backward_gap =
-                    timeline::is_event_next_to_backward_gap(&local_event).await?
-            }
-        }
-    }
-    if local_event.is_none() || is_event_next_to_backward_gap || is_event_next_to_forward_gap {
-        let remote_servers = room::admin_servers(&args.room_id, false).await?;
-        let Ok((
-            remote_server,
-            TimestampToEventResBody {
-                event_id,
-                origin_server_ts,
-            },
-        )) = remote_timestamp_to_event(
-            &remote_servers,
-            &args.room_id,
-            args.dir,
-            args.ts,
-            local_event.as_ref(),
-        )
-        .await
-        else {
-            return if let Some((event_id, origin_server_ts)) = local_event {
-                json_ok(TimestampToEventResBody {
-                    event_id,
-                    origin_server_ts,
-                })
-            } else {
-                Err(StatusError::not_found().brief("no event found").into())
-            };
-        };
-        let room_version = crate::room::get_version(&args.room_id).await?;
-        let Ok((event_id, event_value)) = parse_fetched_pdu(
-            &args.room_id,
-            &room_version,
-            &fetch_event(&remote_server, &event_id).await?.pdu,
-        ) else {
-            error!("failed parse featch pdu for timestamp to event");
-            return json_ok(TimestampToEventResBody {
-                event_id,
-                origin_server_ts,
-            });
-        };
-        let event_value_for_promotion = event_value.clone();
-        process_pulled_pdu(
-            &remote_server,
-            &event_id,
-            &args.room_id,
-            &room_version,
-            event_value,
-            true,
-        )
-        .await?;
-
-        // Per the spec, after finding the event the server "should try to
-        // backfill this event". Fetching the single event above leaves it as an
-        // outlier whenever its prev_events aren't local yet, which keeps it out
-        // of `/messages` pagination (the timeline page stops at the gap). Pull
-        // the surrounding history so the event becomes a connected, non-outlier
-        // timeline event that subsequent backward pagination can return.
-        if let Err(e) =
-            timeline::backfill_from_extremities(&args.room_id, std::slice::from_ref(&event_id), 100)
-                .await
-        {
-            warn!("failed to backfill history around timestamp_to_event result: {e}");
-        }
-
-        // `/backfill` returns the predecessors of its seed, not necessarily the
-        // seed itself. The first processing attempt above can therefore leave
-        // the timestamp result as an outlier while its predecessor is missing.
-        // Retry after backfill so the now-connected result gets an event-time
-        // state frame and can be authorized by `/context` and `/messages`.
-        if timeline::get_non_outlier_pdu(&event_id).await?.is_none() {
-            process_pulled_pdu(
-                &remote_server,
-                &event_id,
-                &args.room_id,
-                &room_version,
-                event_value_for_promotion,
-                true,
-            )
-            .await?;
-        }
-
-        return json_ok(TimestampToEventResBody {
-            event_id,
-            origin_server_ts,
-        });
-    }
-    if let Some((event_id, origin_server_ts)) = local_event {
-        json_ok(TimestampToEventResBody {
-            event_id,
-            origin_server_ts,
-        })
-    } else {
-        Err(StatusError::not_found().brief("no event found").into())
-    }
+    let (event_id, origin_server_ts) =
+        crate::event::get_event_for_timestamp(&args.room_id, args.ts, args.dir).await?;
+    json_ok(TimestampToEventResBody {
+        event_id,
+        origin_server_ts,
+    })
 }
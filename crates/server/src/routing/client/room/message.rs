@@ -116,13 +116,15 @@ pub(super) async fn get_messages(
     let mut lazy_loaded = HashSet::new();
     match args.dir {
         Direction::Forward => {
-            let events = topolo::load_pdus_forward(
+            let events = topolo::load_pdus(
                 Some(sender_id),
                 &args.room_id,
                 Some(from_tk),
                 until_tk,
-                Some(&args.filter),
                 limit,
+                Some(&args.filter),
+                Direction::Forward,
+                true,
             )
             .await?;
 
@@ -153,21 +155,23 @@ pub(super) async fn get_messages(
             resp.chunk = events;
         }
         Direction::Backward => {
-            let mut events: indexmap::IndexMap<i64, crate::SnPduEvent> =
-                topolo::load_pdus_backward(
-                    Some(sender_id),
-                    &args.room_id,
-                    Some(from_tk),
-                    until_tk,
-                    Some(&args.filter),
-                    limit,
-                )
-                .await?;
+            let mut events: indexmap::IndexMap<i64, crate::SnPduEvent> = topolo::load_pdus(
+                Some(sender_id),
+                &args.room_id,
+                Some(from_tk),
+                until_tk,
+                limit,
+                Some(&args.filter),
+                Direction::Backward,
+                true,
+            )
+            .await?;
             // Backfill if the local page is short on history. Backfilled events
             // may have higher depth than what we already loaded (e.g. on a fresh
             // join, locally we only have low-depth state events while the
             // backfilled timeline messages live above them), so re-run the page
-            // load whenever we filled anything.
+            // load whenever we filled anything. The reload goes to the primary
+            // because a replica may not have the backfilled events yet.
             let filled_events =
                 timeline::backfill_if_required(&args.room_id, &from_tk, &events, limit).await?;
             if !filled_events.is_empty() {
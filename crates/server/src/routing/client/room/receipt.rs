@@ -12,7 +12,7 @@ use crate::core::events::receipt::{
 };
 use crate::core::presence::PresenceState;
 use crate::room::push_action;
-use crate::{AppError, AuthArgs, DepotExt, EmptyResult, empty_ok, room};
+use crate::{AppError, AuthArgs, DepotExt, EmptyResult, MatrixError, data, empty_ok, room};
 
 /// #POST /_matrix/client/r0/rooms/{room_id}/receipt/{receipt_type}/{event_id}
 /// Sets private read marker and public read receipt EDU.
@@ -26,13 +26,21 @@ pub(super) async fn send_receipt(
     let authed = depot.authed_info()?;
     let sender_id = authed.user_id();
     let body = body.into_inner();
-    let thread_id = match &body.thread {
-        ReceiptThread::Thread(id) => Some(&**id),
-        _ => None,
-    };
 
     crate::user::ping_presence(sender_id, &PresenceState::Online).await?;
     let event_sn = crate::event::get_event_sn(&args.event_id).await?;
+    if let ReceiptThread::Thread(thread_id) = &body.thread
+        && *thread_id != args.event_id
+        && data::room::event_thread_id(&args.event_id).await?.as_ref() != Some(thread_id)
+    {
+        return Err(
+            MatrixError::invalid_param("Event is not related to the receipt's thread.").into(),
+        );
+    }
+    let receipt = Receipt {
+        ts: Some(UnixMillis::now()),
+        thread: body.thread.clone(),
+    };
     match args.receipt_type {
         ReceiptType::FullyRead => {
             let fully_read_event = FullyReadEvent {
@@ -51,13 +59,7 @@ pub(super) async fn send_receipt(
         }
         ReceiptType::Read => {
             let mut user_receipts = BTreeMap::new();
-            user_receipts.insert(
-                sender_id.to_owned(),
-                Receipt {
-                    ts: Some(UnixMillis::now()),
-                    thread: body.thread.clone(),
-                },
-            );
+            user_receipts.insert(sender_id.to_owned(), receipt);
             let mut receipts = BTreeMap::new();
             receipts.insert(ReceiptType::Read, user_receipts);
 
@@ -74,7 +76,7 @@ pub(super) async fn send_receipt(
                 true,
             )
             .await?;
-            push_action::remove_actions_until(sender_id, &args.room_id, event_sn, thread_id)
+            push_action::remove_actions_until(sender_id, &args.room_id, event_sn, &body.thread)
                 .await?;
         }
         ReceiptType::ReadPrivate => {
@@ -85,9 +87,10 @@ pub(super) async fn send_receipt(
                 sender_id,
                 &args.event_id,
                 event_sn,
+                &receipt,
             )
             .await?;
-            push_action::remove_actions_until(sender_id, &args.room_id, event_sn, thread_id)
+            push_action::remove_actions_until(sender_id, &args.room_id, event_sn, &body.thread)
                 .await?;
         }
         _ => return Err(AppError::internal("unsupported receipt type")),
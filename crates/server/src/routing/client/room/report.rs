@@ -0,0 +1,51 @@
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+
+use crate::core::client::room::ReportRoomReqBody;
+use crate::core::identifiers::*;
+use crate::utils::HtmlEscape;
+use crate::{AuthArgs, DepotExt, EmptyResult, MatrixError, config, data, empty_ok, room};
+
+/// #POST /_matrix/client/v3/rooms/{room_id}/report
+/// Reports a room as inappropriate to homeserver admins
+#[endpoint]
+pub(super) async fn report_room(
+    _aa: AuthArgs,
+    room_id: PathParam<OwnedRoomId>,
+    body: JsonBody<ReportRoomReqBody>,
+    depot: &mut Depot,
+) -> EmptyResult {
+    let authed = depot.authed_info()?;
+    let room_id = room_id.into_inner();
+    let reason = body.into_inner().reason;
+
+    if reason.as_ref().is_some_and(|s| s.chars().count() > 250) {
+        return Err(MatrixError::invalid_param(
+            "reason too long, should be 250 characters or fewer",
+        )
+        .into());
+    }
+    if !room::room_exists(&room_id).await? {
+        return Err(MatrixError::not_found("room not found").into());
+    }
+
+    let report_id = data::room::create_room_report(data::room::NewDbRoomReport::new(
+        room_id.clone(),
+        authed.user_id().to_owned(),
+        reason.clone(),
+    ))
+    .await?;
+
+    if config::get().admin.report_notices {
+        let _ = crate::admin::send_text(&format!(
+            "Room report #{report_id} received from {}\n\n\
+             Room ID: `{room_id}`\n\
+             Report Reason: {}",
+            authed.user_id(),
+            HtmlEscape(reason.as_deref().unwrap_or(""))
+        ))
+        .await;
+    }
+
+    empty_ok()
+}
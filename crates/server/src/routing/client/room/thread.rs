@@ -1,8 +1,12 @@
+use salvo::oapi::extract::*;
 use salvo::prelude::*;
 
-use crate::core::client::room::{ThreadsReqArgs, ThreadsResBody};
-use crate::room::state;
-use crate::{AuthArgs, DepotExt, JsonResult, json_ok};
+use crate::core::client::room::{
+    SetThreadSubscriptionReqBody, ThreadSubscriptionResBody, ThreadSubscriptionsChangesResBody,
+    ThreadsReqArgs, ThreadsResBody,
+};
+use crate::core::identifiers::*;
+use crate::{AuthArgs, DepotExt, EmptyResult, JsonResult, MatrixError, empty_ok, json_ok};
 
 /// #GET /_matrix/client/r0/rooms/{room_id}/threads
 #[endpoint]
@@ -26,24 +30,92 @@ pub(super) async fn list_threads(
         None
     };
 
-    let (events, next_batch) =
-        crate::room::thread::get_threads(&args.room_id, &args.include, limit, from).await?;
-
-    let mut threads = Vec::new();
-    for (sn, pdu) in events.into_iter() {
-        if state::user_can_see_event(authed.user_id(), &pdu.event_id)
-            .await
-            .unwrap_or(false)
-        {
-            threads.push((sn, pdu));
-        }
-    }
+    let (events, next_batch) = crate::room::thread::get_threads(
+        authed.user_id(),
+        &args.room_id,
+        &args.include,
+        limit,
+        from,
+    )
+    .await?;
 
     json_ok(ThreadsResBody {
-        chunk: threads
-            .into_iter()
-            .map(|(_, pdu)| pdu.to_room_event())
-            .collect(),
+        chunk: events.into_iter().map(|pdu| pdu.to_room_event()).collect(),
         next_batch: next_batch.map(|b| b.to_string()),
     })
 }
+
+/// #GET /_matrix/client/unstable/io.element.msc4306/rooms/{room_id}/thread/{thread_root}/subscription
+/// Gets the subscription state of the current user to a thread.
+#[endpoint]
+pub(crate) async fn get_thread_subscription(
+    _aa: AuthArgs,
+    room_id: PathParam<OwnedRoomId>,
+    thread_root: PathParam<OwnedEventId>,
+    depot: &mut Depot,
+) -> JsonResult<ThreadSubscriptionResBody> {
+    let authed = depot.authed_info()?;
+    let subscription =
+        crate::room::thread::get_subscription(authed.user_id(), &room_id, &thread_root)
+            .await?
+            .ok_or_else(|| MatrixError::not_found("Not subscribed to this thread."))?;
+    json_ok(ThreadSubscriptionResBody::new(subscription.automatic))
+}
+
+/// #PUT /_matrix/client/unstable/io.element.msc4306/rooms/{room_id}/thread/{thread_root}/subscription
+/// Subscribes the current user to a thread.
+#[endpoint]
+pub(crate) async fn set_thread_subscription(
+    _aa: AuthArgs,
+    room_id: PathParam<OwnedRoomId>,
+    thread_root: PathParam<OwnedEventId>,
+    body: JsonBody<SetThreadSubscriptionReqBody>,
+    depot: &mut Depot,
+) -> EmptyResult {
+    let authed = depot.authed_info()?;
+    let body = body.into_inner();
+    crate::room::thread::subscribe(
+        authed.user_id(),
+        &room_id,
+        &thread_root,
+        body.automatic.as_deref(),
+    )
+    .await?;
+    empty_ok()
+}
+
+/// #DELETE /_matrix/client/unstable/io.element.msc4306/rooms/{room_id}/thread/{thread_root}/subscription
+/// Unsubscribes the current user from a thread.
+#[endpoint]
+pub(crate) async fn delete_thread_subscription(
+    _aa: AuthArgs,
+    room_id: PathParam<OwnedRoomId>,
+    thread_root: PathParam<OwnedEventId>,
+    depot: &mut Depot,
+) -> EmptyResult {
+    let authed = depot.authed_info()?;
+    crate::room::thread::unsubscribe(authed.user_id(), &room_id, &thread_root).await?;
+    empty_ok()
+}
+
+/// #GET /_matrix/client/unstable/io.element.msc4308/thread_subscriptions
+/// Paginates backwards through the thread subscription changes of the current user.
+#[endpoint]
+pub(crate) async fn list_thread_subscriptions(
+    _aa: AuthArgs,
+    from: QueryParam<String, false>,
+    to: QueryParam<String, false>,
+    limit: QueryParam<i64, false>,
+    depot: &mut Depot,
+) -> JsonResult<ThreadSubscriptionsChangesResBody> {
+    let authed = depot.authed_info()?;
+    let from = from
+        .into_inner()
+        .map(|from| from.parse::<i64>())
+        .transpose()?;
+    let to = to.into_inner().map(|to| to.parse::<i64>()).transpose()?;
+    let limit = limit.into_inner().unwrap_or(100).clamp(1, 100);
+
+    let body = crate::room::thread::subscription_changes(authed.user_id(), from, to, limit).await?;
+    json_ok(body)
+}
@@ -51,7 +51,7 @@ pub(super) async fn sync_events_v3(
     let sender_id = authed.user_id();
     let device_id = authed.device_id();
 
-    crate::user::ping_presence(sender_id, &args.set_presence).await?;
+    crate::user::sync_presence(sender_id, &args.set_presence).await?;
     let mut body = crate::sync_v3::sync_events(sender_id, device_id, &args).await?;
 
     if !args.full_state
@@ -2,6 +2,7 @@ use salvo::prelude::*;
 
 use crate::core::MatrixError;
 use crate::core::client::discovery::rendezvous::DiscoverRendezvousResBody;
+use crate::routing::openapi;
 use crate::{JsonResult, config, hoops, json_ok};
 
 pub(super) fn router() -> Router {
@@ -20,6 +21,7 @@ pub(super) fn router() -> Router {
             Router::new()
                 .hoop(hoops::limit_rate)
                 .hoop(hoops::auth_by_access_token)
+                .oapi_security(openapi::access_token())
                 .push(
                     Router::with_path(
                         "org.matrix.msc3391/user/{user_id}/account_data/{account_type}",
@@ -47,6 +49,18 @@ pub(super) fn router() -> Router {
                                 .post(super::to_device::for_dehydrated_legacy),
                         ),
                 )
+                .push(
+                    Router::with_path(
+                        "io.element.msc4306/rooms/{room_id}/thread/{thread_root}/subscription",
+                    )
+                    .get(super::room::thread::get_thread_subscription)
+                    .put(super::room::thread::set_thread_subscription)
+                    .delete(super::room::thread::delete_thread_subscription),
+                )
+                .push(
+                    Router::with_path("io.element.msc4308/thread_subscriptions")
+                        .get(super::room::thread::list_thread_subscriptions),
+                )
                 .push(
                     Router::with_path("im.nheko.summary/rooms/{room_id_or_alias}/summary")
                         .get(super::room::summary::get_summary_msc_3266),
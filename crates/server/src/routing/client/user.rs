@@ -1,11 +1,13 @@
 mod account;
 mod filter;
 mod openid;
+mod report;
 mod room;
 
 use salvo::prelude::*;
 
 use crate::hoops;
+use crate::routing::openapi;
 
 pub fn authed_router() -> Router {
     Router::with_path("user")
@@ -36,10 +38,17 @@ pub fn authed_router() -> Router {
         )
 }
 
+pub fn users_authed_router() -> Router {
+    Router::with_path("users/{user_id}")
+        .hoop(hoops::limit_rate)
+        .push(Router::with_path("report").post(report::report_user))
+}
+
 pub fn stable_v1_router() -> Router {
     Router::with_hoop(hoops::limit_rate).push(
         Router::with_path("mutual_rooms")
             .hoop(hoops::auth_by_access_token_without_query_masquerade)
+            .oapi_security(openapi::access_token())
             .get(room::get_mutual_rooms_v1),
     )
 }
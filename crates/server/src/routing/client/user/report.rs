@@ -0,0 +1,53 @@
+use salvo::oapi::extract::*;
+use salvo::prelude::*;
+
+use crate::core::client::user::ReportUserReqBody;
+use crate::core::identifiers::*;
+use crate::utils::HtmlEscape;
+use crate::{
+    AuthArgs, DepotExt, EmptyResult, IsRemoteOrLocal, MatrixError, config, data, empty_ok,
+};
+
+/// #POST /_matrix/client/v3/users/{user_id}/report
+/// Reports a user as inappropriate to homeserver admins
+#[endpoint]
+pub(super) async fn report_user(
+    _aa: AuthArgs,
+    user_id: PathParam<OwnedUserId>,
+    body: JsonBody<ReportUserReqBody>,
+    depot: &mut Depot,
+) -> EmptyResult {
+    let authed = depot.authed_info()?;
+    let user_id = user_id.into_inner();
+    let reason = body.into_inner().reason;
+
+    if reason.as_ref().is_some_and(|s| s.chars().count() > 250) {
+        return Err(MatrixError::invalid_param(
+            "reason too long, should be 250 characters or fewer",
+        )
+        .into());
+    }
+    if user_id.is_local() && !data::user::user_exists(&user_id).await? {
+        return Err(MatrixError::not_found("user not found").into());
+    }
+
+    let report_id = data::user::create_user_report(data::user::NewDbUserReport::new(
+        user_id.clone(),
+        authed.user_id().to_owned(),
+        reason.clone(),
+    ))
+    .await?;
+
+    if config::get().admin.report_notices {
+        let _ = crate::admin::send_text(&format!(
+            "User report #{report_id} received from {}\n\n\
+             Reported User: {user_id}\n\
+             Report Reason: {}",
+            authed.user_id(),
+            HtmlEscape(reason.as_deref().unwrap_or(""))
+        ))
+        .await;
+    }
+
+    empty_ok()
+}
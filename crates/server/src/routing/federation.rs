@@ -20,12 +20,14 @@ use salvo::prelude::*;
 
 use crate::core::directory::Server;
 use crate::core::federation::directory::ServerVersionResBody;
+use crate::routing::openapi;
 use crate::{AppError, AppResult, AuthArgs, JsonResult, config, hoops, json_ok};
 
 pub fn router() -> Router {
     Router::with_path("federation")
         .hoop(check_federation_enabled)
         .hoop(hoops::auth_by_access_token_or_signatures)
+        .oapi_securities([openapi::access_token(), openapi::x_matrix()])
         .oapi_tag("federation")
         .push(
             Router::with_path("v2")
@@ -115,8 +115,9 @@ async fn timestamp_to_event(
     let origin = depot.origin()?;
     crate::federation::access_check(origin, &args.room_id, None).await?;
 
+    // Only local history is searched: the requesting server asks the others itself.
     let (event_id, origin_server_ts) =
-        crate::event::get_event_for_timestamp(&args.room_id, args.ts, args.dir).await?;
+        crate::event::get_local_event_for_timestamp(&args.room_id, args.ts, args.dir).await?;
     json_ok(TimestampToEventResBody {
         event_id,
         origin_server_ts,
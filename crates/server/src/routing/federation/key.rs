@@ -13,7 +13,7 @@ use crate::core::federation::discovery::{ServerSigningKeys, VerifyKey};
 use crate::core::serde::{Base64, CanonicalJsonObject};
 use crate::core::signatures::Ed25519KeyPair;
 use crate::core::{OwnedServerName, OwnedServerSigningKeyId, ServerName, UnixMillis};
-use crate::{AppResult, AuthArgs, JsonResult, config, json_ok};
+use crate::{AppResult, AuthArgs, IsRemoteOrLocal, JsonResult, config, json_ok};
 
 pub fn router() -> Router {
     Router::with_path("key").oapi_tag("federation").push(
@@ -41,6 +41,11 @@ async fn fetch_signing_keys(
     server: &OwnedServerName,
     minimum_valid_until_ts: UnixMillis,
 ) -> Option<ServerSigningKeys> {
+    // Our own keys are always at hand, retired ones included.
+    if !server.is_remote() {
+        return own_server_keys().ok();
+    }
+
     // Try local cache first
     if let Ok(cached) = crate::server_key::signing_keys_for(server).await
         && cached.valid_until_ts >= minimum_valid_until_ts
@@ -98,7 +103,7 @@ async fn query_keys_batch(
         if let Some(keys) = fetch_signing_keys(server, min_valid).await {
             result_keys.push(sign_server_keys_for_notary(
                 &conf.server_name,
-                config::keypair(),
+                &config::keypair(),
                 keys,
             )?);
         }
@@ -122,7 +127,7 @@ async fn query_keys_from_server(
     if let Some(keys) = fetch_signing_keys(&args.server_name, args.minimum_valid_until_ts).await {
         result_keys.push(sign_server_keys_for_notary(
             &conf.server_name,
-            config::keypair(),
+            &config::keypair(),
             keys,
         )?);
     }
@@ -138,20 +143,31 @@ async fn query_keys_from_server(
 // Response type for this endpoint is Json because we need to calculate a signature for the response
 #[endpoint]
 async fn server_signing_keys(_aa: AuthArgs) -> JsonResult<ServerKeysResBody> {
+    json_ok(ServerKeysResBody::new(own_server_keys()?))
+}
+
+/// The signing keys of this server, including the retired ones, signed by the active one.
+fn own_server_keys() -> AppResult<ServerSigningKeys> {
     let conf = crate::config::get();
+    let own_keys = crate::signing_keys::own_keys();
     let mut verify_keys: BTreeMap<OwnedServerSigningKeyId, VerifyKey> = BTreeMap::new();
     verify_keys.insert(
-        format!("ed25519:{}", config::keypair().version())
+        format!("ed25519:{}", own_keys.keypair.version())
             .try_into()
             .expect("found invalid server signing keys in DB"),
         VerifyKey {
-            key: Base64::new(config::keypair().public_key().to_vec()),
+            key: Base64::new(own_keys.keypair.public_key().to_vec()),
         },
     );
+    let old_verify_keys = own_keys
+        .old_verify_keys
+        .iter()
+        .filter_map(|(key_id, old)| Some((key_id.as_str().try_into().ok()?, old.clone())))
+        .collect();
     let server_keys = ServerSigningKeys {
         server_name: conf.server_name.clone(),
         verify_keys,
-        old_verify_keys: BTreeMap::new(),
+        old_verify_keys,
         signatures: BTreeMap::new(),
         valid_until_ts: UnixMillis::from_system_time(
             SystemTime::now() + Duration::from_secs(86400 * 7),
@@ -163,13 +179,10 @@ async fn server_signing_keys(_aa: AuthArgs) -> JsonResult<ServerKeysResBody> {
 
     crate::core::signatures::sign_json(
         conf.server_name.as_str(),
-        config::keypair(),
+        &*own_keys.keypair,
         &mut server_keys,
     )?;
-    let server_keys: ServerSigningKeys =
-        serde_json::from_slice(&serde_json::to_vec(&server_keys).unwrap())?;
-
-    json_ok(ServerKeysResBody::new(server_keys))
+    Ok(serde_json::from_slice(&serde_json::to_vec(&server_keys)?)?)
 }
 
 #[cfg(test)]
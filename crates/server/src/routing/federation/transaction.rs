@@ -156,10 +156,12 @@ async fn process_edu_presence(origin: &ServerName, presence: PresenceContent) {
                 user_id: update.user_id.clone(),
                 stream_id: None,
                 state: Some(update.presence.to_string()),
-                last_active_at: Some(UnixMillis::now()),
-                last_federation_update_at: None,
+                last_active_at: Some(UnixMillis(
+                    UnixMillis::now().0.saturating_sub(update.last_active_ago),
+                )),
+                last_federation_update_at: Some(UnixMillis::now()),
                 last_user_sync_at: None,
-                currently_active: None,
+                currently_active: Some(update.currently_active),
                 occur_sn: None,
                 status_msg: update.status_msg.clone(),
             },
@@ -283,6 +285,14 @@ async fn process_edu_device_list_update(origin: &ServerName, content: DeviceList
         return;
     }
 
+    // Updates for users we no longer share a room with are not tracked.
+    if crate::user::untrack_remote_device_list(&user_id)
+        .await
+        .unwrap_or(false)
+    {
+        return;
+    }
+
     let _ = crate::user::mark_device_key_update(&user_id, &device_id).await;
 }
 
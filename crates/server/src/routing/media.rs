@@ -2,6 +2,7 @@ use salvo::prelude::*;
 
 use super::client::media::*;
 use crate::config::MediaConfig;
+use crate::routing::openapi;
 use crate::{config, hoops};
 
 fn legacy_media_enabled(media: &MediaConfig) -> bool {
@@ -26,6 +27,7 @@ pub fn router() -> Router {
         // only withholds the outbound-fetching `preview_url` endpoint.
         let mut authed_routes = Router::with_path(v)
             .hoop(hoops::auth_by_access_token)
+            .oapi_security(openapi::access_token())
             .push(Router::with_path("create").post(create_mxc_uri))
             .push(
                 Router::with_path("upload")
@@ -0,0 +1,103 @@
+//! OpenAPI document of the client, federation and admin APIs.
+use std::sync::LazyLock;
+
+use salvo::oapi::scalar::Scalar;
+use salvo::oapi::security::{ApiKey, ApiKeyValue, Http, HttpAuthScheme};
+use salvo::oapi::swagger_ui::SwaggerUi;
+use salvo::oapi::{OpenApi, SecurityRequirement, SecurityScheme};
+
+use crate::config::OpenApiAdminAccess;
+use crate::routing::prelude::*;
+
+/// Security scheme of routes authenticated by a client access token.
+pub const ACCESS_TOKEN: &str = "access_token";
+/// Security scheme of routes authenticated by a federation `X-Matrix` signature.
+pub const X_MATRIX: &str = "x_matrix";
+/// Security scheme of the MAS admin routes, authenticated by `admin.mas_secret`.
+pub const MAS_SECRET: &str = "mas_secret";
+
+const DOC_PATH: &str = "/api-doc/openapi.json";
+
+// The routers are only walked once, the first time each document is asked for.
+static PUBLIC_DOC: LazyLock<OpenApi> = LazyLock::new(|| document(false));
+static FULL_DOC: LazyLock<OpenApi> = LazyLock::new(|| document(true));
+
+pub fn access_token() -> SecurityRequirement {
+    SecurityRequirement::new(ACCESS_TOKEN, Vec::<String>::new())
+}
+
+pub fn x_matrix() -> SecurityRequirement {
+    SecurityRequirement::new(X_MATRIX, Vec::<String>::new())
+}
+
+pub fn mas_secret() -> SecurityRequirement {
+    SecurityRequirement::new(MAS_SECRET, Vec::<String>::new())
+}
+
+pub fn router() -> Router {
+    let mut router =
+        Router::new().push(Router::with_path("api-doc/openapi.json").get(openapi_json));
+    if config::get().openapi.ui {
+        router = router
+            .push(
+                Scalar::new(DOC_PATH)
+                    .title("Palpo - Scalar")
+                    .into_router("/scalar"),
+            )
+            .push(SwaggerUi::new(DOC_PATH).into_router("/swagger-ui"));
+    }
+    router
+}
+
+fn document(with_admin: bool) -> OpenApi {
+    let doc = OpenApi::new("Palpo API", env!("CARGO_PKG_VERSION"))
+        .add_security_scheme(
+            ACCESS_TOKEN,
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer).description(
+                "Access token of a user or an application service, sent as a bearer token.",
+            )),
+        )
+        .add_security_scheme(
+            X_MATRIX,
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::with_description(
+                "Authorization",
+                "Request signature of a homeserver: \
+                 `X-Matrix origin=\"...\",destination=\"...\",key=\"...\",sig=\"...\"`.",
+            ))),
+        )
+        .merge_router(&super::matrix_router());
+    if with_admin {
+        let doc = doc.add_security_scheme(
+            MAS_SECRET,
+            SecurityScheme::Http(Http::new(HttpAuthScheme::Bearer).description(
+                "Secret shared with the Matrix Authentication Service (`admin.mas_secret`), \
+                 sent as a bearer token.",
+            )),
+        );
+        doc.merge_router(&super::admin::router())
+    } else {
+        doc
+    }
+}
+
+/// Serves the document, with the admin API included as configured in `admin_api`.
+///
+/// Authentication is optional here: a missing or invalid access token only leaves
+/// the admin API out.
+#[handler]
+async fn openapi_json(aa: AuthArgs, depot: &mut Depot, res: &mut Response) {
+    let with_admin = match config::get().openapi.admin_api {
+        OpenApiAdminAccess::Everyone => true,
+        OpenApiAdminAccess::Nobody => false,
+        OpenApiAdminAccess::Admins => {
+            aa.uses_access_token()
+                && hoops::auth_by_access_token_inner(aa, depot).await.is_ok()
+                && depot.authed_info().is_ok_and(|authed| authed.is_admin())
+        }
+    };
+    if with_admin {
+        res.render(Json(&*FULL_DOC));
+    } else {
+        res.render(Json(&*PUBLIC_DOC));
+    }
+}
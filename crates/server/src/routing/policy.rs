@@ -4,12 +4,14 @@ use salvo::prelude::*;
 use crate::core::federation::policy::sign_event::{PolicySignEventReqBody, PolicySignEventResBody};
 use crate::core::serde::CanonicalJsonObject;
 use crate::core::signatures::KeyPair;
+use crate::routing::openapi;
 use crate::{AppError, AppResult, AuthArgs, JsonResult, MatrixError, config, hoops, json_ok};
 
 pub fn router() -> Router {
     Router::with_path("policy")
         .hoop(check_policy_server_enabled)
         .hoop(hoops::auth_by_signatures)
+        .oapi_security(openapi::x_matrix())
         .oapi_tag("policy")
         .push(Router::with_path("v1/sign").post(sign_event))
 }
@@ -1,3 +1,4 @@
+use std::collections::{HashMap, HashSet};
 use std::fmt::Debug;
 use std::sync::{Arc, OnceLock, RwLock};
 use std::time::{Duration, Instant};
@@ -20,17 +21,21 @@ use crate::core::federation::transaction::{
     Edu, SendMessageReqBody, SendMessageResBody, send_message_request,
 };
 use crate::core::identifiers::*;
+use crate::core::push::Pusher;
 pub use crate::core::sending::*;
 use crate::core::serde::{CanonicalJsonObject, RawJsonValue};
 use crate::data::connect;
 use crate::data::schema::*;
 use crate::data::sending::{DbOutgoingRequest, NewDbOutgoingRequest};
 use crate::room::timeline;
-use crate::{AppError, AppResult, GetUrlOrigin, ServerConfig, TlsNameMap, config, data, utils};
+use crate::{
+    AppError, AppResult, GetUrlOrigin, ServerConfig, SnPduEvent, TlsNameMap, config, data, utils,
+};
 
 mod dest;
 pub use dest::*;
 pub mod guard;
+pub mod lease;
 pub mod resolver;
 
 const SELECT_PRESENCE_LIMIT: usize = 256;
@@ -43,6 +48,18 @@ const DEQUEUE_LIMIT: usize = 48;
 /// when draining a backlog.
 const QUEUED_REQUEST_LIMIT: usize = 30;
 
+/// Failed attempts after which a federation destination is caught up instead
+/// of being sent its whole backlog; the backoff has reached about 45 minutes.
+const CATCH_UP_AFTER_TRIES: u32 = 6;
+
+/// First retry delay of a failing push gateway, doubled on every failure.
+const PUSH_INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest delay between retries of a failing push gateway.
+const PUSH_MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+/// How long a pusher may keep failing before its pending notifications are
+/// dropped.
+const PUSH_GIVE_UP_AFTER: Duration = Duration::from_secs(60 * 60 * 24);
+
 const EDU_BUF_CAP: usize = 128;
 const EDU_VEC_CAP: usize = 1;
 
@@ -137,6 +154,16 @@ impl OutgoingKind {
             OutgoingKind::Normal(_) => "normal",
         }
     }
+
+    /// Key of the queue in `sending_leases`.
+    pub fn queue_key(&self) -> String {
+        match self {
+            // User IDs never contain whitespace, pushkeys may contain anything.
+            OutgoingKind::Push(user_id, pushkey) => format!("push:{user_id} {pushkey}"),
+            OutgoingKind::Appservice(id) => format!("appservice:{id}"),
+            OutgoingKind::Normal(server_name) => format!("normal:{server_name}"),
+        }
+    }
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -184,8 +211,6 @@ pub fn federation_client() -> ClientWithMiddleware {
     FEDERATION_CLIENT
         .get_or_init(|| {
             let conf = crate::config::get();
-            // Client is cheap to clone (Arc wrapper) and avoids lifetime issues
-            let tls_name_override = Arc::new(RwLock::new(TlsNameMap::new()));
 
             let retry_policy = ExponentialBackoff::builder()
                 .retry_bounds(
@@ -197,7 +222,7 @@ pub fn federation_client() -> ClientWithMiddleware {
             let client = reqwest_client_builder(conf)
                 .expect("build reqwest client failed")
                 .dns_resolver(Arc::new(resolver::Resolver::new_with_cidr_denylist(
-                    tls_name_override,
+                    crate::TLS_NAME_OVERRIDE.clone(),
                 )))
                 .timeout(Duration::from_secs(2 * 60))
                 .build()
@@ -511,49 +536,59 @@ async fn send_events(
                 }
             }
 
-            for pdu in pdus {
-                // Redacted events are not notification targets (we don't send push for them)
-                if pdu.unsigned.contains_key("redacted_because") {
-                    continue;
-                }
-                let pusher = match data::user::pusher::get_pusher(user_id, pushkey)
-                    .await
-                    .map_err(|e| {
-                        (
-                            OutgoingKind::Push(user_id.clone(), pushkey.clone()),
-                            e.into(),
-                        )
-                    })? {
-                    Some(pusher) => pusher,
-                    None => continue,
-                };
-
-                let rules_for_user = crate::user::get_push_rules(user_id)
-                    .await
-                    .map_err(|e| (kind.clone(), e))?
-                    .global;
+            let pusher = match data::user::pusher::get_pusher(user_id, pushkey).await {
+                Ok(Some(pusher)) if pusher.enabled => pusher,
+                // Removed or disabled since the notifications were queued.
+                Ok(_) => return Ok(kind.clone()),
+                Err(e) => return Err((kind.clone(), e.into())),
+            };
 
-                let notify_summary = crate::room::user::notify_summary(user_id, &pdu.room_id)
+            match send_push_pdus(user_id, &pusher, pdus).await {
+                Ok(rejected) if is_pushkey_rejected(&rejected, pushkey) => {
+                    // The gateway will never deliver to this pushkey again,
+                    // the spec requires removing its pusher.
+                    warn!(%user_id, pushkey, "push gateway rejected pushkey, removing the pusher");
+                    data::user::pusher::delete_pusher(user_id, &pusher.ids.app_id, pushkey)
+                        .await
+                        .map_err(|e| (kind.clone(), e.into()))?;
+                    delete_all_requests_for(&kind)
+                        .await
+                        .map_err(|e| (kind.clone(), e))?;
+                    Ok(kind.clone())
+                }
+                Ok(_) => {
+                    if let Err(e) = data::user::pusher::mark_pusher_success(user_id, pushkey).await
+                    {
+                        error!(%user_id, pushkey, error = ?e, "failed to record pusher success");
+                    }
+                    Ok(kind.clone())
+                }
+                Err(e) => {
+                    let failing_since = match data::user::pusher::mark_pusher_failure(
+                        user_id, pushkey,
+                    )
                     .await
-                    .map_err(|e| (kind.clone(), e))?;
-
-                let max_request = crate::sending::max_request();
-                let permit = max_request.acquire().await;
-
-                let _response = crate::user::pusher::send_push_notice(
-                    user_id,
-                    notify_summary.all_unread_count(),
-                    &pusher,
-                    rules_for_user,
-                    &pdu,
-                )
-                .await
-                .map(|_response| kind.clone())
-                .map_err(|e| (kind.clone(), e));
-
-                drop(permit);
+                    {
+                        Ok(failing_since) => failing_since,
+                        Err(e) => {
+                            error!(%user_id, pushkey, error = ?e, "failed to record pusher failure");
+                            None
+                        }
+                    };
+                    if should_give_up_push(failing_since, UnixMillis::now()) {
+                        // Like Synapse, drop these notifications rather than
+                        // holding the later ones back any longer.
+                        warn!(%user_id, pushkey, error = ?e, "giving up on push notifications");
+                        if let Err(e) =
+                            data::user::pusher::reset_pusher_failure(user_id, pushkey).await
+                        {
+                            error!(%user_id, pushkey, error = ?e, "failed to reset pusher failure");
+                        }
+                        return Ok(kind.clone());
+                    }
+                    Err((kind.clone(), e))
+                }
             }
-            Ok(OutgoingKind::Push(user_id.clone(), pushkey.clone()))
         }
         OutgoingKind::Normal(server) => {
             let mut edu_jsons = Vec::new();
@@ -681,57 +716,6 @@ where
     Ok(response.json().await?)
 }
 
-async fn active_requests() -> AppResult<Vec<(i64, OutgoingKind, SendingEventType)>> {
-    let outgoing_requests = outgoing_requests::table
-        .filter(outgoing_requests::state.eq("pending"))
-        .load::<DbOutgoingRequest>(&mut connect().await?)
-        .await?;
-    Ok(outgoing_requests
-        .into_iter()
-        .filter_map(|item| {
-            let kind = match item.kind.as_str() {
-                "appservice" => {
-                    if let Some(appservice_id) = &item.appservice_id {
-                        OutgoingKind::Appservice(appservice_id.clone())
-                    } else {
-                        return None;
-                    }
-                }
-                "push" => {
-                    if let (Some(user_id), Some(pushkey)) =
-                        (item.user_id.clone(), item.pushkey.clone())
-                    {
-                        OutgoingKind::Push(user_id, pushkey)
-                    } else {
-                        return None;
-                    }
-                }
-                "normal" => {
-                    if let Some(server_id) = &item.server_id {
-                        OutgoingKind::Normal(server_id.to_owned())
-                    } else {
-                        return None;
-                    }
-                }
-                _ => return None,
-            };
-            let event = if let Some(value) = item.edu_json {
-                SendingEventType::Edu(value)
-            } else {
-                SendingEventType::Pdu(item.pdu_id?)
-            };
-            Some((item.id, kind, event))
-        })
-        .collect())
-}
-
-async fn delete_request(id: i64) -> AppResult<()> {
-    diesel::delete(outgoing_requests::table.find(id))
-        .execute(&mut connect().await?)
-        .await?;
-    Ok(())
-}
-
 async fn delete_all_active_requests_for(outgoing_kind: &OutgoingKind) -> AppResult<()> {
     match outgoing_kind {
         OutgoingKind::Appservice(appservice_id) => {
@@ -855,6 +839,7 @@ async fn active_requests_for(
     let mut query = outgoing_requests::table
         .filter(outgoing_requests::kind.eq(outgoing_kind.name()))
         .filter(outgoing_requests::state.eq("pending"))
+        .order_by(outgoing_requests::id.asc())
         .into_boxed();
 
     // Add specific filters based on OutgoingKind
@@ -890,12 +875,187 @@ async fn active_requests_for(
     Ok(list)
 }
 
+/// Notifies `pusher` of `pdus`, stopping at the first failure. Returns the
+/// pushkeys the push gateway rejected.
+async fn send_push_pdus(
+    user_id: &UserId,
+    pusher: &Pusher,
+    pdus: Vec<SnPduEvent>,
+) -> AppResult<Vec<String>> {
+    let mut rejected = Vec::new();
+    for pdu in pdus {
+        // Redacted events are not notification targets (we don't send push for them)
+        if pdu.unsigned.contains_key("redacted_because") {
+            continue;
+        }
+        let rules_for_user = crate::user::get_push_rules(user_id).await?.global;
+        let notify_summary = crate::room::user::notify_summary(user_id, &pdu.room_id).await?;
+
+        let max_request = max_request();
+        let permit = max_request.acquire().await;
+        let result = crate::user::pusher::send_push_notice(
+            user_id,
+            notify_summary.all_unread_count(),
+            pusher,
+            rules_for_user,
+            &pdu,
+        )
+        .await;
+        drop(permit);
+
+        rejected.extend(result?);
+        if is_pushkey_rejected(&rejected, &pusher.ids.pushkey) {
+            break;
+        }
+        // Delivered: a retry after a later failure in this page must not
+        // notify about it again.
+        delete_active_push_request(user_id, &pusher.ids.pushkey, &pdu.event_id).await?;
+    }
+    Ok(rejected)
+}
+
+/// Whether the push gateway rejected `pushkey`, so its pusher must be removed.
+fn is_pushkey_rejected(rejected: &[String], pushkey: &str) -> bool {
+    rejected.iter().any(|rejected| rejected == pushkey)
+}
+
+/// Whether the pending notifications of a pusher failing since `failing_since`
+/// are given up on.
+fn should_give_up_push(failing_since: Option<i64>, now: UnixMillis) -> bool {
+    let give_up_before = now.get() as i64 - PUSH_GIVE_UP_AFTER.as_millis() as i64;
+    failing_since.is_some_and(|since| since < give_up_before)
+}
+
+async fn delete_active_push_request(
+    user_id: &UserId,
+    pushkey: &str,
+    event_id: &EventId,
+) -> AppResult<()> {
+    diesel::delete(
+        outgoing_requests::table
+            .filter(outgoing_requests::kind.eq("push"))
+            .filter(outgoing_requests::state.eq("pending"))
+            .filter(outgoing_requests::user_id.eq(user_id.as_str()))
+            .filter(outgoing_requests::pushkey.eq(pushkey))
+            .filter(outgoing_requests::pdu_id.eq(event_id.as_str())),
+    )
+    .execute(&mut connect().await?)
+    .await?;
+    Ok(())
+}
+
+/// Active requests of a destination to send again, at most one transaction
+/// worth, oldest first.
+///
+/// Active requests beyond that, for example an entire backlog claimed by an
+/// older version, are returned to the queue and sent in later transactions.
+async fn active_page_for(outgoing_kind: &OutgoingKind) -> AppResult<Vec<SendingEventType>> {
+    let mut requests = active_requests_for(outgoing_kind).await?;
+    if requests.len() > QUEUED_REQUEST_LIMIT {
+        let requeued = requests
+            .split_off(QUEUED_REQUEST_LIMIT)
+            .into_iter()
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+        diesel::update(outgoing_requests::table.filter(outgoing_requests::id.eq_any(&requeued)))
+            .set(outgoing_requests::state.eq("created"))
+            .execute(&mut connect().await?)
+            .await?;
+    }
+    Ok(requests.into_iter().map(|(_, event)| event).collect())
+}
+
+/// Reduces the PDU backlog of a destination that was unreachable for a while
+/// to the latest PDU of each room, like Synapse's catch-up mode.
+///
+/// Once it receives them, the destination fetches the events in between with
+/// `get_missing_events`, instead of us replaying the whole outage. EDUs are
+/// kept. Returns the number of PDUs dropped from the backlog.
+async fn catch_up(server_name: &ServerName) -> AppResult<usize> {
+    let requests = outgoing_requests::table
+        .filter(outgoing_requests::kind.eq("normal"))
+        .filter(outgoing_requests::server_id.eq(server_name.as_str()))
+        .filter(outgoing_requests::pdu_id.is_not_null())
+        // Active requests belong to a transaction in flight or being retried.
+        .filter(outgoing_requests::state.ne("pending"))
+        .select((
+            outgoing_requests::id,
+            outgoing_requests::pdu_id.assume_not_null(),
+        ))
+        .load::<(i64, OwnedEventId)>(&mut connect().await?)
+        .await?;
+    if requests.len() <= QUEUED_REQUEST_LIMIT {
+        return Ok(0);
+    }
+
+    let event_ids = requests
+        .iter()
+        .map(|(_, event_id)| event_id.as_str())
+        .collect::<Vec<_>>();
+    let positions = events::table
+        .filter(events::id.eq_any(&event_ids))
+        .select((events::id, events::room_id, events::sn))
+        .load::<(OwnedEventId, OwnedRoomId, i64)>(&mut connect().await?)
+        .await?
+        .into_iter()
+        .map(|(event_id, room_id, sn)| (event_id, (room_id, sn)))
+        .collect::<HashMap<_, _>>();
+
+    let dropped = superseded_requests(&requests, &positions);
+    // Rows claimed since they were loaded are left to their transaction.
+    let dropped = diesel::delete(
+        outgoing_requests::table
+            .filter(outgoing_requests::id.eq_any(&dropped))
+            .filter(outgoing_requests::state.ne("pending")),
+    )
+    .execute(&mut connect().await?)
+    .await?;
+    Ok(dropped)
+}
+
+/// Requests whose PDU is followed by a later queued PDU in the same room.
+///
+/// `positions` maps event ids to their room and sequence number. Requests for
+/// unknown events are kept, `send_events` reports them.
+fn superseded_requests(
+    requests: &[(i64, OwnedEventId)],
+    positions: &HashMap<OwnedEventId, (OwnedRoomId, i64)>,
+) -> Vec<i64> {
+    // Request id of the latest PDU of each room.
+    let mut latest = HashMap::<&RoomId, (i64, i64)>::new();
+    for (id, event_id) in requests {
+        let Some((room_id, sn)) = positions.get(event_id) else {
+            continue;
+        };
+        let entry = latest.entry(room_id).or_insert((*sn, *id));
+        if *sn > entry.0 {
+            *entry = (*sn, *id);
+        }
+    }
+    let kept = latest.values().map(|(_, id)| *id).collect::<HashSet<_>>();
+    requests
+        .iter()
+        .filter(|(id, event_id)| positions.contains_key(event_id) && !kept.contains(id))
+        .map(|(id, _)| *id)
+        .collect()
+}
+
 /// Distinct destinations that still have queued (not yet active) requests.
 ///
 /// The periodic sweep in the sending guard uses this to recover requests
 /// whose wakeup was dropped because the wakeup queue was full, as well as
 /// requests left queued by a previous process.
 async fn queued_kinds() -> AppResult<Vec<OutgoingKind>> {
+    request_kinds(false).await
+}
+
+/// Distinct destinations with active requests, which an instance claimed and
+/// may not have finished sending before it stopped.
+async fn active_kinds() -> AppResult<Vec<OutgoingKind>> {
+    request_kinds(true).await
+}
+
+async fn request_kinds(active: bool) -> AppResult<Vec<OutgoingKind>> {
     type Row = (
         String,
         Option<String>,
@@ -903,8 +1063,16 @@ async fn queued_kinds() -> AppResult<Vec<OutgoingKind>> {
         Option<String>,
         Option<OwnedServerName>,
     );
-    let rows = outgoing_requests::table
-        .filter(outgoing_requests::state.ne("pending"))
+    let query = if active {
+        outgoing_requests::table
+            .filter(outgoing_requests::state.eq("pending"))
+            .into_boxed()
+    } else {
+        outgoing_requests::table
+            .filter(outgoing_requests::state.ne("pending"))
+            .into_boxed()
+    };
+    let rows = query
         .select((
             outgoing_requests::kind,
             outgoing_requests::appservice_id,
@@ -1122,6 +1290,47 @@ mod tests {
         assert!(notify_sender(&sender, kind).is_err());
     }
 
+    #[test]
+    fn catch_up_keeps_the_latest_pdu_of_each_room() {
+        let event = |id: &str| OwnedEventId::try_from(format!("${id}")).unwrap();
+        let room_a = OwnedRoomId::try_from("!a:palpo.example").unwrap();
+        let room_b = OwnedRoomId::try_from("!b:palpo.example").unwrap();
+        let requests = vec![
+            (1, event("a1")),
+            (2, event("b1")),
+            (3, event("a2")),
+            (4, event("unknown")),
+            (5, event("a3")),
+        ];
+        let positions = HashMap::from([
+            (event("a1"), (room_a.clone(), 10)),
+            (event("b1"), (room_b, 11)),
+            (event("a2"), (room_a.clone(), 12)),
+            (event("a3"), (room_a, 13)),
+        ]);
+
+        assert_eq!(superseded_requests(&requests, &positions), vec![1, 3]);
+    }
+
+    #[test]
+    fn rejected_pushkey_removes_only_its_pusher() {
+        let rejected = vec!["other".to_owned(), "mine".to_owned()];
+        assert!(is_pushkey_rejected(&rejected, "mine"));
+        assert!(!is_pushkey_rejected(&rejected, "unrelated"));
+        assert!(!is_pushkey_rejected(&[], "mine"));
+    }
+
+    #[test]
+    fn failing_pusher_is_given_up_after_a_day() {
+        let now = UnixMillis(PUSH_GIVE_UP_AFTER.as_millis() as u64 * 2);
+        let day = PUSH_GIVE_UP_AFTER.as_millis() as i64;
+        let now_ms = now.get() as i64;
+
+        assert!(!should_give_up_push(None, now));
+        assert!(!should_give_up_push(Some(now_ms - day + 1000), now));
+        assert!(should_give_up_push(Some(now_ms - day - 1000), now));
+    }
+
     #[test]
     fn outbound_federation_target_respects_self_allow_and_deny_rules() {
         let own_server = OwnedServerName::try_from("palpo.example").unwrap();
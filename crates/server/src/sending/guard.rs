@@ -6,9 +6,11 @@ use std::time::{Duration, Instant};
 use futures_util::stream::{FuturesUnordered, StreamExt};
 use tokio::sync::mpsc;
 
+use super::lease::{Leases, RENEW_INTERVAL};
 use super::{
-    EduBuf, EduVec, MPSC_SENDER, OutgoingKind, SELECT_EDU_LIMIT, SELECT_PRESENCE_LIMIT,
-    SELECT_RECEIPT_LIMIT, SendingEventType, TransactionStatus,
+    CATCH_UP_AFTER_TRIES, EduBuf, EduVec, MPSC_SENDER, OutgoingKind, PUSH_INITIAL_BACKOFF,
+    PUSH_MAX_BACKOFF, SELECT_EDU_LIMIT, SELECT_PRESENCE_LIMIT, SELECT_RECEIPT_LIMIT,
+    SendingEventType, TransactionStatus,
 };
 use crate::core::device::DeviceListUpdateContent;
 use crate::core::events::receipt::{ReceiptContent, ReceiptData, ReceiptMap, ReceiptType};
@@ -60,36 +62,33 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
     // EDU selection windows of in-flight transactions; persisted as the
     // destination's cursor once the transaction succeeds.
     let mut pending_edu_cursors = HashMap::<OutgoingKind, Seqnum>::new();
+    // Queues this instance sends to; other instances sharing the database
+    // leave them alone while the leases are renewed.
+    let mut leases = Leases::default();
+    if let Err(e) = leases.renew().await {
+        error!(error = ?e, "failed to load sending leases");
+    }
 
     // Retry requests we could not finish yet
-    let mut initial_transactions = HashMap::<OutgoingKind, Vec<SendingEventType>>::new();
-
-    for (id, outgoing_kind, event) in super::active_requests().await? {
-        let entry = initial_transactions
-            .entry(outgoing_kind.clone())
-            .or_default();
-
-        if entry.len() > 512 {
-            error!(
-                "Too many pending events ({}) for {:?}, dropping oldest event {:?}",
-                entry.len(),
-                outgoing_kind,
-                id
-            );
-            super::delete_request(id).await?;
+    for outgoing_kind in super::active_kinds().await? {
+        // Another instance owns the queue and is sending these already.
+        if !leases.acquire(&outgoing_kind).await {
             continue;
         }
 
-        entry.push(event);
-    }
-
-    for (outgoing_kind, events) in initial_transactions {
+        let events = resume_page(&outgoing_kind, persisted_tries(&outgoing_kind).await).await?;
+        if events.is_empty() {
+            leases.release(&outgoing_kind).await;
+            continue;
+        }
         current_transaction_status.insert(outgoing_kind.clone(), TransactionStatus::Running);
-        futures.push(super::send_events(outgoing_kind.clone(), events));
+        futures.push(super::send_events(outgoing_kind, events));
     }
 
     let mut sweep = tokio::time::interval(SWEEP_INTERVAL);
     sweep.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+    let mut renew = tokio::time::interval(RENEW_INTERVAL);
+    renew.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
 
     loop {
         let retry_delay = next_retry_delay(&current_transaction_status);
@@ -109,6 +108,15 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
                             error!(?server_name, error = ?e, "failed to advance edu cursor");
                         }
 
+                        if !leases.holds(&outgoing_kind) || leases.over_share() {
+                            // The lease was lost, or this instance hands the
+                            // queue over to balance the load: the owner picks
+                            // its queued requests up in its sweep.
+                            current_transaction_status.remove(&outgoing_kind);
+                            leases.release(&outgoing_kind).await;
+                            continue;
+                        }
+
                         // Find events that have been added since starting the last request
                         let new_events = super::queued_requests(&outgoing_kind, super::QUEUED_REQUEST_LIMIT).await.unwrap_or_default();
 
@@ -146,6 +154,7 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
                             futures.push(super::send_events(outgoing_kind.clone(), events));
                         } else {
                             current_transaction_status.remove(&outgoing_kind);
+                            leases.release(&outgoing_kind).await;
                         }
                     }
                     Err((outgoing_kind, event)) => {
@@ -192,6 +201,11 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
                     // The periodic sweep already handled this wakeup.
                     continue;
                 }
+                if !leases.acquire(&outgoing_kind).await {
+                    // Owned by another instance, or left for one with fewer
+                    // queues to pick up in its sweep.
+                    continue;
+                }
                 match select_events(
                     &outgoing_kind,
                     new_events,
@@ -201,16 +215,51 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
                     Ok(Some(events)) => {
                         futures.push(super::send_events(outgoing_kind, events));
                     }
-                    Ok(None) => {}
+                    Ok(None) => {
+                        release_if_settled(&mut leases, &current_transaction_status, &outgoing_kind).await;
+                    }
                     Err(e) => {
                         error!(?outgoing_kind, error = ?e, "failed to select queued requests for wakeup");
+                        release_if_settled(&mut leases, &current_transaction_status, &outgoing_kind).await;
                     }
                 }
             }
             _ = sweep.tick() => {
+                // Take over requests an instance claimed but did not finish
+                // before it stopped, once its lease expired.
+                let kinds = match super::active_kinds().await {
+                    Ok(kinds) => kinds,
+                    Err(e) => {
+                        error!(error = ?e, "failed to load active destinations for sweep");
+                        Vec::new()
+                    }
+                };
+                for outgoing_kind in kinds {
+                    if current_transaction_status.contains_key(&outgoing_kind)
+                        || !leases.acquire(&outgoing_kind).await
+                    {
+                        continue;
+                    }
+                    let tries = persisted_tries(&outgoing_kind).await;
+                    let events = match resume_page(&outgoing_kind, tries).await {
+                        Ok(events) => events,
+                        Err(e) => {
+                            error!(?outgoing_kind, error = ?e, "failed to load active requests for sweep");
+                            Vec::new()
+                        }
+                    };
+                    if events.is_empty() {
+                        // Finished by its owner since the query above.
+                        leases.release(&outgoing_kind).await;
+                        continue;
+                    }
+                    current_transaction_status.insert(outgoing_kind.clone(), TransactionStatus::Running);
+                    futures.push(super::send_events(outgoing_kind, events));
+                }
+
                 // Recover destinations whose wakeup was dropped because the
                 // wakeup queue was full, and requests left queued by a
-                // previous process.
+                // previous process or an instance that handed them over.
                 let kinds = match super::queued_kinds().await {
                     Ok(kinds) => kinds,
                     Err(e) => {
@@ -231,7 +280,7 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
                             continue;
                         }
                     };
-                    if new_events.is_empty() {
+                    if new_events.is_empty() || !leases.acquire(&outgoing_kind).await {
                         continue;
                     }
                     match select_events(
@@ -243,13 +292,34 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
                         Ok(Some(events)) => {
                             futures.push(super::send_events(outgoing_kind, events));
                         }
-                        Ok(None) => {}
+                        Ok(None) => {
+                            release_if_settled(&mut leases, &current_transaction_status, &outgoing_kind).await;
+                        }
                         Err(e) => {
                             error!(?outgoing_kind, error = ?e, "failed to select queued requests for sweep");
+                            release_if_settled(&mut leases, &current_transaction_status, &outgoing_kind).await;
                         }
                     }
                 }
             }
+            _ = renew.tick() => {
+                match leases.renew().await {
+                    Ok(lost) => {
+                        for outgoing_kind in lost {
+                            warn!(?outgoing_kind, "lost the sending lease to another instance");
+                            // A running transaction still completes, but stops
+                            // there; a backing-off one is not retried here.
+                            if let Some(TransactionStatus::Failed(..)) = current_transaction_status.get(&outgoing_kind) {
+                                current_transaction_status.remove(&outgoing_kind);
+                                pending_edu_cursors.remove(&outgoing_kind);
+                            }
+                        }
+                    }
+                    Err(e) => {
+                        error!(error = ?e, "failed to renew sending leases");
+                    }
+                }
+            }
             _ = async {
                 if let Some(delay) = retry_delay {
                     tokio::time::sleep(delay).await;
@@ -261,7 +331,7 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
                     .iter()
                     .filter_map(|(outgoing_kind, status)| match status {
                         TransactionStatus::Failed(tries, time)
-                            if time.elapsed() >= retry_backoff(*tries) =>
+                            if time.elapsed() >= retry_backoff(outgoing_kind, *tries) =>
                         {
                             Some((outgoing_kind.clone(), *tries))
                         }
@@ -270,7 +340,11 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
                     .collect::<Vec<_>>();
 
                 for (outgoing_kind, tries) in retry_ready {
-                    let active_events = match super::active_requests_for(&outgoing_kind).await {
+                    if !leases.holds(&outgoing_kind) {
+                        current_transaction_status.remove(&outgoing_kind);
+                        continue;
+                    }
+                    let mut events = match resume_page(&outgoing_kind, tries).await {
                         Ok(events) => events,
                         Err(e) => {
                             error!(
@@ -286,7 +360,6 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
                         }
                     };
 
-                    let mut events = active_events.into_iter().map(|(_, event)| event).collect::<Vec<_>>();
                     // Also retry the EDU window whose delivery failed: it was
                     // dropped from pending_edu_cursors without advancing the
                     // cursor, so it is re-selected here instead of waiting for
@@ -301,6 +374,7 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
 
                     if events.is_empty() {
                         current_transaction_status.remove(&outgoing_kind);
+                        leases.release(&outgoing_kind).await;
                         continue;
                     }
 
@@ -313,7 +387,15 @@ async fn process(mut receiver: mpsc::Receiver<super::WakeupMessage>) -> AppResul
     }
 }
 
-fn retry_backoff(tries: u32) -> Duration {
+fn retry_backoff(outgoing_kind: &OutgoingKind, tries: u32) -> Duration {
+    if let OutgoingKind::Push(..) = outgoing_kind {
+        // Push gateways are retried sooner, notifications are only useful
+        // while they are fresh.
+        let factor = 2u32.saturating_pow(tries.saturating_sub(1));
+        return PUSH_INITIAL_BACKOFF
+            .saturating_mul(factor)
+            .min(PUSH_MAX_BACKOFF);
+    }
     let mut duration = Duration::from_secs(30) * tries * tries;
     if duration > Duration::from_secs(60 * 60 * 24) {
         duration = Duration::from_secs(60 * 60 * 24);
@@ -325,16 +407,31 @@ fn next_retry_delay(
     current_transaction_status: &HashMap<OutgoingKind, TransactionStatus>,
 ) -> Option<Duration> {
     current_transaction_status
-        .values()
-        .filter_map(|status| match status {
+        .iter()
+        .filter_map(|(outgoing_kind, status)| match status {
             TransactionStatus::Failed(tries, time) => {
-                Some(retry_backoff(*tries).saturating_sub(time.elapsed()))
+                Some(retry_backoff(outgoing_kind, *tries).saturating_sub(time.elapsed()))
             }
             _ => None,
         })
         .min()
 }
 
+/// Releases the lease of a queue once it has drained.
+///
+/// `select_events` also returns nothing while a transaction is in flight or
+/// backing off; the lease is kept then, so no other instance resends the same
+/// requests or skips the backoff.
+async fn release_if_settled(
+    leases: &mut Leases,
+    current_transaction_status: &HashMap<OutgoingKind, TransactionStatus>,
+    outgoing_kind: &OutgoingKind,
+) {
+    if !current_transaction_status.contains_key(outgoing_kind) {
+        leases.release(outgoing_kind).await;
+    }
+}
+
 #[tracing::instrument(skip_all)]
 async fn select_events(
     outgoing_kind: &OutgoingKind,
@@ -342,7 +439,7 @@ async fn select_events(
     current_transaction_status: &mut HashMap<OutgoingKind, TransactionStatus>,
     pending_edu_cursors: &mut HashMap<OutgoingKind, Seqnum>,
 ) -> AppResult<Option<Vec<SendingEventType>>> {
-    let mut retry = false;
+    let mut retry = None;
     let mut allow = true;
 
     let entry = current_transaction_status.entry(outgoing_kind.clone());
@@ -354,12 +451,12 @@ async fn select_events(
             }
             TransactionStatus::Failed(tries, time) => {
                 // Fail if a request has failed recently (exponential backoff)
-                let min_elapsed_duration = retry_backoff(*tries);
+                let min_elapsed_duration = retry_backoff(outgoing_kind, *tries);
 
                 if time.elapsed() < min_elapsed_duration {
                     allow = false;
                 } else {
-                    retry = true;
+                    retry = Some(*tries);
                     *e = TransactionStatus::Retrying(*tries);
                 }
             }
@@ -370,15 +467,11 @@ async fn select_events(
         return Ok(None);
     }
 
-    let mut events = Vec::new();
-
-    if retry {
+    let mut events = if let Some(tries) = retry {
         // We retry the previous transaction
-        for (_, e) in super::active_requests_for(outgoing_kind).await? {
-            events.push(e);
-        }
+        resume_page(outgoing_kind, tries).await?
     } else {
-        events = match super::claim_queued_requests(&new_events).await {
+        let events = match super::claim_queued_requests(&new_events).await {
             Ok(events) => events,
             Err(error) => {
                 current_transaction_status.remove(outgoing_kind);
@@ -392,7 +485,8 @@ async fn select_events(
             current_transaction_status.remove(outgoing_kind);
             return Ok(None);
         }
-    }
+        events
+    };
 
     // Piggyback pending EDUs on fresh and retry transactions alike. The
     // cursor only advances once a transaction succeeds, so a retry simply
@@ -407,6 +501,41 @@ async fn select_events(
     Ok(Some(events))
 }
 
+/// Loads the active requests of a queue to send them again, one transaction at
+/// a time.
+///
+/// A federation destination that failed `tries` times in a row has been
+/// unreachable for a while, so it is caught up: only the latest PDU of each
+/// room is kept from its backlog.
+async fn resume_page(outgoing_kind: &OutgoingKind, tries: u32) -> AppResult<Vec<SendingEventType>> {
+    if let OutgoingKind::Normal(server_name) = outgoing_kind
+        && tries >= CATCH_UP_AFTER_TRIES
+    {
+        let dropped = super::catch_up(server_name).await?;
+        if dropped > 0 {
+            info!(
+                %server_name,
+                dropped,
+                "catching up unreachable destination with the latest event of each room"
+            );
+        }
+    }
+    super::active_page_for(outgoing_kind).await
+}
+
+/// Failed attempts of a queue persisted by the instance that sent to it before.
+async fn persisted_tries(outgoing_kind: &OutgoingKind) -> u32 {
+    let OutgoingKind::Normal(server_name) = outgoing_kind else {
+        return 0;
+    };
+    data::sending::destination_retry_count(server_name)
+        .await
+        .unwrap_or_else(|e| {
+            error!(%server_name, error = ?e, "failed to load persisted retry count");
+            0
+        })
+}
+
 /// Persist retry state to the database so other instances can respect backoff.
 async fn persist_retry_state(outgoing_kind: &OutgoingKind, tries: u32) -> AppResult<()> {
     let dest = match outgoing_kind {
@@ -682,9 +811,22 @@ mod tests {
 
     #[test]
     fn retry_backoff_grows_quadratically_and_caps_at_one_day() {
-        assert_eq!(retry_backoff(1), Duration::from_secs(30));
-        assert_eq!(retry_backoff(2), Duration::from_secs(120));
-        assert_eq!(retry_backoff(100), Duration::from_secs(60 * 60 * 24));
+        let kind = OutgoingKind::Normal(OwnedServerName::try_from("remote.example").unwrap());
+        assert_eq!(retry_backoff(&kind, 1), Duration::from_secs(30));
+        assert_eq!(retry_backoff(&kind, 2), Duration::from_secs(120));
+        assert_eq!(retry_backoff(&kind, 100), Duration::from_secs(60 * 60 * 24));
+    }
+
+    #[test]
+    fn push_retry_backoff_doubles_and_caps_at_one_hour() {
+        let kind = OutgoingKind::Push(
+            OwnedUserId::try_from("@alice:example.com").unwrap(),
+            "pushkey".to_owned(),
+        );
+        assert_eq!(retry_backoff(&kind, 1), Duration::from_secs(1));
+        assert_eq!(retry_backoff(&kind, 2), Duration::from_secs(2));
+        assert_eq!(retry_backoff(&kind, 5), Duration::from_secs(16));
+        assert_eq!(retry_backoff(&kind, 100), Duration::from_secs(60 * 60));
     }
 
     #[test]
@@ -0,0 +1,187 @@
+//! Ownership of the sending queues among instances sharing one database.
+//!
+//! An instance only sends to a queue (a federation destination, an appservice
+//! or a pusher) while it holds the queue's lease. Leases are renewed while the
+//! queue has work and released once it drains, so the queues of an instance
+//! that dies are taken over by the others once its leases expire.
+use std::collections::HashSet;
+use std::sync::LazyLock;
+use std::time::Duration;
+
+use super::OutgoingKind;
+use crate::core::UnixMillis;
+use crate::data::sending::DbSendingInstance;
+use crate::{AppResult, config, data, utils};
+
+/// How long a lease stays valid without being renewed.
+pub const LEASE_TTL: Duration = Duration::from_secs(60);
+/// How often held leases are renewed, well within `LEASE_TTL`.
+pub(super) const RENEW_INTERVAL: Duration = Duration::from_secs(20);
+/// Instances and leases silent for this long are removed from the database.
+const FORGET_AFTER: Duration = Duration::from_secs(60 * 60);
+
+static INSTANCE_NAME: LazyLock<String> = LazyLock::new(|| {
+    config::get()
+        .instance_name
+        .clone()
+        .unwrap_or_else(|| utils::random_string(10))
+});
+
+/// Name this instance holds its leases under.
+pub fn instance_name() -> &'static str {
+    &INSTANCE_NAME
+}
+
+/// Instances that renewed their leases recently.
+pub async fn live_instances() -> AppResult<Vec<DbSendingInstance>> {
+    let alive_since = UnixMillis::now().get() as i64 - LEASE_TTL.as_millis() as i64;
+    Ok(data::sending::live_sending_instances(alive_since).await?)
+}
+
+/// The queues this instance holds the lease of.
+#[derive(Debug)]
+pub(super) struct Leases {
+    held: HashSet<OutgoingKind>,
+    /// Queues leased by the other instances at the last renewal.
+    others: usize,
+    /// Live instances at the last renewal, this one included.
+    instances: usize,
+}
+
+impl Default for Leases {
+    fn default() -> Self {
+        Self {
+            held: HashSet::new(),
+            others: 0,
+            instances: 1,
+        }
+    }
+}
+
+impl Leases {
+    pub(super) fn holds(&self, kind: &OutgoingKind) -> bool {
+        self.held.contains(kind)
+    }
+
+    /// Takes the lease of `kind`, unless another instance holds it or this one
+    /// already sends to more than its share of the queues.
+    pub(super) async fn acquire(&mut self, kind: &OutgoingKind) -> bool {
+        if self.held.contains(kind) {
+            return true;
+        }
+        if !self.has_room() {
+            return false;
+        }
+        match data::sending::acquire_sending_lease(
+            &kind.queue_key(),
+            instance_name(),
+            LEASE_TTL.as_millis() as i64,
+        )
+        .await
+        {
+            Ok(true) => {
+                self.held.insert(kind.clone());
+                true
+            }
+            Ok(false) => false,
+            Err(e) => {
+                error!(?kind, error = ?e, "failed to acquire sending lease");
+                false
+            }
+        }
+    }
+
+    /// Gives the lease of `kind` up so another instance can take the queue over.
+    pub(super) async fn release(&mut self, kind: &OutgoingKind) {
+        if self.held.remove(kind)
+            && let Err(e) =
+                data::sending::release_sending_lease(&kind.queue_key(), instance_name()).await
+        {
+            error!(?kind, error = ?e, "failed to release sending lease");
+        }
+    }
+
+    /// Whether this instance may take one more queue without exceeding its share.
+    fn has_room(&self) -> bool {
+        self.held.len() < fair_share(self.others + self.held.len() + 1, self.instances)
+    }
+
+    /// Whether this instance holds clearly more queues than the others, for
+    /// example after an instance joined. Queues are then handed over once
+    /// their current transaction completes.
+    pub(super) fn over_share(&self) -> bool {
+        self.held.len() > fair_share(self.others + self.held.len(), self.instances) + 1
+    }
+
+    /// Renews the held leases and returns the queues whose lease was lost,
+    /// because it expired before being renewed and another instance took it.
+    pub(super) async fn renew(&mut self) -> AppResult<Vec<OutgoingKind>> {
+        let now = UnixMillis::now().get() as i64;
+        data::sending::heartbeat_sending_instance(
+            instance_name(),
+            now - FORGET_AFTER.as_millis() as i64,
+        )
+        .await?;
+
+        let renewed =
+            data::sending::renew_sending_leases(instance_name(), LEASE_TTL.as_millis() as i64)
+                .await?
+                .into_iter()
+                .collect::<HashSet<_>>();
+        let lost = self
+            .held
+            .iter()
+            .filter(|kind| !renewed.contains(&kind.queue_key()))
+            .cloned()
+            .collect::<Vec<_>>();
+        for kind in &lost {
+            self.held.remove(kind);
+        }
+
+        self.instances = live_instances().await?.len().max(1);
+        self.others = data::sending::live_sending_leases()
+            .await?
+            .iter()
+            .filter(|lease| lease.instance_id != instance_name())
+            .count();
+        Ok(lost)
+    }
+}
+
+/// Queues each instance should send to when `queues` are spread over `instances`.
+fn fair_share(queues: usize, instances: usize) -> usize {
+    queues.div_ceil(instances.max(1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn leases(held: usize, others: usize, instances: usize) -> Leases {
+        Leases {
+            held: (0..held)
+                .map(|i| OutgoingKind::Appservice(i.to_string()))
+                .collect(),
+            others,
+            instances,
+        }
+    }
+
+    #[test]
+    fn single_instance_is_never_over_its_share() {
+        let leases = leases(1000, 0, 1);
+        assert!(leases.has_room());
+        assert!(!leases.over_share());
+    }
+
+    #[test]
+    fn instance_hands_queues_over_after_another_joins() {
+        // 20 queues, all on this instance, while a second one joined.
+        assert!(leases(20, 0, 2).over_share());
+        // Once spread evenly nothing moves anymore.
+        assert!(!leases(10, 10, 2).over_share());
+        assert!(!leases(11, 9, 2).over_share());
+        assert!(!leases(11, 9, 2).has_room());
+        assert!(leases(9, 11, 2).has_room());
+    }
+}
@@ -1,14 +1,10 @@
 use std::error::Error as StdError;
 use std::net::SocketAddr;
-use std::str::FromStr;
 use std::sync::{Arc, RwLock};
 use std::{future, iter};
 
-use futures_util::FutureExt;
-use hyper_util::client::legacy::connect::dns::{GaiResolver, Name as HyperName};
 use ipaddress::IPAddress;
 use reqwest::dns::{Addrs, Name, Resolve, Resolving};
-use tower_service::Service as TowerService;
 
 use crate::TlsNameMap;
 
@@ -19,8 +15,9 @@ pub const SESSION_ID_LENGTH: usize = 32;
 pub const AUTO_GEN_PASSWORD_LENGTH: usize = 15;
 pub const RANDOM_USER_ID_LENGTH: usize = 10;
 
+/// Resolves hostnames through the shared, caching DNS resolver configured in the
+/// `dns` section.
 pub struct Resolver {
-    inner: GaiResolver,
     overrides: Arc<RwLock<TlsNameMap>>,
     enforce_cidr_denylist: bool,
 }
@@ -28,7 +25,6 @@ pub struct Resolver {
 impl Resolver {
     pub fn new(overrides: Arc<RwLock<TlsNameMap>>) -> Self {
         Resolver {
-            inner: GaiResolver::new(),
             overrides,
             enforce_cidr_denylist: false,
         }
@@ -43,7 +39,6 @@ impl Resolver {
     /// pre-validate IP-literal URLs (see `crate::utils::url_guard`).
     pub fn new_with_cidr_denylist(overrides: Arc<RwLock<TlsNameMap>>) -> Self {
         Resolver {
-            inner: GaiResolver::new(),
             overrides,
             enforce_cidr_denylist: true,
         }
@@ -81,20 +76,20 @@ impl Resolve for Resolver {
                 })
             })
             .unwrap_or_else(|| {
-                let this = &mut self.inner.clone();
-                Box::pin(
-                    TowerService::<HyperName>::call(
-                        this,
-                        // Beautiful hack, please remove this in the future.
-                        HyperName::from_str(name.as_str())
-                            .expect("reqwest Name is just wrapper for hyper-util Name"),
-                    )
-                    .map(|result| {
-                        result
-                            .map(|addrs| -> Addrs { Box::new(addrs) })
-                            .map_err(|err| -> Box<dyn StdError + Send + Sync> { Box::new(err) })
-                    }),
-                )
+                Box::pin(async move {
+                    let lookup = crate::dns_resolver()
+                        .lookup_ip(name.as_str())
+                        .await
+                        .map_err(|err| -> Box<dyn StdError + Send + Sync> { Box::new(err) })?;
+                    let addrs: Addrs = Box::new(
+                        lookup
+                            .iter()
+                            .map(|ip| SocketAddr::new(ip, 0))
+                            .collect::<Vec<_>>()
+                            .into_iter(),
+                    );
+                    Ok(addrs)
+                })
             });
 
         if !enforce {
@@ -35,6 +35,10 @@ fn merge_signing_keys_for_storage(
 
     keys.verify_keys.extend(new_keys.verify_keys);
     keys.old_verify_keys.extend(new_keys.old_verify_keys);
+    // A key the server retired is no longer one it signs with.
+    let old_verify_keys = &keys.old_verify_keys;
+    keys.verify_keys
+        .retain(|key_id, _| !old_verify_keys.contains_key(key_id));
     for (server, new_sigs) in new_keys.signatures {
         keys.signatures.entry(server).or_default().extend(new_sigs);
     }
@@ -92,15 +96,21 @@ pub async fn verify_keys_for(server: &ServerName) -> VerifyKeys {
         .unwrap_or_default();
 
     if !server.is_remote() {
-        let keypair = config::keypair();
+        let own_keys = crate::signing_keys::own_keys();
         let verify_key = VerifyKey {
-            key: Base64::new(keypair.public_key().to_vec()),
+            key: Base64::new(own_keys.keypair.public_key().to_vec()),
         };
 
-        let id = format!("ed25519:{}", keypair.version());
+        let id = format!("ed25519:{}", own_keys.keypair.version());
         let verify_keys: VerifyKeys = [(id.try_into().expect("should work"), verify_key)].into();
 
         keys.extend(verify_keys);
+        keys.extend(own_keys.old_verify_keys.iter().filter_map(|(id, old)| {
+            Some((
+                id.as_str().try_into().ok()?,
+                VerifyKey::new(old.key.clone()),
+            ))
+        }));
     }
 
     keys
@@ -256,7 +266,7 @@ async fn get_verify_key_from_origin(
 pub fn sign_json(object: &mut CanonicalJsonObject) -> AppResult<()> {
     signatures::sign_json(
         config::get().server_name.as_str(),
-        config::keypair(),
+        &*config::keypair(),
         object,
     )
     .map_err(Into::into)
@@ -269,7 +279,7 @@ pub fn hash_and_sign_event(
     let version_rules = crate::room::get_version_rules(room_version)?;
     signatures::hash_and_sign_event(
         config::get().server_name.as_str(),
-        config::keypair(),
+        &*config::keypair(),
         object,
         &version_rules.redaction,
     )?;
@@ -350,4 +360,29 @@ mod tests {
         assert_eq!(notary_sigs[&key1], "sig-a");
         assert_eq!(notary_sigs[&key2], "sig-b");
     }
+
+    #[test]
+    fn merge_moves_retired_keys_out_of_verify_keys() {
+        let server_name = OwnedServerName::try_from("remote.example").unwrap();
+        let key_id: OwnedServerSigningKeyId = "ed25519:rotated".try_into().unwrap();
+
+        let mut existing = ServerSigningKeys::new(server_name.clone(), UnixMillis(100));
+        existing
+            .verify_keys
+            .insert(key_id.clone(), VerifyKey::from_bytes(vec![1, 2, 3]));
+
+        let mut new_keys = ServerSigningKeys::new(server_name, UnixMillis(200));
+        new_keys.old_verify_keys.insert(
+            key_id.clone(),
+            crate::core::federation::discovery::OldVerifyKey::new(
+                UnixMillis(150),
+                Base64::new(vec![1, 2, 3]),
+            ),
+        );
+
+        let merged = merge_signing_keys_for_storage(Some(existing), new_keys);
+
+        assert!(!merged.verify_keys.contains_key(&key_id));
+        assert!(merged.old_verify_keys.contains_key(&key_id));
+    }
 }
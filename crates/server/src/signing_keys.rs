@@ -1,12 +1,143 @@
 use std::collections::BTreeMap;
+use std::sync::{Arc, LazyLock, RwLock};
 use std::time::{Duration, SystemTime};
 
+use base64::Engine as _;
+use base64::engine::general_purpose::STANDARD;
 use serde::Deserialize;
 
-use crate::config;
 use crate::core::UnixMillis;
 use crate::core::federation::discovery::{OldVerifyKey, ServerSigningKeys, VerifyKey};
 use crate::core::serde::Base64;
+use crate::core::signatures::Ed25519KeyPair;
+use crate::data::misc::DbServerKeypair;
+use crate::{AppError, AppResult, config, data, utils};
+
+/// The keys of this server: the one it signs with, and those it signed with before.
+pub struct OwnKeys {
+    pub keypair: Arc<Ed25519KeyPair>,
+    pub old_verify_keys: BTreeMap<String, OldVerifyKey>,
+}
+
+static OWN_KEYS: LazyLock<RwLock<Arc<OwnKeys>>> = LazyLock::new(|| {
+    let keypair = configured_keypair().unwrap_or_else(utils::generate_keypair);
+    RwLock::new(Arc::new(OwnKeys {
+        keypair: Arc::new(keypair),
+        old_verify_keys: configured_old_keys(),
+    }))
+});
+
+pub fn own_keys() -> Arc<OwnKeys> {
+    OWN_KEYS.read().expect("own keys lock poisoned").clone()
+}
+
+fn configured_keypair() -> Option<Ed25519KeyPair> {
+    let keypair = config::get().keypair.as_ref()?;
+    let bytes = STANDARD
+        .decode(&keypair.document)
+        .expect("server keypair is invalid base64 string");
+    Some(
+        Ed25519KeyPair::from_der(&bytes, keypair.version.clone())
+            .expect("invalid server Ed25519KeyPair"),
+    )
+}
+
+fn configured_old_keys() -> BTreeMap<String, OldVerifyKey> {
+    let Some(keypair) = &config::get().keypair else {
+        return BTreeMap::new();
+    };
+    keypair
+        .old_keys
+        .iter()
+        .map(|old| {
+            let key = Base64::parse(&old.key).expect("old server key is invalid base64 string");
+            (
+                format!("ed25519:{}", old.version),
+                OldVerifyKey::new(old.expired_ts, key),
+            )
+        })
+        .collect()
+}
+
+/// Reloads the keypairs stored in the database.
+///
+/// A stored active keypair supersedes the configured one, which is then published as an
+/// old key. Without either, a keypair is generated and stored so it survives restarts.
+pub async fn refresh() -> AppResult<()> {
+    let stored = data::misc::server_keypairs().await?;
+    let configured = configured_keypair();
+    let active = stored.iter().find(|k| k.expired_at.is_none());
+    if active.is_none() && configured.is_none() {
+        return match rotate().await {
+            Ok(_) => Ok(()),
+            // Another instance starting at the same time stored its keypair first.
+            Err(e) if e.is_unique_violation() => Box::pin(refresh()).await,
+            Err(e) => Err(e),
+        };
+    }
+
+    let mut old_verify_keys = configured_old_keys();
+    for retired in &stored {
+        if let Some(expired_at) = retired.expired_at {
+            let key = Base64::parse(&retired.public_key).map_err(|e| {
+                AppError::internal(format!("invalid stored key {}: {e}", retired.version))
+            })?;
+            old_verify_keys.insert(
+                format!("ed25519:{}", retired.version),
+                OldVerifyKey::new(expired_at, key),
+            );
+        }
+    }
+
+    let keypair = match (active, configured) {
+        (Some(active), configured) => {
+            let bytes = STANDARD.decode(&active.document).map_err(|e| {
+                AppError::internal(format!("invalid stored keypair {}: {e}", active.version))
+            })?;
+            let keypair = Ed25519KeyPair::from_der(&bytes, active.version.clone())?;
+            // The configured key signed until the first stored one was activated.
+            if let Some(configured) = configured
+                && configured.version() != keypair.version()
+                && let Some(expired_ts) = stored.iter().map(|k| k.activated_at).min()
+            {
+                old_verify_keys
+                    .entry(format!("ed25519:{}", configured.version()))
+                    .or_insert_with(|| {
+                        OldVerifyKey::new(expired_ts, Base64::new(configured.public_key().to_vec()))
+                    });
+            }
+            keypair
+        }
+        (None, Some(configured)) => configured,
+        (None, None) => unreachable!("a keypair is generated above"),
+    };
+    old_verify_keys.remove(&format!("ed25519:{}", keypair.version()));
+
+    *OWN_KEYS.write().expect("own keys lock poisoned") = Arc::new(OwnKeys {
+        keypair: Arc::new(keypair),
+        old_verify_keys,
+    });
+    Ok(())
+}
+
+/// Generates a new keypair and starts signing with it, retiring the current one.
+///
+/// Returns the id of the new key.
+pub async fn rotate() -> AppResult<String> {
+    let document = Ed25519KeyPair::generate()?;
+    let keypair = Ed25519KeyPair::from_der(&document, utils::random_string(8))?;
+    let public_key: Base64 = Base64::new(keypair.public_key().to_vec());
+    data::misc::activate_server_keypair(&DbServerKeypair {
+        version: keypair.version().to_owned(),
+        document: STANDARD.encode(document.as_slice()),
+        public_key: public_key.encode(),
+        activated_at: UnixMillis::now(),
+        expired_at: None,
+    })
+    .await?;
+    Box::pin(refresh()).await?;
+    Ok(format!("ed25519:{}", keypair.version()))
+}
 
 /// Similar to ServerSigningKeys, but drops a few unnecessary fields we don't require
 /// post-validation
@@ -20,9 +151,10 @@ pub struct SigningKeys {
 impl SigningKeys {
     /// Creates the SigningKeys struct, using the keys of the current server
     pub fn load_own_keys() -> Self {
+        let own_keys = own_keys();
         let mut keys = Self {
             verify_keys: BTreeMap::new(),
-            old_verify_keys: BTreeMap::new(),
+            old_verify_keys: own_keys.old_verify_keys.clone(),
             valid_until_ts: UnixMillis::from_system_time(
                 SystemTime::now() + Duration::from_secs(7 * 86400),
             )
@@ -30,9 +162,9 @@ impl SigningKeys {
         };
 
         keys.verify_keys.insert(
-            format!("ed25519:{}", config::keypair().version()),
+            format!("ed25519:{}", own_keys.keypair.version()),
             VerifyKey {
-                key: Base64::new(config::keypair().public_key().to_vec()),
+                key: Base64::new(own_keys.keypair.public_key().to_vec()),
             },
         );
 
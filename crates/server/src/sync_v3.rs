@@ -200,6 +200,7 @@ pub async fn sync_events(
             next_batch,
             full_state,
             &filter,
+            args.use_state_after,
             &mut device_list_updates,
             &mut joined_users,
             &mut left_users,
@@ -229,47 +230,11 @@ pub async fn sync_events(
     })
     .collect();
 
-    for left_room in left_rooms.keys() {
-        for user_id in room::joined_users(left_room, None).await? {
-            let mut dont_share_encrypted_room = true;
-            for other_room_id in
-                room::user::shared_rooms(vec![sender_id.to_owned(), user_id.clone()]).await?
-            {
-                let encrypted =
-                    room::get_state(&other_room_id, &StateEventType::RoomEncryption, "", None)
-                        .await
-                        .is_ok();
-                if encrypted {
-                    dont_share_encrypted_room = false;
-                    break;
-                }
-            }
-            // If the user doesn't share an encrypted room with the target anymore, we need to tell
-            // them.
-            if dont_share_encrypted_room {
-                device_list_left.insert(user_id);
-            }
-        }
-    }
-    for user_id in left_users {
-        let mut dont_share_encrypted_room = true;
-        for other_room_id in
-            room::user::shared_rooms(vec![sender_id.to_owned(), user_id.clone()]).await?
-        {
-            let encrypted =
-                room::get_state(&other_room_id, &StateEventType::RoomEncryption, "", None)
-                    .await
-                    .is_ok();
-            if encrypted {
-                dont_share_encrypted_room = false;
-                break;
-            }
-        }
-        // If the user doesn't share an encrypted room with the target anymore, we need to tell
-        // them.
-        if dont_share_encrypted_room {
-            device_list_left.insert(user_id);
-        }
+    // Users who no longer share any room with the sender since the last sync.
+    if let Some(since_tk) = since_tk {
+        device_list_left.extend(
+            room::user::left_shared_users(sender_id, since_tk.event_sn(), Some(curr_sn)).await?,
+        );
     }
 
     let mut knocked_rooms: BTreeMap<_, _> = BTreeMap::default();
@@ -370,7 +335,10 @@ pub async fn sync_events(
             .collect(),
     };
     let device_lists = DeviceLists {
-        changed: device_list_updates.into_iter().collect(),
+        changed: device_list_updates
+            .difference(&device_list_left)
+            .cloned()
+            .collect(),
         left: device_list_left.into_iter().collect(),
     };
 
@@ -396,8 +364,11 @@ pub async fn sync_events(
                 .unwrap_or_default()
         },
         to_device,
-        // Fallback keys are not yet supported
-        device_unused_fallback_key_types: None,
+        device_unused_fallback_key_types: Some(
+            data::user::unused_fallback_key_types(sender_id, device_id)
+                .await
+                .unwrap_or_default(),
+        ),
     };
     Ok(res_body)
 }
@@ -412,7 +383,7 @@ async fn load_joined_room(
     next_batch: BatchToken,
     full_state: bool,
     filter: &FilterDefinition,
-    _use_state_after: bool, // TODO
+    use_state_after: bool,
     device_list_updates: &mut HashSet<OwnedUserId>,
     joined_users: &mut HashSet<OwnedUserId>,
     left_users: &mut HashSet<OwnedUserId>,
@@ -570,8 +541,14 @@ async fn load_joined_room(
                 // Probably since = 0, we will do an initial sync
                 let (joined_member_count, invited_member_count, heroes) =
                     calculate_counts().await?;
-                let current_state_ids =
-                    state::get_full_state_ids(since_frame_id.unwrap_or(current_frame_id)).await?;
+                // With `state_after` the state must match the end of the timeline, which
+                // the timeline itself is not applied on top of.
+                let state_frame_id = if use_state_after {
+                    current_frame_id
+                } else {
+                    since_frame_id.unwrap_or(current_frame_id)
+                };
+                let current_state_ids = state::get_full_state_ids(state_frame_id).await?;
                 let mut state_events = Vec::new();
                 let mut lazy_loaded = HashSet::new();
 
@@ -589,7 +566,9 @@ async fn load_joined_room(
 
                     // skip room name type is just for pass TestSyncOmitsStateChangeOnFilteredEvents
                     // test
-                    if timeline_pdu_ids.contains(&event_id) && event_ty != StateEventType::RoomName
+                    if !use_state_after
+                        && timeline_pdu_ids.contains(&event_id)
+                        && event_ty != StateEventType::RoomName
                     {
                         continue;
                     }
@@ -869,6 +848,16 @@ async fn load_joined_room(
         let receipt = SyncReceiptEvent { content };
         edus.push(RawJson::new(&receipt)?.cast());
     }
+    // Private read receipts are only ever sent back to their owner.
+    if data::room::receipt::last_private_read_update_sn(sender_id, room_id)
+        .await
+        .unwrap_or_default()
+        >= since_tk.event_sn()
+        && let Ok(content) = room::receipt::last_private_read(sender_id, room_id).await
+    {
+        let receipt = SyncReceiptEvent { content };
+        edus.push(RawJson::new(&receipt)?.cast());
+    }
     if room::typing::last_typing_update(room_id).await? >= since_tk.event_sn() {
         edus.push(
             serde_json::from_str(&serde_json::to_string(
@@ -921,13 +910,7 @@ async fn load_joined_room(
                 .map(|(_, pdu)| pdu.to_sync_room_event())
                 .collect(),
         },
-        state: State::Before(
-            state_events
-                .iter()
-                .map(|pdu| pdu.to_sync_state_event())
-                .collect::<Vec<_>>()
-                .into(),
-        ),
+        state: sync_state(&state_events, use_state_after),
         ephemeral: Ephemeral { events: edus },
         unread_thread_notifications: if filter.room.timeline.unread_thread_notifications {
             notify_summary
@@ -960,8 +943,9 @@ async fn load_left_room(
     _until_tk: Option<BatchToken>,
     _left_sn: Seqnum,
     next_batch: BatchToken,
-    _full_state: bool,
+    full_state: bool,
     filter: &FilterDefinition,
+    use_state_after: bool,
     _device_list_updates: &mut HashSet<OwnedUserId>,
     _joined_users: &mut HashSet<OwnedUserId>,
     _left_users: &mut HashSet<OwnedUserId>,
@@ -996,7 +980,11 @@ async fn load_left_room(
                 prev_batch: Some(next_batch.to_string()),
                 events: Vec::new(),
             },
-            state: State::Before(vec![event.to_sync_state_event()].into()),
+            state: if use_state_after {
+                State::After(vec![event.to_sync_state_event()].into())
+            } else {
+                State::Before(vec![event.to_sync_state_event()].into())
+            },
         });
     }
 
@@ -1038,33 +1026,76 @@ async fn load_left_room(
         state::ensure_field_id(&StateEventType::RoomMember, sender_id.as_str()).await?;
     left_state_ids.insert(leave_state_key_id, left_event_id.clone());
 
-    for (key, event_id) in left_state_ids {
-        if let Some(state_limit) = filter.room.state.limit
-            && state_events.len() >= state_limit
-        {
-            break;
-        }
-        if timeline_pdu_ids.contains(&event_id) {
-            continue;
-        }
-        let DbRoomStateField { state_key, .. } = state::get_field(key).await?;
-
-        if *sender_id == state_key {
-            let pdu = match timeline::get_pdu(&event_id).await {
-                Ok(pdu) => pdu,
-                _ => {
-                    warn!(
-                        "pdu in state not found: {} state_key: {}",
-                        event_id, state_key
-                    );
-                    continue;
-                }
+    if use_state_after {
+        // The state at the leave, which ends the timeline, as changed since the last sync.
+        let since_state_ids = if since_tk.event_sn() > 0 && !full_state {
+            match crate::event::get_last_frame_id(room_id, Some(since_tk.event_sn())).await {
+                Ok(since_frame_id) => state::get_full_state_ids(since_frame_id).await?,
+                Err(_) => Default::default(),
+            }
+        } else {
+            Default::default()
+        };
+        let lazy_load_enabled = filter.room.state.lazy_load_options.is_enabled()
+            || filter.room.timeline.lazy_load_options.is_enabled();
+        for (key, event_id) in left_state_ids {
+            if let Some(state_limit) = filter.room.state.limit
+                && state_events.len() >= state_limit
+            {
+                break;
+            }
+            if since_state_ids.get(&key) == Some(&event_id) {
+                continue;
+            }
+            let DbRoomStateField {
+                event_ty,
+                state_key,
+                ..
+            } = state::get_field(key).await?;
+            if event_ty == StateEventType::RoomMember
+                && lazy_load_enabled
+                && *sender_id != state_key
+                && !timeline_users.contains(&state_key)
+            {
+                continue;
+            }
+            let Ok(pdu) = timeline::get_pdu(&event_id).await else {
+                warn!("pdu in state not found: {}", event_id);
+                continue;
             };
-
             if pdu.can_pass_filter(&filter.room.state) {
                 state_events.push(pdu);
             }
         }
+    } else {
+        for (key, event_id) in left_state_ids {
+            if let Some(state_limit) = filter.room.state.limit
+                && state_events.len() >= state_limit
+            {
+                break;
+            }
+            if timeline_pdu_ids.contains(&event_id) {
+                continue;
+            }
+            let DbRoomStateField { state_key, .. } = state::get_field(key).await?;
+
+            if *sender_id == state_key {
+                let pdu = match timeline::get_pdu(&event_id).await {
+                    Ok(pdu) => pdu,
+                    _ => {
+                        warn!(
+                            "pdu in state not found: {} state_key: {}",
+                            event_id, state_key
+                        );
+                        continue;
+                    }
+                };
+
+                if pdu.can_pass_filter(&filter.room.state) {
+                    state_events.push(pdu);
+                }
+            }
+        }
     }
 
     if let Some((_, first_event)) = timeline.events.first()
@@ -1096,16 +1127,25 @@ async fn load_left_room(
                 .map(|(_, pdu)| pdu.to_sync_room_event())
                 .collect(),
         },
-        state: State::Before(
-            state_events
-                .iter()
-                .map(|pdu| pdu.to_sync_state_event())
-                .collect::<Vec<_>>()
-                .into(),
-        ),
+        state: sync_state(&state_events, use_state_after),
     })
 }
 
+/// Wraps the state of a room in the block the client asked for: `state_after` (MSC4222)
+/// holds the state at the end of the timeline, `state` the state at its start.
+fn sync_state(state_events: &[SnPduEvent], use_state_after: bool) -> State {
+    let events = state_events
+        .iter()
+        .map(|pdu| pdu.to_sync_state_event())
+        .collect::<Vec<_>>()
+        .into();
+    if use_state_after {
+        State::After(events)
+    } else {
+        State::Before(events)
+    }
+}
+
 pub struct TimelineData {
     pub events: IndexMap<Seqnum, SnPduEvent>,
     pub limited: bool,
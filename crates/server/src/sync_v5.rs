@@ -417,6 +417,7 @@ pub async fn sync_events(
             typing: collect_typing(sync_info, next_batch, all_rooms.iter().cloned()).await?,
             #[cfg(feature = "unstable-msc4262")]
             profiles: Default::default(),
+            thread_subscriptions: collect_thread_subscriptions(sync_info, next_batch).await?,
         },
     };
 
@@ -1046,7 +1047,6 @@ async fn collect_e2ee(
     if !req_body.extensions.e2ee.enabled.unwrap_or(false) {
         return Ok(sync_events::v5::E2ee::default());
     }
-    let mut left_encrypted_users = HashSet::new(); // Users that have left any encrypted rooms the sender was in
     let mut device_list_changes = HashSet::new();
     let mut device_list_left = HashSet::new();
     // Look for device list updates of this account
@@ -1101,21 +1101,11 @@ async fn collect_e2ee(
                             }
 
                             let content: RoomMemberEventContent = pdu.get_content()?;
-                            match content.membership {
-                                MembershipState::Join => {
-                                    // A new user joined an encrypted room
-                                    if !share_encrypted_room(sender_id, &user_id, Some(room_id))
-                                        .await?
-                                    {
-                                        device_list_changes.insert(user_id.to_owned());
-                                    }
-                                }
-                                MembershipState::Leave => {
-                                    // Write down users that have left encrypted rooms we
-                                    // are in
-                                    left_encrypted_users.insert(user_id.to_owned());
-                                }
-                                _ => {}
+                            // A new user joined an encrypted room
+                            if content.membership == MembershipState::Join
+                                && !share_encrypted_room(sender_id, &user_id, Some(room_id)).await?
+                            {
+                                device_list_changes.insert(user_id.to_owned());
                             }
                         }
                     }
@@ -1146,16 +1136,11 @@ async fn collect_e2ee(
             .extend(crate::room::keys_changed_users(room_id, since_sn, Some(until_sn)).await?);
     }
 
-    for user_id in left_encrypted_users {
-        let Ok(share_encrypted_room) = share_encrypted_room(sender_id, &user_id, None).await else {
-            continue;
-        };
-
-        // If the user doesn't share an encrypted room with the target anymore, we need
-        // to tell them
-        if !share_encrypted_room {
-            device_list_left.insert(user_id);
-        }
+    // Users who no longer share any room with the sender since the last sync.
+    if since_sn > 0 {
+        device_list_left
+            .extend(room::user::left_shared_users(sender_id, since_sn, Some(until_sn)).await?);
+        device_list_changes.retain(|user_id| !device_list_left.contains(user_id));
     }
 
     Ok(E2ee {
@@ -1165,31 +1150,13 @@ async fn collect_e2ee(
         },
         device_one_time_keys_count: data::user::count_one_time_keys(sender_id, device_id).await?,
         device_unused_fallback_key_types: Some(
-            get_unused_fallback_key_types(sender_id, device_id).await,
+            data::user::unused_fallback_key_types(sender_id, device_id)
+                .await
+                .unwrap_or_default(),
         ),
     })
 }
 
-async fn get_unused_fallback_key_types(
-    user_id: &UserId,
-    device_id: &DeviceId,
-) -> Vec<DeviceKeyAlgorithm> {
-    let algos = match connect().await {
-        Ok(mut conn) => e2e_fallback_keys::table
-            .filter(e2e_fallback_keys::user_id.eq(user_id.as_str()))
-            .filter(e2e_fallback_keys::device_id.eq(device_id.as_str()))
-            .filter(e2e_fallback_keys::used_at.is_null())
-            .select(e2e_fallback_keys::algorithm)
-            .load::<String>(&mut conn)
-            .await
-            .ok(),
-        Err(_) => None,
-    };
-    algos
-        .map(|algos| algos.into_iter().map(|a| a.into()).collect())
-        .unwrap_or_default()
-}
-
 async fn collect_to_device(
     SyncInfo {
         sender_id,
@@ -1229,6 +1196,37 @@ fn collect_receipts(SyncInfo { req_body, .. }: SyncInfo<'_>) -> sync_events::v5:
     }
 }
 
+async fn collect_thread_subscriptions(
+    SyncInfo {
+        sender_id,
+        since_sn,
+        req_body,
+        ..
+    }: SyncInfo<'_>,
+    next_batch: Seqnum,
+) -> AppResult<sync_events::v5::ThreadSubscriptions> {
+    let config = &req_body.extensions.thread_subscriptions;
+    if !config.enabled.unwrap_or(false) {
+        return Ok(sync_events::v5::ThreadSubscriptions::default());
+    }
+    let limit = config.limit.unwrap_or(100).clamp(1, 100) as i64;
+
+    // Newest changes since the last `pos`; older ones are left to `/thread_subscriptions`
+    // starting at `prev_batch`.
+    let changes = crate::room::thread::subscription_changes(
+        sender_id,
+        Some(next_batch),
+        Some(since_sn - 1),
+        limit,
+    )
+    .await?;
+    Ok(sync_events::v5::ThreadSubscriptions {
+        subscribed: changes.subscribed,
+        unsubscribed: changes.unsubscribed,
+        prev_batch: changes.end,
+    })
+}
+
 async fn collect_typing<'a, Rooms>(
     SyncInfo { req_body, .. }: SyncInfo<'_>,
     _next_batch: Seqnum,
@@ -1402,6 +1400,14 @@ pub async fn update_sync_request_with_cache(
             &mut req_body.extensions.receipts.lists,
             cached.extensions.receipts.lists.clone(),
         );
+        some_or_sticky(
+            &mut req_body.extensions.thread_subscriptions.enabled,
+            cached.extensions.thread_subscriptions.enabled,
+        );
+        some_or_sticky(
+            &mut req_body.extensions.thread_subscriptions.limit,
+            cached.extensions.thread_subscriptions.limit,
+        );
 
         cached.extensions = req_body.extensions.clone();
         let known = cached.known_rooms.clone();
@@ -0,0 +1,111 @@
+//! Certificates of the TLS listeners, reloaded when their files change.
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
+
+use futures_util::Stream;
+use rustls::crypto::ring::sign::any_supported_type;
+use rustls::pki_types::pem::PemObject;
+use rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use rustls::sign::CertifiedKey;
+use salvo::conn::rustls::{Keycert, RustlsConfig};
+use tokio::sync::watch;
+
+use crate::config::TlsConfig;
+use crate::{AppError, AppResult};
+
+/// Bumped on every [`reload`]. Each listener holds a receiver, so a reload asked
+/// while it is loading certificates is still seen once it is done.
+static RELOAD: LazyLock<watch::Sender<u64>> = LazyLock::new(|| watch::Sender::new(0));
+
+/// Asks every TLS listener to load its certificates again.
+pub fn reload() {
+    RELOAD.send_modify(|generation| *generation += 1);
+}
+
+/// Loads the certificates of a listener, checking each one matches its key.
+pub fn load_config(conf: &TlsConfig) -> AppResult<RustlsConfig> {
+    let mut config = RustlsConfig::new(load_keycert(&conf.cert, &conf.key)?);
+    for sni in &conf.sni {
+        config = config.keycert(sni.domain.clone(), load_keycert(&sni.cert, &sni.key)?);
+    }
+    Ok(config)
+}
+
+fn load_keycert(cert_path: &str, key_path: &str) -> AppResult<Keycert> {
+    let cert = std::fs::read(cert_path)?;
+    let key = std::fs::read(key_path)?;
+
+    let cert_chain = CertificateDer::pem_slice_iter(&cert)
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| AppError::internal(format!("invalid certificate {cert_path}: {e}")))?;
+    if cert_chain.is_empty() {
+        return Err(AppError::internal(format!(
+            "no certificate found in {cert_path}"
+        )));
+    }
+    let private_key = PrivateKeyDer::from_pem_slice(&key)
+        .map_err(|e| AppError::internal(format!("invalid private key {key_path}: {e}")))?;
+    let signing_key = any_supported_type(&private_key)
+        .map_err(|e| AppError::internal(format!("unsupported private key {key_path}: {e}")))?;
+    CertifiedKey::new(cert_chain, signing_key)
+        .keys_match()
+        .map_err(|e| {
+            AppError::internal(format!(
+                "certificate {cert_path} does not match key {key_path}: {e}"
+            ))
+        })?;
+
+    Ok(Keycert::new().cert(cert).key(key))
+}
+
+/// The certificates of a listener: `initial`, then every valid set loaded after a
+/// certificate file changed or [`reload`] was called.
+///
+/// Certificates failing validation are logged and skipped, the listener keeps serving
+/// the previous ones.
+pub fn config_stream(
+    conf: &'static TlsConfig,
+    initial: RustlsConfig,
+) -> impl Stream<Item = RustlsConfig> + Send + 'static {
+    let state = (Some(initial), modified_times(conf), RELOAD.subscribe());
+    futures_util::stream::unfold(
+        state,
+        move |(initial, mut last_modified, mut reloads)| async move {
+            if let Some(initial) = initial {
+                return Some((initial, (None, last_modified, reloads)));
+            }
+            loop {
+                let reload_requested = if conf.reload_interval == 0 {
+                    reloads.changed().await.is_ok()
+                } else {
+                    tokio::select! {
+                        changed = reloads.changed() => changed.is_ok(),
+                        _ = tokio::time::sleep(Duration::from_secs(conf.reload_interval)) => false,
+                    }
+                };
+                let modified = modified_times(conf);
+                if !reload_requested && modified == last_modified {
+                    continue;
+                }
+                last_modified = modified;
+                match load_config(conf) {
+                    Ok(config) => {
+                        tracing::info!("Reloaded TLS certificate {}", conf.cert);
+                        return Some((config, (None, last_modified, reloads)));
+                    }
+                    Err(e) => {
+                        tracing::error!("Keeping the current TLS certificates: {e}");
+                    }
+                }
+            }
+        },
+    )
+}
+
+fn modified_times(conf: &TlsConfig) -> Vec<Option<SystemTime>> {
+    let modified = |path: &str| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    std::iter::once((&conf.cert, &conf.key))
+        .chain(conf.sni.iter().map(|sni| (&sni.cert, &sni.key)))
+        .flat_map(|(cert, key)| [modified(cert), modified(key)])
+        .collect()
+}
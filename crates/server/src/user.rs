@@ -1,12 +1,14 @@
 mod password;
 use palpo_data::user::set_display_name;
 pub use password::*;
+pub mod directory;
 pub mod key;
 pub mod pusher;
 pub use key::*;
 pub mod presence;
 // mod ldap;
 // pub use ldap::*;
+pub mod export;
 pub mod session;
 use std::collections::BTreeSet;
 use std::mem;
@@ -159,9 +161,14 @@ pub async fn ignored_users(recipient_id: &UserId) -> BTreeSet<OwnedUserId> {
 /// - Removing avatar URL and blurhash
 /// - Removing all profile data
 /// - Leaving all rooms (and forgets all of them)
+///
+/// With `erase`, the user's local media, account data, push rules, key backups
+/// and profile fields are deleted as well before returning. Their events are then
+/// pruned in the background, resumed by [`resume_erasures`] after a restart.
 pub async fn full_user_deactivate(
     user_id: &UserId,
     all_joined_rooms: &[OwnedRoomId],
+    erase: bool,
 ) -> AppResult<()> {
     data::user::deactivate(user_id).await?;
     if let Err(e) = remove_all_devices(user_id).await {
@@ -169,11 +176,43 @@ pub async fn full_user_deactivate(
     }
     data::user::delete_profile(user_id).await.ok();
 
-    // TODO: remove all user data
-    // for profile_key in data::user::all_profile_keys(user_id) {
-    //     data::user::set_profile_key(user_id, &profile_key, None);
-    // }
+    if erase {
+        data::user::add_pending_erasure(user_id).await?;
+    }
+    // Leave first, so the user stops being visible as a member right away.
+    leave_all_joined_rooms(user_id, all_joined_rooms).await?;
+    if erase {
+        delete_all_media(user_id).await?;
+        data::user::erase(user_id).await?;
+        info!("Erased data of {user_id} as part of account deactivation");
+        spawn_event_pruning(user_id.to_owned());
+    }
+    Ok(())
+}
+
+/// Resume the pruning of the events of users erased before the last restart.
+pub async fn resume_erasures() -> AppResult<()> {
+    for user_id in data::user::pending_erasures().await? {
+        spawn_event_pruning(user_id);
+    }
+    Ok(())
+}
+
+fn spawn_event_pruning(user_id: OwnedUserId) {
+    tokio::spawn(async move {
+        match prune_all_events(&user_id).await {
+            Ok(()) => info!("Pruned the events of erased user {user_id}"),
+            Err(e) => tracing::error!(%user_id, "failed to prune events of erased user: {e}"),
+        }
+    });
+}
 
+/// Demotes the deactivated user's own power level where allowed and leaves
+/// all their rooms.
+async fn leave_all_joined_rooms(
+    user_id: &UserId,
+    all_joined_rooms: &[OwnedRoomId],
+) -> AppResult<()> {
     for room_id in all_joined_rooms {
         let state_lock = room::lock_state(room_id).await;
 
@@ -224,6 +263,22 @@ pub async fn full_user_deactivate(
     Ok(())
 }
 
+/// Prune every event the erased user sent, their memberships included, so other
+/// users stop seeing their messages and profile.
+///
+/// The user left their rooms and can no longer send redactions, so only the copies
+/// on this server are pruned. The pending erasure is cleared once all are.
+async fn prune_all_events(user_id: &UserId) -> AppResult<()> {
+    for (room_id, event_id) in data::user::unredacted_event_ids(user_id).await? {
+        // ignore errors so erasure doesn't stop at the first failing event
+        if let Err(e) = timeline::prune_pdu(&event_id).await {
+            warn!(%room_id, %event_id, "Failed to prune event of erased user: {e}");
+        }
+    }
+    data::user::remove_pending_erasure(user_id).await?;
+    Ok(())
+}
+
 pub async fn remove_device(user_id: &UserId, device_id: &DeviceId) -> AppResult<()> {
     data::user::device::remove_device(user_id, device_id).await?;
     crate::user::key::mark_device_key_update(user_id, device_id).await?;
@@ -0,0 +1,88 @@
+use crate::config::UserDirectoryScope;
+use crate::core::client::user_directory::{SearchUsersResBody, SearchedUser};
+use crate::core::events::StateEventType;
+use crate::core::events::room::join_rule::RoomJoinRulesEventContent;
+use crate::core::events::room::member::{MembershipState, RoomMemberEventContent};
+use crate::core::identifiers::*;
+use crate::core::room::JoinRule;
+use crate::data::user::directory::{DirectoryVisibility, NewDbUserDirectoryEntry};
+use crate::{AppResult, IsRemoteOrLocal, PduEvent, config, data, room};
+
+/// Search the user directory as `searcher`, honouring the configured search scope.
+pub async fn search(
+    searcher: &UserId,
+    search_term: &str,
+    limit: usize,
+) -> AppResult<SearchUsersResBody> {
+    let conf = &config::get().user_directory;
+    let visibility = DirectoryVisibility {
+        public_rooms: conf.search_scope != UserDirectoryScope::SharedRooms,
+        local_users: conf.search_scope == UserDirectoryScope::AllLocalUsers,
+        appservice_users: conf.include_appservice_users,
+    };
+    // Users in an exclusive appservice namespace are not always registered by the
+    // appservice itself, so they are matched by user ID.
+    let excluded_users = if conf.include_appservice_users {
+        Vec::new()
+    } else {
+        crate::appservice::all()
+            .await?
+            .values()
+            .flat_map(|info| info.exclusive_user_patterns())
+            .collect()
+    };
+
+    let mut results = data::user::directory::search(
+        searcher,
+        search_term,
+        visibility,
+        &excluded_users,
+        limit as i64 + 1,
+    )
+    .await?
+    .into_iter()
+    .map(|entry| SearchedUser {
+        user_id: entry.user_id,
+        display_name: entry.display_name,
+        avatar_url: entry.avatar_url,
+    })
+    .collect::<Vec<_>>();
+    let limited = results.len() > limit;
+    results.truncate(limit);
+    Ok(SearchUsersResBody { results, limited })
+}
+
+/// Keep the directory entry of a remote user in line with their latest join event.
+///
+/// Local users are indexed from their global profile instead.
+pub async fn update_member_profile(pdu: &PduEvent, user_id: &UserId) -> AppResult<()> {
+    if user_id.is_local() {
+        return Ok(());
+    }
+    let content = pdu.get_content::<RoomMemberEventContent>()?;
+    if content.membership != MembershipState::Join {
+        return Ok(());
+    }
+    data::user::directory::upsert_entry(&NewDbUserDirectoryEntry::new(
+        user_id.to_owned(),
+        content.display_name,
+        content.avatar_url,
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Refresh whether the members of a room are visible to every searcher.
+pub async fn update_public_room(room_id: &RoomId) -> AppResult<()> {
+    let public = room::get_state_content::<RoomJoinRulesEventContent>(
+        room_id,
+        &StateEventType::RoomJoinRules,
+        "",
+        None,
+    )
+    .await
+    .map(|c| c.join_rule == JoinRule::Public)
+    .unwrap_or(false);
+    data::user::directory::set_public_room(room_id, public).await?;
+    Ok(())
+}
@@ -0,0 +1,240 @@
+//! Export of everything stored about a user, for data-subject access requests.
+//!
+//! The export is a gzip-compressed tar archive containing:
+//!
+//! - `user.json`: account details and third-party identifiers
+//! - `profile.json`: global and per-room profiles
+//! - `rooms.json`: the user's latest membership in every room
+//! - `messages.jsonl`: every event the user sent, one per line
+//! - `media.json`: the media the user uploaded
+//! - `devices.json`: the user's devices
+//! - `account_data.json`: global and per-room account data
+
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+
+use flate2::Compression;
+use flate2::write::GzEncoder;
+use serde_json::json;
+use tempfile::NamedTempFile;
+use tokio::io::AsyncWriteExt;
+
+use crate::core::UnixMillis;
+use crate::core::identifiers::*;
+use crate::{AppError, AppResult, data};
+
+const MESSAGE_BATCH_SIZE: i64 = 1000;
+
+/// Build the export archive of a user.
+///
+/// The archive is written to a temporary file, removed once the returned handle
+/// is dropped, so exports of users with a long history are never held in memory.
+/// The data is loaded first, then packed on a blocking thread.
+pub async fn export_user_data(user_id: &UserId) -> AppResult<NamedTempFile> {
+    let mut entries = Vec::new();
+
+    let user = data::user::get_user(user_id).await?;
+    let threepids: Vec<_> = data::user::get_threepids(user_id)
+        .await?
+        .into_iter()
+        .map(|t| {
+            json!({
+                "medium": t.medium,
+                "address": t.address,
+                "added_at": t.added_at,
+                "validated_at": t.validated_at,
+            })
+        })
+        .collect();
+    entries.push(ExportEntry::json(
+        "user.json",
+        &json!({
+            "user_id": user.id,
+            "is_admin": user.is_admin,
+            "is_guest": user.is_guest,
+            "created_at": user.created_at,
+            "deactivated_at": user.deactivated_at,
+            "consent_at": user.consent_at,
+            "consent_version": user.consent_version,
+            "threepids": threepids,
+        }),
+    )?);
+
+    let profiles: Vec<_> = data::user::get_profiles(user_id)
+        .await?
+        .into_iter()
+        .map(|p| {
+            json!({
+                "room_id": p.room_id,
+                "displayname": p.display_name,
+                "avatar_url": p.avatar_url,
+                "blurhash": p.blurhash,
+                "fields": p.fields,
+            })
+        })
+        .collect();
+    entries.push(ExportEntry::json("profile.json", &profiles)?);
+
+    let rooms: Vec<_> = data::user::room_memberships(user_id)
+        .await?
+        .into_iter()
+        .map(|(room_id, membership)| json!({ "room_id": room_id, "membership": membership }))
+        .collect();
+    entries.push(ExportEntry::json("rooms.json", &rooms)?);
+
+    // The size of an entry precedes its content, so the messages are collected
+    // in a file of their own first.
+    let messages = blocking(|| Ok(tempfile::tempfile()?)).await?;
+    let mut messages = tokio::io::BufWriter::new(tokio::fs::File::from_std(messages));
+    let mut since_sn = 0;
+    loop {
+        let batch = data::user::sent_events(user_id, since_sn, MESSAGE_BATCH_SIZE).await?;
+        let Some((last_sn, _)) = batch.last() else {
+            break;
+        };
+        since_sn = *last_sn;
+        for (_, event) in &batch {
+            let mut line = serde_json::to_vec(event)?;
+            line.push(b'\n');
+            messages.write_all(&line).await?;
+        }
+        if (batch.len() as i64) < MESSAGE_BATCH_SIZE {
+            break;
+        }
+    }
+    messages.flush().await?;
+    let messages = messages.into_inner().into_std().await;
+    entries.push(ExportEntry::File("messages.jsonl", messages));
+
+    let media: Vec<_> = data::media::list_media_created_by(user_id)
+        .await?
+        .into_iter()
+        .map(|(server_name, media_id)| format!("mxc://{server_name}/{media_id}"))
+        .collect();
+    entries.push(ExportEntry::json("media.json", &media)?);
+
+    let devices: Vec<_> = data::user::device::get_devices(user_id)
+        .await?
+        .into_iter()
+        .map(|d| {
+            json!({
+                "device_id": d.device_id,
+                "display_name": d.display_name,
+                "user_agent": d.user_agent,
+                "last_seen_ip": d.last_seen_ip,
+                "last_seen_ts": d.last_seen_at,
+                "created_at": d.created_at,
+            })
+        })
+        .collect();
+    entries.push(ExportEntry::json("devices.json", &devices)?);
+
+    entries.push(ExportEntry::json(
+        "account_data.json",
+        &json!({
+            "global": data::user::get_global_account_data(user_id).await?,
+            "rooms": data::user::get_room_account_data(user_id).await?,
+        }),
+    )?);
+
+    blocking(move || {
+        let archive_file = NamedTempFile::new()?;
+        let mut archive = tar::Builder::new(GzEncoder::new(
+            BufWriter::new(archive_file.reopen()?),
+            Compression::default(),
+        ));
+        let mtime = UnixMillis::now().get() / 1000;
+        for entry in entries {
+            match entry {
+                ExportEntry::Json(path, content) => append(
+                    &mut archive,
+                    mtime,
+                    path,
+                    content.len() as u64,
+                    content.as_slice(),
+                )?,
+                ExportEntry::File(path, mut file) => {
+                    let size = file.seek(SeekFrom::End(0))?;
+                    file.rewind()?;
+                    append(&mut archive, mtime, path, size, file)?;
+                }
+            }
+        }
+        archive.into_inner()?.finish()?.flush()?;
+        Ok(archive_file)
+    })
+    .await
+}
+
+/// A file of the archive.
+enum ExportEntry {
+    Json(&'static str, Vec<u8>),
+    /// Content too large to be held in memory, spooled to a temporary file.
+    File(&'static str, std::fs::File),
+}
+
+impl ExportEntry {
+    fn json<T: serde::Serialize>(path: &'static str, value: &T) -> AppResult<Self> {
+        Ok(Self::Json(path, serde_json::to_vec_pretty(value)?))
+    }
+}
+
+/// Runs file I/O on a blocking thread.
+async fn blocking<T: Send + 'static>(
+    f: impl FnOnce() -> AppResult<T> + Send + 'static,
+) -> AppResult<T> {
+    tokio::task::spawn_blocking(f)
+        .await
+        .map_err(|e| AppError::internal(format!("export task failed: {e}")))?
+}
+
+fn append(
+    archive: &mut tar::Builder<impl Write>,
+    mtime: u64,
+    path: &str,
+    size: u64,
+    content: impl Read,
+) -> AppResult<()> {
+    let mut header = tar::Header::new_ustar();
+    header.set_size(size);
+    header.set_mode(0o644);
+    header.set_mtime(mtime);
+    archive.append_data(&mut header, path, content)?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn archive_entries_read_back() {
+        let mut archive = tar::Builder::new(Vec::new());
+        let user = serde_json::to_vec_pretty(&json!({ "user_id": "@alice:example.org" })).unwrap();
+        append(
+            &mut archive,
+            0,
+            "user.json",
+            user.len() as u64,
+            user.as_slice(),
+        )
+        .unwrap();
+        append(&mut archive, 0, "messages.jsonl", 5, b"hello".as_slice()).unwrap();
+        let bytes = archive.into_inner().unwrap();
+
+        let mut entries = tar::Archive::new(bytes.as_slice());
+        let mut contents = Vec::new();
+        for entry in entries.entries().unwrap() {
+            let mut entry = entry.unwrap();
+            let path = entry.path().unwrap().display().to_string();
+            let mut content = String::new();
+            entry.read_to_string(&mut content).unwrap();
+            contents.push((path, content));
+        }
+        assert_eq!(contents[0].0, "user.json");
+        assert!(contents[0].1.contains("@alice:example.org"));
+        assert_eq!(
+            contents[1],
+            ("messages.jsonl".to_owned(), "hello".to_owned())
+        );
+    }
+}
@@ -702,6 +702,35 @@ pub async fn mark_device_key_update(user_id: &UserId, _device_id: &DeviceId) ->
     Ok(())
 }
 
+/// Stop tracking the device list of a remote user once no local user shares a room with them.
+///
+/// Their cached device keys are dropped, so a later query fetches them again over federation.
+pub async fn untrack_remote_device_list(user_id: &UserId) -> AppResult<bool> {
+    if user_id.is_local() || crate::room::user::shares_room_with_local_users(user_id).await? {
+        return Ok(false);
+    }
+    data::user::key::delete_all_device_keys(user_id).await?;
+    Ok(true)
+}
+
+/// Untrack the remote users that `user_id` leaving `room_id` left without a room shared with
+/// a local user.
+pub async fn untrack_remote_device_lists_after_leave(
+    room_id: &RoomId,
+    user_id: &UserId,
+) -> AppResult<()> {
+    if !user_id.is_local() {
+        untrack_remote_device_list(user_id).await?;
+    } else {
+        let untracked =
+            data::room::remote_members_without_local_rooms(room_id, config::server_name()).await?;
+        for member in untracked {
+            data::user::key::delete_all_device_keys(&member).await?;
+        }
+    }
+    Ok(())
+}
+
 pub async fn mark_device_key_update_with_joined_rooms(
     user_id: &UserId,
     _device_id: &DeviceId,
@@ -25,13 +25,15 @@ pub async fn verify_password(user: &DbUser, password: &str) -> AppResult<()> {
         return Err(MatrixError::user_deactivated("the user has been deactivated").into());
     }
 
-    let hash_matches = argon2::verify_encoded(&hash, password.as_bytes()).unwrap_or(false);
-
-    if !hash_matches {
-        Err(MatrixError::unauthorized("wrong username or password.").into())
-    } else {
-        Ok(())
+    if !crate::utils::verify_password_hash(&hash, password) {
+        return Err(MatrixError::unauthorized("wrong username or password.").into());
+    }
+    if crate::utils::is_legacy_password_hash(&hash)
+        && let Err(e) = set_password(&user.id, password).await
+    {
+        warn!(user_id = %user.id, "failed to upgrade legacy password hash: {e}");
     }
+    Ok(())
 }
 
 pub async fn get_password_hash(user_id: &UserId) -> AppResult<String> {
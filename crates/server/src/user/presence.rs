@@ -1,54 +1,73 @@
 use crate::core::federation::transaction::Edu;
 use crate::core::presence::{PresenceContent, PresenceState, PresenceUpdate};
 use crate::core::{UnixMillis, UserId};
-use crate::data::user::{NewDbPresence, last_presence};
+use crate::data::user::{DbPresence, NewDbPresence};
 use crate::{AppResult, config, data, sending};
 
+/// Activity more recent than this is not written again, to spare the database.
+const REFRESH_TIMEOUT: u64 = 60 * 1000;
+
 /// Resets the presence timeout, so the user will stay in their current presence state.
 pub async fn ping_presence(user_id: &UserId, new_state: &PresenceState) -> AppResult<()> {
     if !config::get().presence.allow_local {
         return Ok(());
     }
 
-    const REFRESH_TIMEOUT: u64 = 60 * 1000;
-
-    let last_presence = last_presence(user_id).await;
-    let state_changed = match last_presence {
-        Err(_) => true,
-        Ok(ref presence) => presence.content.presence != *new_state,
-    };
+    let now = UnixMillis::now();
+    let last_presence = data::user::get_presence(user_id).await?;
+    if let Some(presence) = &last_presence
+        && presence_state(presence) == *new_state
+    {
+        let recently_active = presence
+            .last_active_at
+            .is_some_and(|at| now.0.saturating_sub(at.0) < REFRESH_TIMEOUT);
+        if !recently_active {
+            data::user::touch_presence(user_id, Some(now), None).await?;
+        }
+        return Ok(());
+    }
 
-    let last_last_active_ago = match last_presence {
-        Err(_) => 0_u64,
-        Ok(ref presence) => presence.content.last_active_ago.unwrap_or_default(),
-    };
+    let status_msg = last_presence.and_then(|p| p.status_msg);
+    update_presence(user_id, new_state.clone(), status_msg, Some(now)).await
+}
 
-    if !state_changed && last_last_active_ago < REFRESH_TIMEOUT {
+/// Records that a local user synced, setting them to `set_presence` as requested.
+///
+/// Syncing keeps a user from going offline, but does not count as activity: an idle
+/// user that keeps syncing stays unavailable.
+pub async fn sync_presence(user_id: &UserId, set_presence: &PresenceState) -> AppResult<()> {
+    if !config::get().presence.allow_local {
         return Ok(());
     }
 
-    let _status_msg = match last_presence {
-        Ok(presence) => presence.content.status_msg.clone(),
-        Err(_) => Some(String::new()),
+    let now = UnixMillis::now();
+    let last_presence = data::user::get_presence(user_id).await?;
+    let last_state = last_presence.as_ref().map(presence_state);
+    let new_state = match (set_presence, &last_state) {
+        // `offline` asks for the sync not to change the user's presence.
+        (PresenceState::Offline, _) => None,
+        (PresenceState::Online, Some(PresenceState::Online | PresenceState::Unavailable)) => None,
+        (state, last_state) if last_state.as_ref() == Some(state) => None,
+        (state, _) => Some(state.clone()),
     };
 
-    let currently_active = *new_state == PresenceState::Online;
-
-    data::user::set_presence(
-        NewDbPresence {
-            user_id: user_id.to_owned(),
-            stream_id: None,
-            state: Some(new_state.to_string()),
-            status_msg: None,
-            last_active_at: Some(UnixMillis::now()),
-            last_federation_update_at: None,
-            last_user_sync_at: None,
-            currently_active: Some(currently_active),
-            occur_sn: None,
-        },
-        false,
-    )
-    .await?;
+    if let Some(new_state) = new_state {
+        let last_active_at = if new_state == PresenceState::Online {
+            Some(now)
+        } else {
+            last_presence.as_ref().and_then(|p| p.last_active_at)
+        };
+        let status_msg = last_presence.and_then(|p| p.status_msg);
+        return update_presence(user_id, new_state, status_msg, last_active_at).await;
+    }
+    if let Some(presence) = &last_presence {
+        let recently_synced = presence
+            .last_user_sync_at
+            .is_some_and(|at| now.0.saturating_sub(at.0) < REFRESH_TIMEOUT);
+        if !recently_synced {
+            data::user::touch_presence(user_id, None, Some(now)).await?;
+        }
+    }
     Ok(())
 }
 
@@ -67,41 +86,167 @@ pub async fn set_presence(
         data::user::remove_presence(sender_id).await?;
         return Ok(false);
     };
+    let now = UnixMillis::now();
     let db_presence = NewDbPresence {
         user_id: sender_id.to_owned(),
         stream_id: None,
         state: Some(presence_state.to_string()),
         status_msg: status_msg.clone(),
-        last_active_at: None,
+        last_active_at: Some(now),
         last_federation_update_at: None,
-        last_user_sync_at: None,
+        last_user_sync_at: Some(now),
         currently_active: Some(presence_state == PresenceState::Online),
         occur_sn: None,
     };
 
     let state_changed = data::user::set_presence(db_presence, force).await?;
     if state_changed {
-        let edu = Edu::Presence(PresenceContent {
-            push: vec![PresenceUpdate {
-                user_id: sender_id.to_owned(),
-                status_msg,
-                last_active_ago: 0,
-                currently_active: presence_state == PresenceState::Online,
-                presence: presence_state,
-                #[cfg(feature = "unstable-msc4495")]
-                recipients: Default::default(),
-                #[cfg(feature = "unstable-msc4495")]
-                stream_id: None,
-                #[cfg(feature = "unstable-msc4495")]
-                prev_id: None,
-            }],
-        });
-
-        let joined_rooms = data::user::joined_rooms(sender_id).await?;
-        let remote_servers = data::room::joined_servers_for_rooms(&joined_rooms).await?;
-
-        sending::send_edu_servers(remote_servers.into_iter(), &edu).await?;
+        send_presence_edu(presence_update(
+            sender_id,
+            presence_state.clone(),
+            status_msg,
+            0,
+            presence_state == PresenceState::Online,
+        ))
+        .await?;
     }
 
     Ok(state_changed)
 }
+
+/// Moves users whose presence timed out to unavailable or offline, as configured by
+/// `idle_timeout` and `offline_timeout`.
+///
+/// Every server instance may run this: each transition is applied, and federated, once.
+pub async fn expire_presences() -> AppResult<()> {
+    let conf = &config::get().presence;
+    let now = UnixMillis::now();
+
+    if conf.allow_local {
+        let idle_before = UnixMillis(now.0.saturating_sub(conf.idle_timeout));
+        for presence in data::user::idle_local_presences(idle_before).await? {
+            if let Err(e) = announce_timeout(&presence, PresenceState::Unavailable, now).await {
+                warn!(
+                    "failed to announce idle presence of {}: {e}",
+                    presence.user_id
+                );
+            }
+        }
+        let offline_before = UnixMillis(now.0.saturating_sub(conf.offline_timeout));
+        for presence in data::user::offline_local_presences(offline_before).await? {
+            if let Err(e) = announce_timeout(&presence, PresenceState::Offline, now).await {
+                warn!(
+                    "failed to announce offline presence of {}: {e}",
+                    presence.user_id
+                );
+            }
+        }
+    }
+
+    // Remote servers announce their own users' transitions, this only catches users
+    // whose server went quiet.
+    if conf.allow_incoming && conf.timeout_remote_users {
+        let offline_before = UnixMillis(now.0.saturating_sub(conf.offline_timeout));
+        let count = data::user::offline_remote_presences(offline_before).await?;
+        if count > 0 {
+            debug!("timed out presence of {count} remote users");
+        }
+    }
+    Ok(())
+}
+
+async fn announce_timeout(
+    presence: &DbPresence,
+    state: PresenceState,
+    now: UnixMillis,
+) -> AppResult<()> {
+    let last_active_ago = presence
+        .last_active_at
+        .map(|at| now.0.saturating_sub(at.0))
+        .unwrap_or_default();
+    send_presence_edu(presence_update(
+        &presence.user_id,
+        state,
+        presence.status_msg.clone(),
+        last_active_ago,
+        false,
+    ))
+    .await
+}
+
+async fn update_presence(
+    user_id: &UserId,
+    state: PresenceState,
+    status_msg: Option<String>,
+    last_active_at: Option<UnixMillis>,
+) -> AppResult<()> {
+    let currently_active = state == PresenceState::Online;
+    let state_changed = data::user::set_presence(
+        NewDbPresence {
+            user_id: user_id.to_owned(),
+            stream_id: None,
+            state: Some(state.to_string()),
+            status_msg: status_msg.clone(),
+            last_active_at,
+            last_federation_update_at: None,
+            last_user_sync_at: Some(UnixMillis::now()),
+            currently_active: Some(currently_active),
+            occur_sn: None,
+        },
+        false,
+    )
+    .await?;
+    if state_changed {
+        send_presence_edu(presence_update(
+            user_id,
+            state,
+            status_msg,
+            0,
+            currently_active,
+        ))
+        .await?;
+    }
+    Ok(())
+}
+
+fn presence_state(presence: &DbPresence) -> PresenceState {
+    presence
+        .state
+        .as_deref()
+        .map(PresenceState::from)
+        .unwrap_or_default()
+}
+
+fn presence_update(
+    user_id: &UserId,
+    presence: PresenceState,
+    status_msg: Option<String>,
+    last_active_ago: u64,
+    currently_active: bool,
+) -> PresenceUpdate {
+    PresenceUpdate {
+        user_id: user_id.to_owned(),
+        presence,
+        status_msg,
+        last_active_ago,
+        currently_active,
+        #[cfg(feature = "unstable-msc4495")]
+        recipients: Default::default(),
+        #[cfg(feature = "unstable-msc4495")]
+        stream_id: None,
+        #[cfg(feature = "unstable-msc4495")]
+        prev_id: None,
+    }
+}
+
+/// Sends a presence update of a local user to the servers sharing a room with them.
+async fn send_presence_edu(update: PresenceUpdate) -> AppResult<()> {
+    if !config::get().presence.allow_outgoing {
+        return Ok(());
+    }
+    let joined_rooms = data::user::joined_rooms(&update.user_id).await?;
+    let remote_servers = data::room::joined_servers_for_rooms(&joined_rooms).await?;
+    let edu = Edu::Presence(PresenceContent { push: vec![update] });
+    sending::send_edu_servers(remote_servers.into_iter(), &edu).await?;
+    Ok(())
+}
@@ -7,6 +7,7 @@ use crate::core::events::TimelineEventType;
 use crate::core::identifiers::*;
 use crate::core::push::push_gateway::{
     Device, Notification, NotificationCounts, NotificationPriority, SendEventNotificationReqBody,
+    SendEventNotificationResBody,
 };
 use crate::core::push::{
     Action, HighlightTweakValue, PushFormat, Pusher, PusherKind, Ruleset, Tweak,
@@ -28,12 +29,14 @@ pub async fn set_pusher(authed: &AuthedInfo, pusher: PusherAction) -> AppResult<
                         device_display_name,
                         lang,
                         profile_tag,
-                        ..
+                        enabled,
                     },
                 append,
             } = data;
-            if !append {
-                data::user::pusher::delete_pusher(authed.user_id(), &app_id, &pushkey).await?;
+            for owner in data::user::pusher::pusher_owners(&app_id, &pushkey).await? {
+                if replaces_pusher(authed.user_id(), &owner, append) {
+                    data::user::pusher::delete_pusher(&owner, &app_id, &pushkey).await?;
+                }
             }
             data::user::pusher::insert_pusher(&NewDbPusher {
                 user_id: authed.user_id().to_owned(),
@@ -47,7 +50,7 @@ pub async fn set_pusher(authed: &AuthedInfo, pusher: PusherAction) -> AppResult<
                 pushkey,
                 lang,
                 data: kind.json_data()?,
-                enabled: true, // TODO
+                enabled,
                 created_at: UnixMillis::now(),
             })
             .await?;
@@ -59,6 +62,15 @@ pub async fn set_pusher(authed: &AuthedInfo, pusher: PusherAction) -> AppResult<
     Ok(())
 }
 
+/// Whether a pusher `user_id` sets replaces the pusher of `owner` with the same
+/// app id and pushkey.
+///
+/// The user's own pusher is always updated. Without `append` the pushkey also
+/// stops notifying other users.
+fn replaces_pusher(user_id: &UserId, owner: &UserId, append: bool) -> bool {
+    owner == user_id || !append
+}
+
 // #[tracing::instrument(skip(destination, request))]
 // pub async fn send_request<T: OutgoingRequest>(destination: &str, request: T) ->
 // AppResult<T::IncomingResponse> where
@@ -122,6 +134,10 @@ pub async fn set_pusher(authed: &AuthedInfo, pusher: PusherAction) -> AppResult<
 //     }
 // }
 
+/// Notifies `pusher` about `pdu` if the push rules of `user` ask for it.
+///
+/// Returns the pushkeys the push gateway rejected, whose pushers must be
+/// removed.
 #[tracing::instrument(skip(user, unread, pusher, ruleset, pdu))]
 pub async fn send_push_notice(
     user: &UserId,
@@ -129,7 +145,7 @@ pub async fn send_push_notice(
     pusher: &Pusher,
     ruleset: Ruleset,
     pdu: &PduEvent,
-) -> AppResult<()> {
+) -> AppResult<Vec<String>> {
     let mut notify = None;
     let mut tweaks = Vec::new();
     let power_levels = room::get_power_levels(&pdu.room_id).await?;
@@ -160,11 +176,11 @@ pub async fn send_push_notice(
     }
 
     if notify == Some(true) {
-        send_notice(unread, pusher, tweaks, pdu).await?;
+        send_notice(unread, pusher, tweaks, pdu).await
+    } else {
+        // The event triggered no actions
+        Ok(Vec::new())
     }
-    // Else the event triggered no actions
-
-    Ok(())
 }
 
 #[tracing::instrument(skip_all)]
@@ -173,7 +189,7 @@ async fn send_notice(
     pusher: &Pusher,
     tweaks: Vec<Tweak>,
     event: &PduEvent,
-) -> AppResult<()> {
+) -> AppResult<Vec<String>> {
     // TODO: email
     match &pusher.kind {
         PusherKind::Http(http) => {
@@ -217,14 +233,8 @@ async fn send_notice(
             // at connect time by `push_gateway_client`'s safe DNS resolver.
             let url = Url::parse(&http.url)?;
             url_guard::ensure_safe_outbound_url(&url)?;
-            let push_client = sending::push_gateway_client();
-
-            if event_id_only {
-                crate::sending::post(url)
-                    .stuff(SendEventNotificationReqBody::new(notification))?
-                    .send_by_client::<()>(push_client)
-                    .await?;
-            } else {
+
+            if !event_id_only {
                 notification.sender = Some(event.sender.clone());
                 notification.event_type = Some(event.event_ty.clone());
                 notification.content = serde_json::value::to_raw_value(&event.content).ok();
@@ -235,17 +245,48 @@ async fn send_notice(
                 notification.sender_display_name =
                     data::user::display_name(&event.sender).await.ok().flatten();
                 notification.room_name = room::get_name(&event.room_id).await.ok();
-
-                crate::sending::post(url)
-                    .stuff(SendEventNotificationReqBody::new(notification))?
-                    .send_by_client::<()>(push_client)
-                    .await?;
             }
 
-            Ok(())
+            notify_gateway(url, notification).await
         }
         // TODO: Handle email
-        PusherKind::Email(_) => Ok(()),
-        _ => Ok(()),
+        PusherKind::Email(_) => Ok(Vec::new()),
+        _ => Ok(Vec::new()),
+    }
+}
+
+/// Posts `notification` to the push gateway at `url`, returning the pushkeys it
+/// rejected.
+async fn notify_gateway(url: Url, notification: Notification) -> AppResult<Vec<String>> {
+    let request = sending::post(url)
+        .stuff(SendEventNotificationReqBody::new(notification))?
+        .into_inner();
+    let response = sending::push_gateway_client().execute(request).await?;
+    let status = response.status();
+    if !status.is_success() {
+        return Err(AppError::internal(format!(
+            "push gateway responded with {status}"
+        )));
+    }
+    Ok(response
+        .json::<SendEventNotificationResBody>()
+        .await?
+        .rejected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::replaces_pusher;
+    use crate::core::user_id;
+
+    #[test]
+    fn append_keeps_the_pushers_of_other_users() {
+        let alice = user_id!("@alice:example.org");
+        let bob = user_id!("@bob:example.org");
+
+        assert!(replaces_pusher(alice, alice, true));
+        assert!(replaces_pusher(alice, alice, false));
+        assert!(!replaces_pusher(alice, bob, true));
+        assert!(replaces_pusher(alice, bob, false));
     }
 }
@@ -54,6 +54,24 @@ pub fn hash_password(password: &str) -> Result<String, argon2::Error> {
     argon2::hash_encoded(password.as_bytes(), salt.as_bytes(), &hashing_conf)
 }
 
+/// Checks a password against a stored hash.
+///
+/// Besides our own argon2 hashes this accepts bcrypt hashes, which is what
+/// accounts imported from Synapse carry until their next login.
+pub fn verify_password_hash(hash: &str, password: &str) -> bool {
+    if is_legacy_password_hash(hash) {
+        bcrypt::verify(password, hash).unwrap_or(false)
+    } else {
+        argon2::verify_encoded(hash, password.as_bytes()).unwrap_or(false)
+    }
+}
+
+/// Whether the hash was produced by another server and should be replaced with
+/// an argon2 hash once the password is known.
+pub fn is_legacy_password_hash(hash: &str) -> bool {
+    hash.starts_with("$2")
+}
+
 #[tracing::instrument(skip(keys))]
 pub fn hash_keys<'a, T, I>(keys: I) -> Vec<u8>
 where